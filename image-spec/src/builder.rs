@@ -0,0 +1,459 @@
+//! Builder for editing images consistently.
+//!
+//! An image is described by its configuration and its manifest, which refer to each other: the
+//! manifest lists the layers and the digest of the configuration, and the configuration lists the
+//! DiffIDs and the history of the same layers. [`ImageBuilder`] edits both at once, so that they
+//! stay consistent.
+//!
+//! This module requires the `builder` feature (enabled by default).
+//!
+//! [`ImageBuilder`]: struct.ImageBuilder.html
+
+use std::{error::Error, fmt};
+
+use chrono::{DateTime, FixedOffset};
+use environ_str::EnvVar;
+
+use crate::{
+    config::{History, Port},
+    digest::Algorithm,
+    Descriptor, Digest, Image, Manifest, MediaType,
+};
+
+/// Builder for editing an image, consisting of its configuration and manifest.
+///
+/// # Examples
+///
+/// ```
+/// use oci_image_spec::{annotation_keys, builder::ImageBuilder, config::Port, Image, Manifest};
+///
+/// # let image: Image = serde_json::from_str(
+/// #     r#"{"created":"2019-08-01T00:00:00Z","architecture":"amd64","os":"linux",
+/// #         "rootfs":{"type":"layers","diff_ids":[]}}"#,
+/// # ).unwrap();
+/// # let manifest: Manifest = serde_json::from_str(
+/// #     r#"{"schemaVersion":2,"config":{"mediaType":"application/vnd.oci.image.config.v1+json",
+/// #         "digest":"sha256:6c3c624b58dbbcd3c0dd82b4c53f04194d1247c6eebdaab7c610cf7d66709b3b",
+/// #         "size":0},"layers":[]}"#,
+/// # ).unwrap();
+/// let built = ImageBuilder::new(image, manifest)
+///     .entrypoint(vec!["/bin/server"])
+///     .cmd(vec!["--port", "8080"])
+///     .expose(Port::Tcp { port: 8080 })
+///     .label(annotation_keys::VERSION, "1.0.0")
+///     .build()
+///     .unwrap();
+///
+/// assert!(built.manifest.config.digest.verify(&built.config_blob[..]).unwrap());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ImageBuilder {
+    image: Image,
+    manifest: Manifest,
+}
+
+/// Image built by an `ImageBuilder`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuiltImage {
+    /// Configuration of the image.
+    pub image: Image,
+    /// Serialized configuration, which MUST be stored as the blob referenced by the manifest.
+    pub config_blob: Vec<u8>,
+    /// Manifest of the image.
+    pub manifest: Manifest,
+}
+
+/// Error type that can be returned when failed to build an image.
+#[derive(Debug)]
+pub enum BuildError {
+    /// Numbers of layers, DiffIDs, and history entries of non-empty layers do not match.
+    InconsistentLayers {
+        /// Number of layers in the manifest.
+        layers: usize,
+        /// Number of DiffIDs in the configuration.
+        diff_ids: usize,
+        /// Number of history entries which are not marked as empty layers.
+        history: usize,
+    },
+    /// Failed to serialize the configuration.
+    Serialize(serde_json::Error),
+}
+
+impl ImageBuilder {
+    /// Creates a builder editing `image` and `manifest`.
+    pub fn new(image: Image, manifest: Manifest) -> Self {
+        Self { image, manifest }
+    }
+
+    /// Sets the date and time on which the image was created.
+    pub fn created(mut self, created: DateTime<FixedOffset>) -> Self {
+        self.image.created = created;
+        self
+    }
+
+    /// Sets the author of the image.
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.image.author = Some(author.into());
+        self
+    }
+
+    /// Sets the user which the process runs as.
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        self.config().user = Some(user.into());
+        self
+    }
+
+    /// Sets the entry point of a container.
+    pub fn entrypoint<I, S>(mut self, entrypoint: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config().entrypoint = entrypoint.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the default arguments to the entry point.
+    pub fn cmd<I, S>(mut self, cmd: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config().cmd = cmd.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the working directory of the entry point process.
+    pub fn working_dir(mut self, working_dir: impl Into<String>) -> Self {
+        self.config().working_dir = Some(working_dir.into());
+        self
+    }
+
+    /// Adds an environment variable, replacing any variable with the same name.
+    pub fn env(mut self, env_var: EnvVar) -> Self {
+        let env = &mut self.config().env;
+        match env.iter_mut().find(|e| e.name == env_var.name) {
+            Some(e) => *e = env_var,
+            None => env.push(env_var),
+        }
+        self
+    }
+
+    /// Adds a port to expose.
+    pub fn expose(mut self, port: Port) -> Self {
+        self.config().exposed_ports.insert(port);
+        self
+    }
+
+    /// Adds a volume.
+    pub fn volume(mut self, volume: impl Into<String>) -> Self {
+        self.config().volumes.insert(volume.into());
+        self
+    }
+
+    /// Sets a label, such as one of the keys in [`annotation_keys`].
+    ///
+    /// [`annotation_keys`]: ../annotation_keys/index.html
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config().labels.insert(key.into(), value.into());
+        self
+    }
+
+    /// Appends a layer with its DiffID and history.
+    ///
+    /// If `history` has no creation time, the creation time of the image is used. The creation
+    /// time of the image is updated if `history` is newer.
+    ///
+    /// If the image has layers but no history, history entries without any information are added
+    /// for the existing layers first, as history MUST cover all layers if present.
+    pub fn layer(mut self, layer: Descriptor, diff_id: Digest, mut history: History) -> Self {
+        history.empty_layer = None;
+        self.manifest.layers.push(layer);
+        self.image.rootfs.diff_ids.push(diff_id);
+        self.push_history(history)
+    }
+
+    /// Appends history which did not create a layer, such as a change of the configuration.
+    ///
+    /// The creation time and missing history of existing layers are handled in the same way as
+    /// [`layer`].
+    ///
+    /// [`layer`]: #method.layer
+    pub fn empty_layer(self, mut history: History) -> Self {
+        history.empty_layer = Some(true);
+        self.push_history(history)
+    }
+
+    /// Serializes the configuration and updates the manifest to reference it.
+    ///
+    /// # Errors
+    ///
+    /// If the layers are not consistent with the DiffIDs and the history, or the configuration
+    /// cannot be serialized, `Err(BuildError)` is returned.
+    pub fn build(self) -> Result<BuiltImage, BuildError> {
+        use sha2::Digest as _;
+
+        let Self {
+            image,
+            mut manifest,
+        } = self;
+
+        let layers = manifest.layers.len();
+        let diff_ids = image.rootfs.diff_ids.len();
+        let history = image
+            .history
+            .iter()
+            .filter(|h| h.empty_layer != Some(true))
+            .count();
+        // History is optional, but it MUST cover all layers if present
+        if layers != diff_ids || (!image.history.is_empty() && layers != history) {
+            return Err(BuildError::InconsistentLayers {
+                layers,
+                diff_ids,
+                history,
+            });
+        }
+
+        let config_blob = serde_json::to_vec(&image).map_err(BuildError::Serialize)?;
+
+        let config = &mut manifest.config;
+        config.media_type = MediaType::ImageConfig;
+        config.digest = Digest {
+            algorithm: Algorithm::Sha256,
+            encoded: hex::encode(sha2::Sha256::digest(&config_blob)),
+        };
+        config.size = config_blob.len() as u64;
+
+        Ok(BuiltImage {
+            image,
+            config_blob,
+            manifest,
+        })
+    }
+
+    fn config(&mut self) -> &mut crate::Config {
+        self.image.config.get_or_insert_with(Default::default)
+    }
+
+    fn push_history(mut self, mut history: History) -> Self {
+        if self.image.history.is_empty() {
+            // Layers before the one `history` is for, if any
+            let layers = self.image.rootfs.diff_ids.len();
+            let existing = if history.empty_layer == Some(true) {
+                layers
+            } else {
+                layers.saturating_sub(1)
+            };
+            let placeholder = History {
+                created: None,
+                author: None,
+                created_by: None,
+                comment: None,
+                empty_layer: None,
+            };
+            self.image.history = vec![placeholder; existing];
+        }
+
+        match history.created {
+            Some(created) if created > self.image.created => self.image.created = created,
+            Some(_) => {}
+            None => history.created = Some(self.image.created),
+        }
+        self.image.history.push(history);
+        self
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InconsistentLayers {
+                layers,
+                diff_ids,
+                history,
+            } => write!(
+                f,
+                "Inconsistent layers: {} layers, {} DiffIDs, and {} history entries",
+                layers, diff_ids, history
+            ),
+            Self::Serialize(e) => write!(f, "Failed to serialize configuration: {}", e),
+        }
+    }
+}
+
+impl Error for BuildError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InconsistentLayers { .. } => None,
+            Self::Serialize(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{annotation_keys, config::RootFs, descriptor::Architecture, descriptor::Os};
+
+    fn time(s: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(s).unwrap()
+    }
+
+    fn digest(n: u64) -> Digest {
+        format!("sha256:{:064x}", n).parse().unwrap()
+    }
+
+    fn base() -> ImageBuilder {
+        let image = Image {
+            created: time("2019-08-01T00:00:00Z"),
+            author: None,
+            architecture: Architecture::Amd64,
+            os: Os::Linux,
+            config: None,
+            rootfs: RootFs {
+                type_: crate::config::TYPE_LAYERS.to_string(),
+                diff_ids: vec![],
+            },
+            history: vec![],
+        };
+        let manifest = Manifest {
+            schema_version: crate::SCHEMA_VERSION,
+            config: layer(0),
+            layers: vec![],
+            annotations: Default::default(),
+        };
+        ImageBuilder::new(image, manifest)
+    }
+
+    fn layer(n: u64) -> Descriptor {
+        Descriptor {
+            media_type: MediaType::LayerTarGzip,
+            digest: digest(n),
+            size: n,
+            urls: vec![],
+            annotations: Default::default(),
+            platform: None,
+        }
+    }
+
+    fn history(created: Option<&str>) -> History {
+        History {
+            created: created.map(time),
+            author: None,
+            created_by: Some("test".to_string()),
+            comment: None,
+            empty_layer: None,
+        }
+    }
+
+    #[test]
+    fn test_image_builder_config() {
+        let env_var = |name: &str, value: &str| EnvVar {
+            name: name.to_string(),
+            value: value.to_string(),
+        };
+
+        let built = base()
+            .entrypoint(vec!["/bin/sh", "-c"])
+            .cmd(vec!["echo"])
+            .env(env_var("PATH", "/bin"))
+            .env(env_var("HOME", "/root"))
+            .env(env_var("PATH", "/usr/bin"))
+            .expose(Port::Tcp { port: 80 })
+            .expose(Port::Tcp { port: 80 })
+            .label(annotation_keys::TITLE, "test")
+            .build()
+            .unwrap();
+
+        let config = built.image.config.unwrap();
+        assert_eq!(config.entrypoint, vec!["/bin/sh", "-c"]);
+        assert_eq!(config.cmd, vec!["echo"]);
+        assert_eq!(
+            config.env,
+            vec![env_var("PATH", "/usr/bin"), env_var("HOME", "/root")]
+        );
+        assert_eq!(
+            config.exposed_ports,
+            [Port::Tcp { port: 80 }].iter().copied().collect()
+        );
+        assert_eq!(config.labels[annotation_keys::TITLE], "test");
+    }
+
+    #[test]
+    fn test_image_builder_layers() {
+        let built = base()
+            .layer(layer(1), digest(11), history(None))
+            .empty_layer(history(Some("2019-08-02T00:00:00Z")))
+            .layer(layer(2), digest(12), history(Some("2019-08-03T00:00:00Z")))
+            .build()
+            .unwrap();
+
+        assert_eq!(built.manifest.layers, vec![layer(1), layer(2)]);
+        assert_eq!(built.image.rootfs.diff_ids, vec![digest(11), digest(12)]);
+
+        let history = &built.image.history;
+        assert_eq!(history[0].created, Some(time("2019-08-01T00:00:00Z")));
+        assert_eq!(history[1].empty_layer, Some(true));
+        assert_eq!(history[2].empty_layer, None);
+        assert_eq!(built.image.created, time("2019-08-03T00:00:00Z"));
+    }
+
+    #[test]
+    fn test_image_builder_base_layers_without_history() {
+        let mut builder = base();
+        builder.manifest.layers = vec![layer(1), layer(2)];
+        builder.image.rootfs.diff_ids = vec![digest(11), digest(12)];
+
+        let built = builder
+            .empty_layer(history(None))
+            .layer(layer(3), digest(13), history(None))
+            .build()
+            .unwrap();
+
+        let entries = &built.image.history;
+        assert_eq!(entries.len(), 4);
+        for h in &entries[..2] {
+            assert_eq!(h.created_by, None);
+            assert_eq!(h.empty_layer, None);
+        }
+        assert_eq!(entries[2].empty_layer, Some(true));
+        assert_eq!(entries[3].created_by, Some("test".to_string()));
+
+        let mut builder = base();
+        builder.manifest.layers = vec![layer(1)];
+        builder.image.rootfs.diff_ids = vec![digest(11)];
+        let built = builder
+            .layer(layer(2), digest(12), history(None))
+            .build()
+            .unwrap();
+        assert_eq!(built.image.history.len(), 2);
+    }
+
+    #[test]
+    fn test_image_builder_config_digest() {
+        let built = base().author("alice").build().unwrap();
+
+        let config = &built.manifest.config;
+        assert_eq!(config.media_type, MediaType::ImageConfig);
+        assert_eq!(config.size, built.config_blob.len() as u64);
+        assert!(config.digest.verify(&built.config_blob[..]).unwrap());
+
+        let image: Image = serde_json::from_slice(&built.config_blob).unwrap();
+        assert_eq!(image, built.image);
+    }
+
+    #[test]
+    fn err_image_builder_inconsistent() {
+        let mut builder = base().layer(layer(1), digest(11), history(None));
+        builder.image.rootfs.diff_ids.clear();
+
+        match builder.build() {
+            Err(BuildError::InconsistentLayers {
+                layers: 1,
+                diff_ids: 0,
+                history: 1,
+            }) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
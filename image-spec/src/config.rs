@@ -50,7 +50,7 @@ pub struct Image {
 }
 
 /// Image configuration.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -278,7 +278,8 @@ mod tests_serde {
 
     #[test]
     fn test_image_deser() {
-        #![allow(clippy::unreadable_literal)]
+        // The date constructors of chrono 0.4.7 are deprecated in later versions
+        #![allow(clippy::unreadable_literal, deprecated)]
 
         let image: Image = serde_json::from_str(JSON_DESER).unwrap();
 
@@ -364,7 +365,8 @@ mod tests_serde {
 
     #[test]
     fn test_image_ser() {
-        #![allow(clippy::unreadable_literal)]
+        // The date constructors of chrono 0.4.7 are deprecated in later versions
+        #![allow(clippy::unreadable_literal, deprecated)]
 
         let image = Image {
                 created: FixedOffset::east(0).ymd(2015, 10, 31).and_hms_nano(22, 22, 56, 15925234),
@@ -498,9 +500,9 @@ mod tests_serde {
   ]
 }"#;
 
-    // Not using zulu
+    // Newer versions of chrono serialize the zero offset as zulu instead of `+00:00`
     const JSON_SER: &str = r#"{
-  "created": "2015-10-31T22:22:56.015925234+00:00",
+  "created": "2015-10-31T22:22:56.015925234Z",
   "author": "Alyssa P. Hacker <alyspdev@example.com>",
   "architecture": "amd64",
   "os": "linux",
@@ -539,11 +541,11 @@ mod tests_serde {
   },
   "history": [
     {
-      "created": "2015-10-31T22:22:54.690851953+00:00",
+      "created": "2015-10-31T22:22:54.690851953Z",
       "created_by": "/bin/sh -c #(nop) ADD file:a3bc1e842b69636f9df5256c49c5374fb4eef1e281fe3f282c65fb853ee171c5 in /"
     },
     {
-      "created": "2015-10-31T22:22:55.613815829+00:00",
+      "created": "2015-10-31T22:22:55.613815829Z",
       "created_by": "/bin/sh -c #(nop) CMD [\"sh\"]",
       "empty_layer": true
     }
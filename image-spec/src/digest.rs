@@ -281,19 +281,19 @@ mod tests {
             algorithm: Sha512,
             encoded: "f7fbba6e0636f890e56fbbf3283e524c6fa3204ae298382d624741d0dc6638326e282c41be5e4254d8820772c5518a2c5a8c0c7f7eda19594a7eb539453e1ed7".to_string(),
         };
-        assert_eq!(digest.verify(content).unwrap(), true);
+        assert!(digest.verify(content).unwrap());
 
         let digest = Digest {
             algorithm: Sha256,
             encoded: "1c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae".to_string(),
         };
-        assert_eq!(digest.verify(content).unwrap(), false);
+        assert!(!digest.verify(content).unwrap());
 
         let digest = Digest {
             algorithm: Sha512,
             encoded: "g7fbba6e0636f890e56fbbf3283e524c6fa3204ae298382d624741d0dc6638326e282c41be5e4254d8820772c5518a2c5a8c0c7f7eda19594a7eb539453e1ed7".to_string(),
         };
-        assert_eq!(digest.verify(content).unwrap(), false);
+        assert!(!digest.verify(content).unwrap());
     }
 
     #[test]
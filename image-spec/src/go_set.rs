@@ -18,6 +18,18 @@ impl<T: Eq + Hash> GoSet<T> {
     pub fn is_empty(&self) -> bool {
         self.inner.is_empty()
     }
+
+    /// Returns whether this set contains `value`.
+    pub fn contains(&self, value: &T) -> bool {
+        self.inner.contains(value)
+    }
+
+    /// Adds `value` to this set.
+    ///
+    /// Returns whether the value was newly inserted.
+    pub fn insert(&mut self, value: T) -> bool {
+        self.inner.insert(value)
+    }
 }
 
 impl<T: Eq + Hash> Default for GoSet<T> {
@@ -16,7 +16,6 @@
     unused
 )]
 
-#[macro_use]
 macro_rules! impl_string_conversion {
     ($enum: ident, $err: ident, $( ($v: ident, $s: expr) ),* $(,)?) => {
         impl std::fmt::Display for $enum {
@@ -40,7 +39,6 @@ macro_rules! impl_string_conversion {
     }
 }
 
-#[macro_use]
 macro_rules! impl_string_conversion_other {
     ($enum: ident, $( ($v: ident, $s: expr) ),* $(,)?) => {
         impl std::fmt::Display for $enum {
@@ -65,7 +63,6 @@ macro_rules! impl_string_conversion_other {
     }
 }
 
-#[macro_use]
 macro_rules! impl_serde_with_string_conversion {
     ($s: ident) => {
         #[cfg(feature = "serde")]
@@ -86,6 +83,8 @@ macro_rules! impl_serde_with_string_conversion {
 }
 
 pub mod annotation_keys;
+#[cfg(feature = "builder")]
+pub mod builder;
 pub mod config;
 pub mod descriptor;
 pub mod digest;
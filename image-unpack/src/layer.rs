@@ -0,0 +1,1200 @@
+//! Extracting a single layer.
+//!
+//! See the [OCI image spec] for more information about layer changesets.
+//!
+//! [OCI image spec]: https://github.com/opencontainers/image-spec/blob/v1.0.1/layer.md
+
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs,
+    io::{self, Read},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{symlink, MetadataExt, PermissionsExt},
+    },
+    path::{Path, PathBuf},
+};
+
+use flate2::read::GzDecoder;
+use image_spec::{Descriptor, MediaType};
+use tar::{Archive, EntryType, Header};
+
+use crate::{
+    blob_path,
+    policy::{Rule, Violation, ViolationKind},
+    resolve::{self, Escape},
+    rootless::{Resource, ROOTLESS_XATTR},
+    sys, Error, Options,
+};
+
+/// Prefix of the file name of a whiteout file.
+pub const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// File name of an opaque whiteout file.
+pub const WHITEOUT_OPAQUE: &str = ".wh..wh..opq";
+
+/// Extended attribute marking a directory as opaque for overlayfs.
+pub const OVERLAY_OPAQUE_XATTR: &str = "trusted.overlay.opaque";
+
+/// Extended attribute marking a directory as opaque for overlayfs mounted with the `userxattr`
+/// option, which is used instead of [`OVERLAY_OPAQUE_XATTR`] in rootless mode.
+///
+/// [`OVERLAY_OPAQUE_XATTR`]: constant.OVERLAY_OPAQUE_XATTR.html
+pub const OVERLAY_USER_OPAQUE_XATTR: &str = "user.overlay.opaque";
+
+/// How whiteout files in a layer are applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WhiteoutMode {
+    /// Remove whited-out files from the destination directory, which holds the lower layers.
+    Remove,
+    /// Convert whiteout files into overlayfs whiteouts, i.e. character devices with device number
+    /// 0/0 and the `trusted.overlay.opaque` extended attribute (`user.overlay.opaque` in rootless
+    /// mode) on opaque directories.
+    Overlay,
+}
+
+/// Opens the layer blob referenced by `descriptor` in the image layout at `image`.
+///
+/// Compressed layers are decompressed transparently.
+///
+/// # Errors
+///
+/// If the media type of the layer is not supported, `Err(Error::UnsupportedMediaType)` is returned.
+pub fn open(image: &Path, descriptor: &Descriptor) -> Result<Box<dyn Read>, Error> {
+    let open = || fs::File::open(blob_path(image, &descriptor.digest));
+
+    match descriptor.media_type {
+        MediaType::LayerTar | MediaType::LayerTarNondistributable => Ok(Box::new(open()?)),
+        MediaType::LayerTarGzip | MediaType::LayerTarGzipNondistributable => {
+            Ok(Box::new(GzDecoder::new(open()?)))
+        }
+        ref media_type => Err(Error::UnsupportedMediaType(media_type.clone())),
+    }
+}
+
+/// Prefix of the PAX extended header records holding extended attributes.
+const PAX_XATTR_PREFIX: &str = "SCHILY.xattr.";
+
+/// Extracts a layer tar archive read from `reader` into `dst`.
+///
+/// Permissions and modification times of the entries are preserved. Permissions and modification
+/// times of directories are set after the whole layer is extracted, so that read-only directories
+/// can be populated without privileges. Ownership of the entries is remapped or stored in an
+/// extended attribute according to `options`. In rootless mode, device nodes are skipped, since
+/// they cannot be created without privileges.
+///
+/// Paths of entries are resolved inside `dst`, following symbolic links already extracted as if
+/// `dst` was the root directory. Entries which escape `dst` or are denied by `options.policy` are
+/// skipped.
+///
+/// # Errors
+///
+/// If the archive cannot be read or an entry cannot be extracted, `Err(Error)` is returned. If any
+/// entries were skipped, `Err(Error::Violations)` listing all of them is returned after the rest of
+/// the layer is extracted.
+pub fn apply(
+    reader: impl Read,
+    dst: &Path,
+    whiteout: WhiteoutMode,
+    options: &Options,
+) -> Result<(), Error> {
+    let mut archive = Archive::new(reader);
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(true);
+
+    // Resolved paths (and their ancestors) extracted from this layer, which opaque whiteouts must
+    // keep
+    let mut extracted = HashSet::new();
+    let mut violations = Vec::new();
+    let mut dirs = PendingDirs::default();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        let violation = |kind| Violation {
+            path: entry_path.clone(),
+            kind,
+        };
+
+        let path = match resolve::lexical(Path::new(""), &entry_path)
+            .and_then(|p| resolve::in_root(dst, &p))
+        {
+            Ok(p) => p,
+            Err(Escape) => {
+                violations.push(violation(ViolationKind::PathEscape));
+                continue;
+            }
+        };
+
+        let file_name = match path.file_name() {
+            Some(n) => n.as_bytes().to_vec(),
+            None => continue, // root directory
+        };
+        let parent = path.parent().unwrap_or_else(|| Path::new(""));
+        dirs.make_accessible(dst, parent)?;
+
+        if file_name == WHITEOUT_OPAQUE.as_bytes() {
+            apply_opaque(dst, parent, &extracted, whiteout, options)?;
+            continue;
+        } else if file_name.starts_with(WHITEOUT_PREFIX.as_bytes()) {
+            let name = &file_name[WHITEOUT_PREFIX.len()..];
+            // `.wh..` would otherwise white out the parent of the directory
+            if name.is_empty() || name == b"." || name == b".." {
+                violations.push(violation(ViolationKind::PathEscape));
+            } else {
+                apply_whiteout(dst, &parent.join(OsStr::from_bytes(name)), whiteout)?;
+            }
+            continue;
+        }
+
+        let header = entry.header().clone();
+        let kind = header.entry_type();
+        let policy = &options.policy;
+
+        let link_target = match kind {
+            EntryType::Symlink | EntryType::Link => {
+                let target = entry
+                    .link_name()?
+                    .ok_or_else(|| invalid_entry(&entry_path))?
+                    .into_owned();
+
+                // Relative symbolic links are relative to the directory holding them, while hard
+                // links are relative to the root
+                let resolved = match kind {
+                    EntryType::Symlink => resolve::lexical(parent, &target).map(|_| target.clone()),
+                    _ => resolve::lexical(Path::new(""), &target)
+                        .and_then(|t| resolve::in_root(dst, &t)),
+                };
+                match resolved {
+                    Ok(t) => Some(t),
+                    Err(Escape) => {
+                        violations.push(violation(ViolationKind::LinkEscape(target)));
+                        continue;
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        let is_device = kind == EntryType::Char || kind == EntryType::Block;
+        if is_device {
+            match policy.devices {
+                Rule::Allow => {}
+                Rule::Drop => continue,
+                Rule::Deny => {
+                    violations.push(violation(ViolationKind::Device));
+                    continue;
+                }
+            }
+            // Device nodes cannot be created without privileges
+            if options.rootless {
+                continue;
+            }
+        }
+
+        let mut mode = header.mode()? & 0o7777;
+        if mode & 0o6000 != 0 {
+            match policy.setuid {
+                Rule::Allow => {}
+                Rule::Drop => mode &= !0o6000,
+                Rule::Deny => {
+                    violations.push(violation(ViolationKind::Setuid));
+                    continue;
+                }
+            }
+        }
+
+        let mut xattrs = Vec::new();
+        if let Some(extensions) = entry.pax_extensions()? {
+            for extension in extensions {
+                let extension = extension?;
+                if let Some(name) = extension
+                    .key()
+                    .ok()
+                    .filter(|k| k.starts_with(PAX_XATTR_PREFIX))
+                    .map(|k| &k[PAX_XATTR_PREFIX.len()..])
+                {
+                    xattrs.push((name.to_string(), extension.value_bytes().to_vec()));
+                }
+            }
+        }
+        if !xattrs.is_empty() {
+            match policy.xattrs {
+                Rule::Allow => {}
+                Rule::Drop => xattrs.clear(),
+                Rule::Deny => {
+                    violations.extend(
+                        xattrs
+                            .into_iter()
+                            .map(|(name, _)| violation(ViolationKind::Xattr(name))),
+                    );
+                    continue;
+                }
+            }
+        }
+
+        for ancestor in path.ancestors() {
+            extracted.insert(ancestor.to_path_buf());
+        }
+
+        let full_path = dst.join(&path);
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        remove_conflicting(&full_path, kind)?;
+
+        match (kind, link_target) {
+            (EntryType::Directory, _) => {
+                if !full_path.is_dir() {
+                    fs::create_dir(&full_path)?;
+                }
+            }
+            (EntryType::Symlink, Some(target)) => {
+                symlink(target, &full_path)?;
+            }
+            (EntryType::Link, Some(target)) => {
+                fs::hard_link(dst.join(target), &full_path)?;
+                // Hard links share metadata with their targets
+                continue;
+            }
+            (EntryType::Char, _) | (EntryType::Block, _) | (EntryType::Fifo, _) => {
+                let file_type = match kind {
+                    EntryType::Char => libc::S_IFCHR,
+                    EntryType::Block => libc::S_IFBLK,
+                    _ => libc::S_IFIFO,
+                };
+                let major = header.device_major()?.unwrap_or(0);
+                let minor = header.device_minor()?.unwrap_or(0);
+                sys::mknod(&full_path, file_type, major, minor)?;
+            }
+            _ => {
+                entry.unpack(&full_path)?;
+            }
+        }
+
+        if kind == EntryType::Directory {
+            dirs.defer(&full_path, mode, header.mtime()? as libc::time_t)?;
+        }
+        set_metadata(&full_path, &entry_path, &header, mode, &xattrs, options)?;
+    }
+    dirs.restore()?;
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Violations(violations))
+    }
+}
+
+fn invalid_entry(path: &Path) -> Error {
+    Error::InvalidPath(path.to_path_buf())
+}
+
+/// Removes an existing file at `path` unless both of it and the new entry are directories.
+fn remove_conflicting(path: &Path, kind: EntryType) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(ref m) if m.is_dir() && kind == EntryType::Directory => Ok(()),
+        Ok(ref m) if m.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+fn apply_whiteout(dst: &Path, target: &Path, whiteout: WhiteoutMode) -> Result<(), Error> {
+    let full_path = dst.join(target);
+
+    match whiteout {
+        WhiteoutMode::Remove => remove_conflicting(&full_path, EntryType::Regular)?,
+        WhiteoutMode::Overlay => {
+            if let Some(parent) = full_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            remove_conflicting(&full_path, EntryType::Char)?;
+            sys::mknod(&full_path, libc::S_IFCHR, 0, 0)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn apply_opaque(
+    dst: &Path,
+    dir: &Path,
+    extracted: &HashSet<PathBuf>,
+    whiteout: WhiteoutMode,
+    options: &Options,
+) -> Result<(), Error> {
+    let full_path = dst.join(dir);
+
+    // The directory itself may be a symbolic link, which must not be followed
+    if fs::symlink_metadata(&full_path)
+        .map(|m| m.file_type().is_symlink())
+        .unwrap_or(false)
+    {
+        return Ok(());
+    }
+
+    match whiteout {
+        WhiteoutMode::Remove => {
+            let entries = match fs::read_dir(&full_path) {
+                Ok(entries) => entries,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+                Err(e) => return Err(e.into()),
+            };
+
+            for entry in entries {
+                let entry = entry?;
+                if !extracted.contains(&dir.join(entry.file_name())) {
+                    remove_conflicting(&entry.path(), EntryType::Regular)?;
+                }
+            }
+        }
+        WhiteoutMode::Overlay => {
+            // `trusted.*` attributes cannot be set without privileges
+            let name = if options.rootless {
+                OVERLAY_USER_OPAQUE_XATTR
+            } else {
+                OVERLAY_OPAQUE_XATTR
+            };
+            fs::create_dir_all(&full_path)?;
+            sys::lsetxattr(&full_path, name, b"y")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Directories whose permissions and modification times are set after a layer is extracted.
+#[derive(Default)]
+struct PendingDirs {
+    /// Directories already made accessible.
+    checked: HashSet<PathBuf>,
+    /// Final permissions and modification times of directories, if they are to be changed.
+    pending: HashMap<PathBuf, (u32, Option<libc::time_t>)>,
+}
+
+impl PendingDirs {
+    /// Makes `dir` in `dst` and its ancestors writable and searchable by their owner, so that
+    /// entries can be created and removed in them without privileges.
+    fn make_accessible(&mut self, dst: &Path, dir: &Path) -> io::Result<()> {
+        let mut ancestors = dir.ancestors().collect::<Vec<_>>();
+        ancestors.reverse();
+
+        for ancestor in ancestors {
+            let full_path = dst.join(ancestor);
+            if self.checked.contains(&full_path) {
+                continue;
+            }
+            let metadata = match fs::symlink_metadata(&full_path) {
+                Ok(m) => m,
+                // Missing directories are created accessible
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            if !metadata.is_dir() {
+                return Ok(());
+            }
+
+            let mode = metadata.mode() & 0o7777;
+            if mode & 0o700 != 0o700 {
+                fs::set_permissions(&full_path, fs::Permissions::from_mode(mode | 0o700))?;
+                self.pending
+                    .entry(full_path.clone())
+                    .or_insert((mode, None));
+            }
+            self.checked.insert(full_path);
+        }
+
+        Ok(())
+    }
+
+    /// Makes the extracted directory `dir` accessible, and sets its permissions to `mode` and
+    /// modification time to `mtime` later.
+    fn defer(&mut self, dir: &Path, mode: u32, mtime: libc::time_t) -> io::Result<()> {
+        fs::set_permissions(dir, fs::Permissions::from_mode(mode | 0o700))?;
+        self.checked.insert(dir.to_path_buf());
+        self.pending.insert(dir.to_path_buf(), (mode, Some(mtime)));
+        Ok(())
+    }
+
+    /// Sets the final permissions and modification times of the directories.
+    fn restore(self) -> io::Result<()> {
+        // Descendants first, while their ancestors are still searchable
+        let mut pending = self.pending.into_iter().collect::<Vec<_>>();
+        pending.sort_by_key(|(dir, _)| Reverse(dir.components().count()));
+
+        for (dir, (mode, mtime)) in pending {
+            // The directory may have been removed or replaced later in the layer
+            match fs::symlink_metadata(&dir) {
+                Ok(ref m) if m.is_dir() => {}
+                Ok(_) => continue,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            }
+            fs::set_permissions(&dir, fs::Permissions::from_mode(mode))?;
+            if let Some(mtime) = mtime {
+                sys::lutimes(&dir, mtime)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Sets the ownership, permissions, extended attributes, and modification time of an extracted
+/// entry.
+///
+/// Permissions and modification times of directories are left to [`PendingDirs`].
+///
+/// [`PendingDirs`]: struct.PendingDirs.html
+fn set_metadata(
+    full_path: &Path,
+    path: &Path,
+    header: &Header,
+    mode: u32,
+    xattrs: &[(String, Vec<u8>)],
+    options: &Options,
+) -> Result<(), Error> {
+    let kind = header.entry_type();
+    let uid = header.uid()? as u32;
+    let gid = header.gid()? as u32;
+
+    // Ownership is set first, as changing it clears setuid and setgid bits and file capabilities
+    if options.rootless {
+        // `user.*` attributes are permitted only on regular files and directories
+        if (uid != 0 || gid != 0) && (kind.is_file() || kind.is_dir()) {
+            let resource = Resource { uid, gid };
+            sys::lsetxattr(full_path, ROOTLESS_XATTR, &resource.encode())?;
+        }
+    } else {
+        let unmapped = |id| Error::UnmappedId {
+            path: path.to_path_buf(),
+            id,
+        };
+        let host_uid = options.map_uid(uid).ok_or_else(|| unmapped(uid))?;
+        let host_gid = options.map_gid(gid).ok_or_else(|| unmapped(gid))?;
+        sys::lchown(full_path, host_uid, host_gid)?;
+    }
+
+    if kind != EntryType::Symlink && kind != EntryType::Directory {
+        fs::set_permissions(full_path, fs::Permissions::from_mode(mode))?;
+    }
+
+    for (name, value) in xattrs {
+        // Unprivileged users can set only `user.*` attributes, which must not override the
+        // ownership
+        if options.rootless && (!name.starts_with("user.") || name == ROOTLESS_XATTR) {
+            continue;
+        }
+        sys::lsetxattr(full_path, name, value)?;
+    }
+
+    if kind != EntryType::Directory {
+        sys::lutimes(full_path, header.mtime()? as libc::time_t)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::Policy;
+    use image_spec::{Digest, Manifest};
+    use runtime_config::linux::UserNamespaceMappings;
+    use std::{
+        env,
+        os::unix::{fs::FileTypeExt, process::CommandExt},
+        process::Command,
+    };
+    use tempfile::TempDir;
+
+    /// Entry of a layer to be built for tests.
+    pub(crate) enum Entry<'a> {
+        Dir(&'a str),
+        ReadOnlyDir(&'a str),
+        File(&'a str, &'a str),
+        Symlink(&'a str, &'a str),
+        Link(&'a str, &'a str),
+        Char(&'a str, u32, u32),
+        Owned(&'a str, u32, u32),
+        Xattr(&'a str, &'a str, &'a str),
+        Xattrs(&'a str, &'a [(&'a str, &'a str)]),
+    }
+
+    pub(crate) fn build_layer(entries: &[Entry<'_>]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+
+        for entry in entries {
+            let mut header = Header::new_gnu();
+            header.set_mtime(1_000_000_000);
+            header.set_uid(0);
+            header.set_gid(0);
+
+            let xattrs = match *entry {
+                Entry::Xattr(_, name, value) => vec![(name, value)],
+                Entry::Xattrs(_, xattrs) => xattrs.to_vec(),
+                _ => vec![],
+            };
+            if !xattrs.is_empty() {
+                let mut records = String::new();
+                for (name, value) in xattrs {
+                    // PAX extended header record: "<length> <key>=<value>\n"
+                    let record = format!(" {}{}={}\n", PAX_XATTR_PREFIX, name, value);
+                    let mut len = record.len();
+                    while record.len() + len.to_string().len() != len {
+                        len = record.len() + len.to_string().len();
+                    }
+                    records.push_str(&format!("{}{}", len, record));
+                }
+
+                let mut pax = Header::new_ustar();
+                pax.set_entry_type(EntryType::XHeader);
+                pax.set_path("PaxHeaders/xattr").unwrap();
+                pax.set_size(records.len() as u64);
+                pax.set_cksum();
+                builder.append(&pax, records.as_bytes()).unwrap();
+            }
+
+            let (path, data): (&str, &[u8]) = match *entry {
+                Entry::Dir(path) | Entry::ReadOnlyDir(path) => {
+                    header.set_entry_type(EntryType::Directory);
+                    header.set_mode(match entry {
+                        Entry::ReadOnlyDir(_) => 0o555,
+                        _ => 0o755,
+                    });
+                    (path, &[])
+                }
+                Entry::File(path, content) => {
+                    header.set_entry_type(EntryType::Regular);
+                    header.set_mode(0o644);
+                    (path, content.as_bytes())
+                }
+                Entry::Symlink(path, target) | Entry::Link(path, target) => {
+                    header.set_entry_type(match entry {
+                        Entry::Symlink(..) => EntryType::Symlink,
+                        _ => EntryType::Link,
+                    });
+                    header.set_mode(0o777);
+                    header.set_link_name(target).unwrap();
+                    (path, &[])
+                }
+                Entry::Xattr(path, ..) | Entry::Xattrs(path, _) => {
+                    header.set_entry_type(EntryType::Regular);
+                    header.set_mode(0o644);
+                    (path, &[])
+                }
+                Entry::Owned(path, uid, gid) => {
+                    header.set_entry_type(EntryType::Regular);
+                    header.set_mode(0o4755);
+                    header.set_uid(u64::from(uid));
+                    header.set_gid(u64::from(gid));
+                    (path, &[])
+                }
+                Entry::Char(path, major, minor) => {
+                    header.set_entry_type(EntryType::Char);
+                    header.set_mode(0o666);
+                    header.set_device_major(major).unwrap();
+                    header.set_device_minor(minor).unwrap();
+                    (path, &[])
+                }
+            };
+
+            // Writes the path as is, since `Header::set_path` rejects unusual paths
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            builder.append(&header, data).unwrap();
+        }
+
+        builder.into_inner().unwrap()
+    }
+
+    /// Creates an image layout holding uncompressed layers built from `layers`.
+    pub(crate) fn image_with_layers(layers: &[&[Entry<'_>]]) -> (TempDir, Manifest) {
+        let image = tempfile::tempdir().unwrap();
+
+        let layers = layers
+            .iter()
+            .enumerate()
+            .map(|(i, entries)| {
+                let blob = build_layer(entries);
+                let digest = format!("sha256:{:064x}", i).parse::<Digest>().unwrap();
+
+                let path = blob_path(image.path(), &digest);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, &blob).unwrap();
+
+                Descriptor {
+                    media_type: MediaType::LayerTar,
+                    digest,
+                    size: blob.len() as u64,
+                    urls: vec![],
+                    annotations: Default::default(),
+                    platform: None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let manifest = Manifest {
+            schema_version: image_spec::SCHEMA_VERSION,
+            config: layers[0].clone(),
+            layers,
+            annotations: Default::default(),
+        };
+
+        (image, manifest)
+    }
+
+    /// Returns whether the tests run as root, which some of them require.
+    pub(crate) fn is_root() -> bool {
+        unsafe { libc::geteuid() == 0 }
+    }
+
+    /// Runs the test `name` in a new user namespace without ID mappings, where root has no
+    /// privileges over the files outside, as if it was an unprivileged user.
+    fn run_unprivileged(name: &str) {
+        let output = unsafe {
+            Command::new(env::current_exe().unwrap())
+                .args(&[name, "--exact"])
+                .pre_exec(|| {
+                    if libc::unshare(libc::CLONE_NEWUSER) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                })
+                .output()
+                .unwrap()
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{}", stdout);
+        assert!(stdout.contains("1 passed"), "{}", stdout);
+    }
+
+    #[test]
+    fn test_apply() {
+        if !is_root() {
+            return;
+        }
+        let layer = build_layer(&[
+            Entry::Dir("./etc/"),
+            Entry::File("/etc/hostname", "host"),
+            Entry::Symlink("etc/localtime", "/usr/share/zoneinfo/UTC"),
+            Entry::Link("etc/hostname.bak", "etc/hostname"),
+            Entry::Char("dev/null", 1, 3),
+        ]);
+
+        let dst = tempfile::tempdir().unwrap();
+        apply(
+            &layer[..],
+            dst.path(),
+            WhiteoutMode::Remove,
+            &Options::default(),
+        )
+        .unwrap();
+
+        let hostname = dst.path().join("etc/hostname");
+        assert_eq!(fs::read_to_string(&hostname).unwrap(), "host");
+        assert_eq!(fs::metadata(&hostname).unwrap().mode() & 0o7777, 0o644);
+        assert_eq!(fs::metadata(&hostname).unwrap().mtime(), 1_000_000_000);
+
+        assert_eq!(
+            fs::read_link(dst.path().join("etc/localtime")).unwrap(),
+            Path::new("/usr/share/zoneinfo/UTC")
+        );
+        assert_eq!(
+            fs::metadata(dst.path().join("etc/hostname.bak"))
+                .unwrap()
+                .ino(),
+            fs::metadata(&hostname).unwrap().ino()
+        );
+
+        let null = fs::metadata(dst.path().join("dev/null")).unwrap();
+        assert!(null.file_type().is_char_device());
+        assert_eq!(null.rdev(), libc::makedev(1, 3));
+    }
+
+    #[test]
+    fn test_apply_replace() {
+        let dst = tempfile::tempdir().unwrap();
+
+        let lower = build_layer(&[
+            Entry::Dir("a"),
+            Entry::File("a/b", ""),
+            Entry::File("c", ""),
+        ]);
+        apply(
+            &lower[..],
+            dst.path(),
+            WhiteoutMode::Remove,
+            &Options::default(),
+        )
+        .unwrap();
+
+        let upper = build_layer(&[Entry::File("a", "file"), Entry::Dir("c")]);
+        apply(
+            &upper[..],
+            dst.path(),
+            WhiteoutMode::Remove,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_eq!(fs::read_to_string(dst.path().join("a")).unwrap(), "file");
+        assert!(dst.path().join("c").is_dir());
+    }
+
+    #[test]
+    fn test_apply_opaque_remove() {
+        let dst = tempfile::tempdir().unwrap();
+
+        let lower = build_layer(&[Entry::Dir("a"), Entry::File("a/lower", "")]);
+        apply(
+            &lower[..],
+            dst.path(),
+            WhiteoutMode::Remove,
+            &Options::default(),
+        )
+        .unwrap();
+
+        let upper = build_layer(&[
+            Entry::Dir("a"),
+            Entry::File("a/upper", ""),
+            Entry::File("a/.wh..wh..opq", ""),
+        ]);
+        apply(
+            &upper[..],
+            dst.path(),
+            WhiteoutMode::Remove,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(!dst.path().join("a/lower").exists());
+        assert!(dst.path().join("a/upper").exists());
+        assert!(!dst.path().join("a/.wh..wh..opq").exists());
+    }
+
+    #[test]
+    fn test_apply_non_utf8_names() {
+        let layer = |names: &[&[u8]]| {
+            let mut builder = tar::Builder::new(Vec::new());
+            for name in names {
+                let mut header = Header::new_gnu();
+                header.set_entry_type(EntryType::Regular);
+                header.set_mode(0o644);
+                header.set_uid(0);
+                header.set_gid(0);
+                header.set_mtime(1_000_000_000);
+                header.set_size(0);
+                header.set_path(OsStr::from_bytes(name)).unwrap();
+                header.set_cksum();
+                builder.append(&header, io::empty()).unwrap();
+            }
+            builder.into_inner().unwrap()
+        };
+        let dst = tempfile::tempdir().unwrap();
+
+        let lower = layer(&[b"caf\xe9", b"na\xefve"]);
+        apply(
+            &lower[..],
+            dst.path(),
+            WhiteoutMode::Remove,
+            &Options::default(),
+        )
+        .unwrap();
+        assert!(dst.path().join(OsStr::from_bytes(b"caf\xe9")).is_file());
+
+        let upper = layer(&[b".wh.na\xefve"]);
+        apply(
+            &upper[..],
+            dst.path(),
+            WhiteoutMode::Remove,
+            &Options::default(),
+        )
+        .unwrap();
+        assert!(!dst.path().join(OsStr::from_bytes(b"na\xefve")).exists());
+    }
+
+    #[test]
+    fn test_apply_overlay() {
+        if !is_root() {
+            return;
+        }
+        let layer = build_layer(&[
+            Entry::Dir("a"),
+            Entry::File("a/.wh..wh..opq", ""),
+            Entry::File("a/.wh.b", ""),
+            Entry::File(".wh.c", ""),
+        ]);
+
+        let dst = tempfile::tempdir().unwrap();
+        apply(
+            &layer[..],
+            dst.path(),
+            WhiteoutMode::Overlay,
+            &Options::default(),
+        )
+        .unwrap();
+
+        for path in &["a/b", "c"] {
+            let metadata = fs::symlink_metadata(dst.path().join(path)).unwrap();
+            assert!(metadata.file_type().is_char_device());
+            assert_eq!(metadata.rdev(), 0);
+        }
+
+        assert_eq!(
+            sys::lgetxattr(&dst.path().join("a"), OVERLAY_OPAQUE_XATTR).unwrap(),
+            Some(b"y".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_apply_id_mappings() {
+        if !is_root() {
+            return;
+        }
+        let layer = build_layer(&[Entry::Owned("a", 0, 5)]);
+        let options = Options {
+            uid_mappings: vec![UserNamespaceMappings {
+                host_id: 100_000,
+                container_id: 0,
+                size: 65536,
+            }],
+            gid_mappings: vec![UserNamespaceMappings {
+                host_id: 200_000,
+                container_id: 0,
+                size: 65536,
+            }],
+            rootless: false,
+            policy: Policy::default(),
+        };
+
+        let dst = tempfile::tempdir().unwrap();
+        apply(&layer[..], dst.path(), WhiteoutMode::Remove, &options).unwrap();
+
+        let metadata = fs::metadata(dst.path().join("a")).unwrap();
+        assert_eq!((metadata.uid(), metadata.gid()), (100_000, 200_005));
+        assert_eq!(metadata.mode() & 0o7777, 0o4755);
+    }
+
+    #[test]
+    fn test_apply_rootless() {
+        let layer = build_layer(&[
+            Entry::Owned("a", 1000, 5),
+            Entry::Owned("b", 0, 0),
+            Entry::Char("dev/null", 1, 3),
+        ]);
+        let options = Options {
+            rootless: true,
+            ..Options::default()
+        };
+
+        let dst = tempfile::tempdir().unwrap();
+        apply(&layer[..], dst.path(), WhiteoutMode::Remove, &options).unwrap();
+
+        let a = dst.path().join("a");
+        let xattr = sys::lgetxattr(&a, ROOTLESS_XATTR).unwrap().unwrap();
+        assert_eq!(Resource::decode(&xattr), Ok(Resource { uid: 1000, gid: 5 }));
+
+        // Files owned by root in the container need no attribute
+        let b = dst.path().join("b");
+        assert_eq!(sys::lgetxattr(&b, ROOTLESS_XATTR).unwrap(), None);
+
+        let metadata = fs::metadata(&a).unwrap();
+        assert_eq!(metadata.uid(), unsafe { libc::geteuid() });
+
+        // Device nodes cannot be created
+        assert!(!dst.path().join("dev/null").exists());
+    }
+
+    #[test]
+    fn test_apply_rootless_overlay() {
+        let layer = build_layer(&[Entry::Dir("a"), Entry::File("a/.wh..wh..opq", "")]);
+        let options = Options {
+            rootless: true,
+            ..Options::default()
+        };
+
+        let dst = tempfile::tempdir().unwrap();
+        apply(&layer[..], dst.path(), WhiteoutMode::Overlay, &options).unwrap();
+
+        let a = dst.path().join("a");
+        assert_eq!(
+            sys::lgetxattr(&a, OVERLAY_USER_OPAQUE_XATTR).unwrap(),
+            Some(b"y".to_vec())
+        );
+        assert_eq!(sys::lgetxattr(&a, OVERLAY_OPAQUE_XATTR).unwrap(), None);
+    }
+
+    #[test]
+    fn test_apply_readonly_dir() {
+        if is_root() {
+            return run_unprivileged("layer::tests::test_apply_readonly_dir");
+        }
+
+        let dst = tempfile::tempdir().unwrap();
+        let lower = build_layer(&[
+            Entry::ReadOnlyDir("usr"),
+            Entry::ReadOnlyDir("usr/bin"),
+            Entry::File("usr/bin/sh", "sh"),
+            Entry::File("usr/bin/cat", "cat"),
+        ]);
+        apply(
+            &lower[..],
+            dst.path(),
+            WhiteoutMode::Remove,
+            &Options::default(),
+        )
+        .unwrap();
+
+        let bin = dst.path().join("usr/bin");
+        assert_eq!(fs::read_to_string(bin.join("sh")).unwrap(), "sh");
+        for dir in &[dst.path().join("usr"), bin.clone()] {
+            let metadata = fs::metadata(dir).unwrap();
+            assert_eq!(metadata.mode() & 0o7777, 0o555);
+            assert_eq!(metadata.mtime(), 1_000_000_000);
+        }
+
+        // Read-only directories of lower layers can be modified too
+        let upper = build_layer(&[
+            Entry::File("usr/bin/ls", "ls"),
+            Entry::File("usr/bin/.wh.cat", ""),
+        ]);
+        apply(
+            &upper[..],
+            dst.path(),
+            WhiteoutMode::Remove,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(bin.join("ls").is_file());
+        assert!(!bin.join("cat").exists());
+        assert_eq!(fs::metadata(&bin).unwrap().mode() & 0o7777, 0o555);
+
+        // Let the temporary directory be removed
+        for dir in &[dst.path().join("usr"), bin] {
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+
+    #[test]
+    fn err_apply_unmapped_id() {
+        let layer = build_layer(&[Entry::Owned("a", 1000, 0)]);
+        let mapping = UserNamespaceMappings {
+            host_id: 100_000,
+            container_id: 0,
+            size: 1000,
+        };
+        let options = Options {
+            uid_mappings: vec![mapping.clone()],
+            gid_mappings: vec![mapping],
+            rootless: false,
+            policy: Policy::default(),
+        };
+
+        let dst = tempfile::tempdir().unwrap();
+        match apply(&layer[..], dst.path(), WhiteoutMode::Remove, &options) {
+            Err(Error::UnmappedId { path, id }) => {
+                assert_eq!(path, Path::new("a"));
+                assert_eq!(id, 1000);
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_apply_parent_dir() {
+        let layer = build_layer(&[Entry::File("a/../b", "")]);
+
+        let dst = tempfile::tempdir().unwrap();
+        apply(
+            &layer[..],
+            dst.path(),
+            WhiteoutMode::Remove,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert!(dst.path().join("b").is_file());
+    }
+
+    #[test]
+    fn test_apply_symlink_parent() {
+        let layer = build_layer(&[
+            Entry::Symlink("etc", "/usr/etc"),
+            Entry::Symlink("up", "../../.."),
+            Entry::File("etc/hostname", "host"),
+            Entry::File("up/hostname", "up"),
+        ]);
+
+        let dst = tempfile::tempdir().unwrap();
+        let rootfs = dst.path().join("rootfs");
+        fs::create_dir(&rootfs).unwrap();
+        let result = apply(
+            &layer[..],
+            &rootfs,
+            WhiteoutMode::Remove,
+            &Options::default(),
+        );
+
+        // The absolute link is followed inside the root, not on the host
+        assert_eq!(
+            fs::read_to_string(rootfs.join("usr/etc/hostname")).unwrap(),
+            "host"
+        );
+        assert!(!dst.path().join("hostname").exists());
+
+        // The escaping link is not created, so the file is extracted into a plain directory
+        assert!(rootfs.join("up/hostname").is_file());
+
+        match result {
+            Err(Error::Violations(violations)) => assert_eq!(
+                violations,
+                vec![Violation {
+                    path: PathBuf::from("up"),
+                    kind: ViolationKind::LinkEscape(PathBuf::from("../../..")),
+                }]
+            ),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_apply_policy_drop() {
+        let layer = build_layer(&[
+            Entry::Char("dev/null", 1, 3),
+            Entry::Owned("su", 0, 0),
+            Entry::Xattr("ping", "security.capability", "cap"),
+        ]);
+        let options = Options {
+            policy: Policy::untrusted(),
+            ..Options::default()
+        };
+
+        let dst = tempfile::tempdir().unwrap();
+        apply(&layer[..], dst.path(), WhiteoutMode::Remove, &options).unwrap();
+
+        assert!(!dst.path().join("dev/null").exists());
+        let su = fs::metadata(dst.path().join("su")).unwrap();
+        assert_eq!(su.mode() & 0o7777, 0o755);
+        let ping = dst.path().join("ping");
+        assert!(ping.is_file());
+        assert_eq!(sys::lgetxattr(&ping, "security.capability").unwrap(), None);
+    }
+
+    #[test]
+    fn test_apply_xattrs() {
+        if !is_root() {
+            return;
+        }
+        let layer = build_layer(&[Entry::Xattr("a", "trusted.test", "value")]);
+
+        let dst = tempfile::tempdir().unwrap();
+        apply(
+            &layer[..],
+            dst.path(),
+            WhiteoutMode::Remove,
+            &Options::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            sys::lgetxattr(&dst.path().join("a"), "trusted.test").unwrap(),
+            Some(b"value".to_vec())
+        );
+    }
+
+    #[test]
+    fn err_apply_policy_deny() {
+        let layer = build_layer(&[
+            Entry::Char("dev/null", 1, 3),
+            Entry::Owned("su", 0, 0),
+            Entry::Xattrs("ping", &[("trusted.a", "a"), ("trusted.b", "b")]),
+            Entry::File("ok", ""),
+        ]);
+        let options = Options {
+            policy: Policy {
+                devices: Rule::Deny,
+                setuid: Rule::Deny,
+                xattrs: Rule::Deny,
+            },
+            ..Options::default()
+        };
+
+        let dst = tempfile::tempdir().unwrap();
+        match apply(&layer[..], dst.path(), WhiteoutMode::Remove, &options) {
+            Err(Error::Violations(violations)) => {
+                let kinds = violations.into_iter().map(|v| v.kind).collect::<Vec<_>>();
+                assert_eq!(
+                    kinds,
+                    vec![
+                        ViolationKind::Device,
+                        ViolationKind::Setuid,
+                        // All denied attributes of an entry are reported
+                        ViolationKind::Xattr("trusted.a".to_string()),
+                        ViolationKind::Xattr("trusted.b".to_string()),
+                    ]
+                );
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        // Other entries are extracted
+        assert!(dst.path().join("ok").is_file());
+        assert!(!dst.path().join("su").exists());
+        assert!(!dst.path().join("ping").exists());
+    }
+
+    #[test]
+    fn err_apply_escape() {
+        let layer = build_layer(&[
+            Entry::File("a/../../b", ""),
+            Entry::Link("c", "../etc/passwd"),
+            Entry::File(".wh..", ""),
+        ]);
+
+        let dst = tempfile::tempdir().unwrap();
+        match apply(
+            &layer[..],
+            dst.path(),
+            WhiteoutMode::Remove,
+            &Options::default(),
+        ) {
+            Err(Error::Violations(violations)) => assert_eq!(
+                violations,
+                vec![
+                    Violation {
+                        path: PathBuf::from("a/../../b"),
+                        kind: ViolationKind::PathEscape,
+                    },
+                    Violation {
+                        path: PathBuf::from("c"),
+                        kind: ViolationKind::LinkEscape(PathBuf::from("../etc/passwd")),
+                    },
+                    Violation {
+                        path: PathBuf::from(".wh.."),
+                        kind: ViolationKind::PathEscape,
+                    },
+                ]
+            ),
+            r => panic!("unexpected result: {:?}", r),
+        }
+        assert!(dst.path().exists());
+    }
+
+    #[test]
+    fn err_open_unsupported() {
+        let (image, mut manifest) = image_with_layers(&[&[]]);
+        manifest.layers[0].media_type = MediaType::ImageConfig;
+
+        match open(image.path(), &manifest.layers[0]) {
+            Err(Error::UnsupportedMediaType(MediaType::ImageConfig)) => {}
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+    }
+}
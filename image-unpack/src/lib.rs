@@ -0,0 +1,373 @@
+//! Unpacking OCI images into root filesystems.
+//!
+//! An image can be unpacked in two ways:
+//!
+//! * [`unpack`] extracts all layers of an image, in order, into a single directory, applying
+//!   whiteouts by removing the whited-out files.
+//! * [`overlay::unpack_layered`] extracts each layer into its own directory, converting whiteouts
+//!   into the form understood by overlayfs, so that the layers can be shared between containers
+//!   and stacked with an overlay mount.
+//!
+//! A root filesystem unpacked with [`repack::unpack_bundle`] can be modified and packed back into a
+//! new revision of the image with [`repack::repack`].
+//!
+//! Ownership of the extracted files can be remapped with [`Options`]. When not running as root,
+//! the ownership is stored in an extended attribute instead, as described in [`rootless`].
+//!
+//! Entries whose paths or link targets escape the destination directory are never extracted, and
+//! device nodes, setuid bits and extended attributes are handled according to [`policy`].
+//!
+//! [`unpack`]: fn.unpack.html
+//! [`overlay::unpack_layered`]: overlay/fn.unpack_layered.html
+//! [`repack::unpack_bundle`]: repack/fn.unpack_bundle.html
+//! [`repack::repack`]: repack/fn.repack.html
+//! [`Options`]: struct.Options.html
+//! [`rootless`]: rootless/index.html
+//! [`policy`]: policy/index.html
+
+#![warn(
+    future_incompatible,
+    missing_docs,
+    missing_debug_implementations,
+    nonstandard_style,
+    rust_2018_idioms,
+    trivial_casts,
+    trivial_numeric_casts,
+    unused
+)]
+
+pub mod layer;
+pub mod mtree;
+pub mod overlay;
+pub mod policy;
+pub mod repack;
+mod resolve;
+pub mod rootless;
+mod sys;
+
+use std::{
+    error, fmt, io,
+    path::{Path, PathBuf},
+};
+
+use image_spec::{builder::BuildError, layout::BLOBS, Digest, Manifest, MediaType};
+use runtime_config::linux::UserNamespaceMappings;
+
+pub use layer::WhiteoutMode;
+pub use policy::Policy;
+
+use policy::Violation;
+use rootless::DecodeError;
+
+/// Error type that can be returned when failed to unpack an image.
+#[derive(Debug)]
+pub enum Error {
+    /// I/O operation failed.
+    Io(io::Error),
+    /// Media type of a layer is not supported.
+    UnsupportedMediaType(MediaType),
+    /// Path of an entry in a layer is not valid.
+    InvalidPath(PathBuf),
+    /// Owner of an entry in a layer is not mapped to the host.
+    UnmappedId {
+        /// Path of the entry.
+        path: PathBuf,
+        /// UID or GID in the container.
+        id: u32,
+    },
+    /// Entries in a layer were not extracted because of the policy.
+    Violations(Vec<Violation>),
+    /// Line of a manifest of a root filesystem is not valid.
+    InvalidMtree(usize),
+    /// Ownership of a file stored in the `user.rootlesscontainers` extended attribute is not valid.
+    InvalidOwnership {
+        /// Path of the file relative to the root filesystem.
+        path: PathBuf,
+        /// Error of decoding the attribute.
+        error: DecodeError,
+    },
+    /// Failed to (de)serialize a JSON blob.
+    Json(serde_json::Error),
+    /// Reference is not found in the index of the image.
+    RefNotFound(String),
+    /// Failed to build a new revision of an image.
+    Build(BuildError),
+}
+
+/// Options for unpacking layers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    /// UID mappings from the host to the container, applied to the owners of extracted files.
+    ///
+    /// If empty, UIDs are not remapped.
+    pub uid_mappings: Vec<UserNamespaceMappings>,
+
+    /// GID mappings from the host to the container, applied to the groups of extracted files.
+    ///
+    /// If empty, GIDs are not remapped.
+    pub gid_mappings: Vec<UserNamespaceMappings>,
+
+    /// Whether to store the ownership of extracted files in the `user.rootlesscontainers`
+    /// extended attribute instead of changing it.
+    ///
+    /// In rootless mode, device nodes are skipped and opaque directories of overlayfs are marked
+    /// with `user.overlay.opaque`, since creating device nodes and setting `trusted.*` attributes
+    /// require privileges.
+    pub rootless: bool,
+
+    /// Policy for extracting device nodes, setuid and setgid bits, and extended attributes.
+    pub policy: Policy,
+}
+
+/// Returns the path to the blob with the given digest in the image layout at `image`.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use image_spec::Digest;
+/// use oci_image_unpack::blob_path;
+///
+/// let digest = "sha256:6c3c624b58dbbcd3c0dd82b4c53f04194d1247c6eebdaab7c610cf7d66709b3b"
+///     .parse::<Digest>()
+///     .unwrap();
+///
+/// assert_eq!(
+///     blob_path(Path::new("image"), &digest),
+///     Path::new("image/blobs/sha256/6c3c624b58dbbcd3c0dd82b4c53f04194d1247c6eebdaab7c610cf7d66709b3b"),
+/// );
+/// ```
+pub fn blob_path(image: &Path, digest: &Digest) -> PathBuf {
+    image
+        .join(BLOBS)
+        .join(digest.algorithm.to_string())
+        .join(&digest.encoded)
+}
+
+/// Unpacks all layers of `manifest` in the image layout at `image` into `rootfs`.
+///
+/// Layers are applied in order. `rootfs` is created if it does not exist.
+///
+/// # Errors
+///
+/// If any of the layers cannot be read or extracted, `Err(Error)` is returned. `rootfs` may be
+/// left partially unpacked.
+pub fn unpack(
+    image: &Path,
+    manifest: &Manifest,
+    rootfs: &Path,
+    options: &Options,
+) -> Result<(), Error> {
+    std::fs::create_dir_all(rootfs)?;
+
+    for descriptor in &manifest.layers {
+        let reader = layer::open(image, descriptor)?;
+        layer::apply(reader, rootfs, WhiteoutMode::Remove, options)?;
+    }
+
+    Ok(())
+}
+
+impl Options {
+    /// Maps a UID in the container to the one on the host.
+    ///
+    /// Returns `None` if the UID is not mapped.
+    pub fn map_uid(&self, uid: u32) -> Option<u32> {
+        map_id(&self.uid_mappings, uid)
+    }
+
+    /// Maps a GID in the container to the one on the host.
+    ///
+    /// Returns `None` if the GID is not mapped.
+    pub fn map_gid(&self, gid: u32) -> Option<u32> {
+        map_id(&self.gid_mappings, gid)
+    }
+
+    /// Maps a UID on the host back to the one in the container.
+    ///
+    /// Returns `None` if the UID is not mapped.
+    pub fn unmap_uid(&self, uid: u32) -> Option<u32> {
+        unmap_id(&self.uid_mappings, uid)
+    }
+
+    /// Maps a GID on the host back to the one in the container.
+    ///
+    /// Returns `None` if the GID is not mapped.
+    pub fn unmap_gid(&self, gid: u32) -> Option<u32> {
+        unmap_id(&self.gid_mappings, gid)
+    }
+}
+
+impl Default for Options {
+    /// Returns options without ID mappings and with the permissive policy, which is rootless if not
+    /// running as root.
+    ///
+    /// Even though the policy allows device nodes, they are skipped in rootless mode.
+    fn default() -> Self {
+        Self {
+            uid_mappings: vec![],
+            gid_mappings: vec![],
+            rootless: unsafe { libc::geteuid() } != 0,
+            policy: Policy::default(),
+        }
+    }
+}
+
+fn map_id(mappings: &[UserNamespaceMappings], id: u32) -> Option<u32> {
+    if mappings.is_empty() {
+        return Some(id);
+    }
+
+    mappings.iter().find_map(|m| {
+        let offset = id.checked_sub(m.container_id)?;
+        if offset < m.size {
+            m.host_id.checked_add(offset)
+        } else {
+            None
+        }
+    })
+}
+
+fn unmap_id(mappings: &[UserNamespaceMappings], id: u32) -> Option<u32> {
+    if mappings.is_empty() {
+        return Some(id);
+    }
+
+    mappings.iter().find_map(|m| {
+        let offset = id.checked_sub(m.host_id)?;
+        if offset < m.size {
+            m.container_id.checked_add(offset)
+        } else {
+            None
+        }
+    })
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O failed: {}", e),
+            Self::UnsupportedMediaType(m) => write!(f, "Unsupported layer media type: {}", m),
+            Self::InvalidPath(p) => write!(f, "Invalid path in layer: {}", p.display()),
+            Self::UnmappedId { path, id } => {
+                write!(f, "ID {} of {} is not mapped", id, path.display())
+            }
+            Self::Violations(violations) => {
+                write!(f, "{} entries violate the policy", violations.len())?;
+                for v in violations {
+                    write!(f, "; {}", v)?;
+                }
+                Ok(())
+            }
+            Self::InvalidMtree(line) => write!(f, "Invalid manifest of rootfs at line {}", line),
+            Self::InvalidOwnership { path, error } => {
+                write!(f, "Invalid ownership of {}: {}", path.display(), error)
+            }
+            Self::Json(e) => write!(f, "Invalid JSON: {}", e),
+            Self::RefNotFound(r) => write!(f, "Reference not found: {}", r),
+            Self::Build(e) => write!(f, "Failed to build image: {}", e),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(ref e) => Some(e),
+            Self::Json(ref e) => Some(e),
+            Self::Build(ref e) => Some(e),
+            Self::InvalidOwnership { ref error, .. } => Some(error),
+            Self::UnsupportedMediaType(_)
+            | Self::InvalidPath(_)
+            | Self::UnmappedId { .. }
+            | Self::Violations(_)
+            | Self::InvalidMtree(_)
+            | Self::RefNotFound(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<BuildError> for Error {
+    fn from(e: BuildError) -> Self {
+        Self::Build(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::tests::{image_with_layers, Entry};
+    use std::fs;
+
+    #[test]
+    fn test_unpack() {
+        let (image, manifest) = image_with_layers(&[
+            &[
+                Entry::Dir("etc"),
+                Entry::File("etc/hostname", "base"),
+                Entry::File("etc/passwd", "root"),
+                Entry::Dir("var"),
+                Entry::File("var/cache", "cache"),
+            ],
+            &[
+                Entry::File("etc/hostname", "top"),
+                Entry::File("etc/.wh.passwd", ""),
+                Entry::File(".wh.var", ""),
+            ],
+        ]);
+
+        let rootfs = image.path().join("rootfs");
+        unpack(image.path(), &manifest, &rootfs, &Options::default()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(rootfs.join("etc/hostname")).unwrap(),
+            "top"
+        );
+        assert!(!rootfs.join("etc/passwd").exists());
+        assert!(!rootfs.join("etc/.wh.passwd").exists());
+        assert!(!rootfs.join("var").exists());
+    }
+
+    #[test]
+    fn test_options_map_id() {
+        let options = Options {
+            uid_mappings: vec![
+                UserNamespaceMappings {
+                    host_id: 100_000,
+                    container_id: 0,
+                    size: 1000,
+                },
+                UserNamespaceMappings {
+                    host_id: 1000,
+                    container_id: 1000,
+                    size: 1,
+                },
+            ],
+            gid_mappings: vec![],
+            rootless: false,
+            policy: Policy::default(),
+        };
+
+        assert_eq!(options.map_uid(0), Some(100_000));
+        assert_eq!(options.map_uid(999), Some(100_999));
+        assert_eq!(options.map_uid(1000), Some(1000));
+        assert_eq!(options.map_uid(1001), None);
+        assert_eq!(options.map_gid(1001), Some(1001));
+
+        assert_eq!(options.unmap_uid(100_999), Some(999));
+        assert_eq!(options.unmap_uid(1000), Some(1000));
+        assert_eq!(options.unmap_uid(0), None);
+    }
+}
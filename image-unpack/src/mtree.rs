@@ -0,0 +1,527 @@
+//! Manifests of root filesystems, in a format similar to [mtree(5)].
+//!
+//! A manifest records the metadata of every file in a root filesystem, so that changes made after
+//! unpacking can be detected and packed into a new layer. Each line describes one file:
+//!
+//! ```text
+//! ./etc/hostname type=file mode=0644 uid=0 gid=0 size=5 time=1000000000.0 sha256digest=...
+//! ```
+//!
+//! Characters in paths other than printable ASCII, as well as `\`, are escaped as `\ooo` octal.
+//! Extended attributes are not recorded, except that the ownership is read from them in rootless
+//! mode.
+//!
+//! [mtree(5)]: https://www.freebsd.org/cgi/man.cgi?mtree(5)
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fmt, fs,
+    io::{self, BufRead, Write},
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{FileTypeExt, MetadataExt},
+    },
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    rootless::{Resource, ROOTLESS_XATTR},
+    sys, Error, Options,
+};
+
+/// Header line of a manifest.
+const HEADER: &str = "#mtree v2.0";
+
+/// Manifest of a root filesystem.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Mtree {
+    /// Files in the root filesystem, keyed by their paths relative to the root.
+    ///
+    /// The root directory itself has an empty path.
+    pub entries: BTreeMap<PathBuf, Entry>,
+}
+
+/// Metadata of a file in a manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Type of the file.
+    pub kind: Kind,
+    /// Permission bits, including setuid, setgid, and sticky bits.
+    pub mode: u32,
+    /// UID of the owner in the container.
+    pub uid: u32,
+    /// GID of the group in the container.
+    pub gid: u32,
+    /// Size of a regular file in bytes.
+    pub size: u64,
+    /// Modification time as seconds and nanoseconds since the Unix epoch.
+    pub time: (i64, i64),
+    /// Target of a symbolic link.
+    pub link: Option<PathBuf>,
+    /// Hex-encoded SHA-256 digest of the content of a regular file.
+    pub sha256: Option<String>,
+    /// Major and minor numbers of a device node.
+    pub device: Option<(u32, u32)>,
+}
+
+/// Types of files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// Regular file.
+    File,
+    /// Directory.
+    Dir,
+    /// Symbolic link.
+    Link,
+    /// Character device.
+    Char,
+    /// Block device.
+    Block,
+    /// Named pipe.
+    Fifo,
+}
+
+/// Change of a file between two manifests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Change {
+    /// File was added.
+    Added,
+    /// Metadata or content of the file was modified.
+    Modified,
+    /// File was removed.
+    Removed,
+}
+
+impl Mtree {
+    /// Walks the root filesystem at `rootfs` and records the metadata of all files in it.
+    ///
+    /// Ownership of the files is mapped back into the container according to `options`. Sockets
+    /// are ignored, as they cannot be stored in layers.
+    ///
+    /// # Errors
+    ///
+    /// If any of the files cannot be read, or the owner of a file is not mapped into the
+    /// container, `Err(Error)` is returned.
+    pub fn walk(rootfs: &Path, options: &Options) -> Result<Self, Error> {
+        let mut mtree = Self::default();
+        mtree.walk_dir(rootfs, Path::new(""), options)?;
+        Ok(mtree)
+    }
+
+    fn walk_dir(&mut self, rootfs: &Path, dir: &Path, options: &Options) -> Result<(), Error> {
+        let full_path = rootfs.join(dir);
+        let metadata = fs::symlink_metadata(&full_path)?;
+
+        if let Some(entry) = Entry::from_metadata(&full_path, dir, &metadata, options)? {
+            self.entries.insert(dir.to_path_buf(), entry);
+        }
+
+        if metadata.is_dir() {
+            for child in fs::read_dir(&full_path)? {
+                let child = child?;
+                self.walk_dir(rootfs, &dir.join(child.file_name()), options)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the changes from this manifest to `other`, in order of paths.
+    pub fn diff<'a>(&'a self, other: &'a Self) -> Vec<(&'a Path, Change)> {
+        let mut changes = BTreeMap::new();
+
+        for (path, entry) in &other.entries {
+            match self.entries.get(path) {
+                None => {
+                    changes.insert(path.as_path(), Change::Added);
+                }
+                Some(e) if e != entry => {
+                    changes.insert(path.as_path(), Change::Modified);
+                }
+                Some(_) => {}
+            }
+        }
+        for path in self.entries.keys() {
+            if !other.entries.contains_key(path) {
+                changes.insert(path.as_path(), Change::Removed);
+            }
+        }
+
+        changes.into_iter().collect()
+    }
+
+    /// Parses a manifest from `reader`.
+    ///
+    /// # Errors
+    ///
+    /// If the manifest cannot be read or has an invalid line, `Err(Error)` is returned.
+    pub fn read(reader: impl BufRead) -> Result<Self, Error> {
+        let mut mtree = Self::default();
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || Error::InvalidMtree(i + 1);
+            let (path, entry) = parse_line(&line).ok_or_else(invalid)?;
+            mtree.entries.insert(path, entry);
+        }
+
+        Ok(mtree)
+    }
+
+    /// Writes this manifest to `writer`.
+    ///
+    /// # Errors
+    ///
+    /// If the manifest cannot be written, `Err(io::Error)` is returned.
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "{}", HEADER)?;
+        for (path, entry) in &self.entries {
+            writeln!(writer, "{} {}", escape(&Path::new(".").join(path)), entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl Entry {
+    fn from_metadata(
+        full_path: &Path,
+        path: &Path,
+        metadata: &fs::Metadata,
+        options: &Options,
+    ) -> Result<Option<Self>, Error> {
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_file() {
+            Kind::File
+        } else if file_type.is_dir() {
+            Kind::Dir
+        } else if file_type.is_symlink() {
+            Kind::Link
+        } else if file_type.is_char_device() {
+            Kind::Char
+        } else if file_type.is_block_device() {
+            Kind::Block
+        } else if file_type.is_fifo() {
+            Kind::Fifo
+        } else {
+            return Ok(None);
+        };
+
+        let (uid, gid) = if options.rootless {
+            // Files without the attribute are owned by root in the container
+            match sys::lgetxattr(full_path, ROOTLESS_XATTR)? {
+                Some(value) => {
+                    let resource =
+                        Resource::decode(&value).map_err(|error| Error::InvalidOwnership {
+                            path: path.to_path_buf(),
+                            error,
+                        })?;
+                    (resource.uid, resource.gid)
+                }
+                None => (0, 0),
+            }
+        } else {
+            let unmapped = |id| Error::UnmappedId {
+                path: path.to_path_buf(),
+                id,
+            };
+            let uid = options.unmap_uid(metadata.uid());
+            let gid = options.unmap_gid(metadata.gid());
+            (
+                uid.ok_or_else(|| unmapped(metadata.uid()))?,
+                gid.ok_or_else(|| unmapped(metadata.gid()))?,
+            )
+        };
+
+        let sha256 = match kind {
+            Kind::File => {
+                use sha2::Digest;
+
+                let mut hasher = sha2::Sha256::new();
+                io::copy(&mut fs::File::open(full_path)?, &mut hasher)?;
+                Some(hex::encode(hasher.result()))
+            }
+            _ => None,
+        };
+
+        let device = match kind {
+            Kind::Char | Kind::Block => {
+                let rdev = metadata.rdev();
+                Some((libc::major(rdev), libc::minor(rdev)))
+            }
+            _ => None,
+        };
+
+        Ok(Some(Self {
+            kind,
+            mode: metadata.mode() & 0o7777,
+            uid,
+            gid,
+            size: if kind == Kind::File {
+                metadata.len()
+            } else {
+                0
+            },
+            time: (metadata.mtime(), metadata.mtime_nsec()),
+            link: match kind {
+                Kind::Link => Some(fs::read_link(full_path)?),
+                _ => None,
+            },
+            sha256,
+            device,
+        }))
+    }
+}
+
+impl Kind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::File => "file",
+            Kind::Dir => "dir",
+            Kind::Link => "link",
+            Kind::Char => "char",
+            Kind::Block => "block",
+            Kind::Fifo => "fifo",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "file" => Some(Kind::File),
+            "dir" => Some(Kind::Dir),
+            "link" => Some(Kind::Link),
+            "char" => Some(Kind::Char),
+            "block" => Some(Kind::Block),
+            "fifo" => Some(Kind::Fifo),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "type={} mode={:04o} uid={} gid={}",
+            self.kind.as_str(),
+            self.mode,
+            self.uid,
+            self.gid
+        )?;
+        if self.kind == Kind::File {
+            write!(f, " size={}", self.size)?;
+        }
+        write!(f, " time={}.{}", self.time.0, self.time.1)?;
+        if let Some(ref link) = self.link {
+            write!(f, " link={}", escape(link))?;
+        }
+        if let Some(ref sha256) = self.sha256 {
+            write!(f, " sha256digest={}", sha256)?;
+        }
+        if let Some((major, minor)) = self.device {
+            write!(f, " device={},{}", major, minor)?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_line(line: &str) -> Option<(PathBuf, Entry)> {
+    let mut fields = line.split(' ');
+    let path = unescape(fields.next()?)?;
+    let path = path.strip_prefix(".").ok()?.to_path_buf();
+
+    let mut entry = Entry {
+        kind: Kind::File,
+        mode: 0,
+        uid: 0,
+        gid: 0,
+        size: 0,
+        time: (0, 0),
+        link: None,
+        sha256: None,
+        device: None,
+    };
+    let mut kind = None;
+
+    for field in fields {
+        let mut kv = field.splitn(2, '=');
+        let (key, value) = (kv.next()?, kv.next()?);
+        match key {
+            "type" => kind = Some(Kind::from_str(value)?),
+            "mode" => entry.mode = u32::from_str_radix(value, 8).ok()?,
+            "uid" => entry.uid = value.parse().ok()?,
+            "gid" => entry.gid = value.parse().ok()?,
+            "size" => entry.size = value.parse().ok()?,
+            "time" => {
+                let mut t = value.splitn(2, '.');
+                entry.time = (t.next()?.parse().ok()?, t.next()?.parse().ok()?);
+            }
+            "link" => entry.link = Some(unescape(value)?),
+            "sha256digest" => entry.sha256 = Some(value.to_string()),
+            "device" => {
+                let mut d = value.splitn(2, ',');
+                entry.device = Some((d.next()?.parse().ok()?, d.next()?.parse().ok()?));
+            }
+            _ => return None,
+        }
+    }
+
+    entry.kind = kind?;
+    Some((path, entry))
+}
+
+fn escape(path: &Path) -> String {
+    let mut escaped = String::new();
+    for &b in path.as_os_str().as_bytes() {
+        if b.is_ascii_graphic() && b != b'\\' {
+            escaped.push(b as char);
+        } else {
+            escaped.push_str(&format!("\\{:03o}", b));
+        }
+    }
+    escaped
+}
+
+fn unescape(s: &str) -> Option<PathBuf> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+
+    while let Some(b) = iter.next() {
+        if b == b'\\' {
+            let octal = [iter.next()?, iter.next()?, iter.next()?];
+            let octal = std::str::from_utf8(&octal).ok()?;
+            bytes.push(u8::from_str_radix(octal, 8).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+
+    Some(PathBuf::from(OsStr::from_bytes(&bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::tests::is_root;
+    use std::os::unix::fs::symlink;
+
+    fn options() -> Options {
+        Options {
+            rootless: false,
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn test_mtree_walk() {
+        let rootfs = tempfile::tempdir().unwrap();
+        fs::create_dir(rootfs.path().join("etc")).unwrap();
+        fs::write(rootfs.path().join("etc/hostname"), "host").unwrap();
+        symlink("/etc/hostname", rootfs.path().join("hostname")).unwrap();
+
+        let mtree = Mtree::walk(rootfs.path(), &options()).unwrap();
+        let paths = mtree.entries.keys().collect::<Vec<_>>();
+        assert_eq!(paths, vec!["", "etc", "etc/hostname", "hostname"]);
+
+        let hostname = &mtree.entries[Path::new("etc/hostname")];
+        assert_eq!(hostname.kind, Kind::File);
+        assert_eq!(hostname.size, 4);
+        assert_eq!(
+            hostname.sha256.as_ref().unwrap(),
+            "4740ae6347b0172c01254ff55bae5aff5199f4446e7f6d643d40185b3f475145"
+        );
+
+        let link = &mtree.entries[Path::new("hostname")];
+        assert_eq!(link.link.as_ref().unwrap(), Path::new("/etc/hostname"));
+    }
+
+    #[test]
+    fn test_mtree_read_write() {
+        let rootfs = tempfile::tempdir().unwrap();
+        fs::write(rootfs.path().join("with space\\"), "").unwrap();
+        symlink("a b", rootfs.path().join("link")).unwrap();
+        sys::mknod(&rootfs.path().join("fifo"), libc::S_IFIFO, 0, 0).unwrap();
+        // Creating device nodes requires privileges
+        if is_root() {
+            sys::mknod(&rootfs.path().join("null"), libc::S_IFCHR, 1, 3).unwrap();
+        }
+
+        let mtree = Mtree::walk(rootfs.path(), &options()).unwrap();
+        let mut buf = Vec::new();
+        mtree.write(&mut buf).unwrap();
+
+        let text = String::from_utf8(buf.clone()).unwrap();
+        assert!(text.starts_with("#mtree v2.0\n"));
+        assert!(text.contains("./with\\040space\\134 type=file"));
+        assert!(text.contains("link=a\\040b"));
+        assert!(text.contains("./fifo type=fifo"));
+        if is_root() {
+            assert!(text.contains("device=1,3"));
+        }
+
+        assert_eq!(Mtree::read(&buf[..]).unwrap(), mtree);
+    }
+
+    #[test]
+    fn test_mtree_diff() {
+        let rootfs = tempfile::tempdir().unwrap();
+        fs::write(rootfs.path().join("a"), "a").unwrap();
+        fs::write(rootfs.path().join("b"), "b").unwrap();
+        let old = Mtree::walk(rootfs.path(), &options()).unwrap();
+
+        fs::remove_file(rootfs.path().join("a")).unwrap();
+        fs::write(rootfs.path().join("c"), "c").unwrap();
+        let mut new = Mtree::walk(rootfs.path(), &options()).unwrap();
+        new.entries.get_mut(Path::new("b")).unwrap().mode = 0o600;
+        // Modification time of the root directory is not compared here
+        new.entries
+            .insert(PathBuf::new(), old.entries[Path::new("")].clone());
+
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                (Path::new("a"), Change::Removed),
+                (Path::new("b"), Change::Modified),
+                (Path::new("c"), Change::Added),
+            ]
+        );
+    }
+
+    #[test]
+    fn err_mtree_walk_invalid_ownership() {
+        let rootfs = tempfile::tempdir().unwrap();
+        fs::write(rootfs.path().join("a"), "").unwrap();
+        sys::lsetxattr(&rootfs.path().join("a"), ROOTLESS_XATTR, b"\xff").unwrap();
+
+        let options = Options {
+            rootless: true,
+            ..Options::default()
+        };
+        match Mtree::walk(rootfs.path(), &options) {
+            Err(Error::InvalidOwnership { ref path, .. }) if path == Path::new("a") => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn err_mtree_read() {
+        let test_cases: &[&str] = &[
+            "./a mode=0644",          // missing type
+            "./a type=sock",          // unknown type
+            "./a type=file mode=999", // invalid mode
+            "a type=file",            // not relative to the root
+            "./a\\0 type=file",       // truncated escape
+        ];
+
+        for case in test_cases {
+            match Mtree::read(case.as_bytes()) {
+                Err(Error::InvalidMtree(1)) => {}
+                r => panic!("unexpected result for {}: {:?}", case, r),
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Unpacking an image into per-layer directories stacked with overlayfs.
+//!
+//! Unlike [`unpack`], which flattens all layers into a single directory, each layer is extracted
+//! into its own directory named after the digest of the layer. Directories of layers which have
+//! already been extracted are reused, so base layers can be shared between many containers.
+//!
+//! For more information about overlayfs, see the [kernel docs].
+//!
+//! [`unpack`]: ../fn.unpack.html
+//! [kernel docs]: https://www.kernel.org/doc/Documentation/filesystems/overlayfs.txt
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use image_spec::Manifest;
+use runtime_config::config::{Mount, MountType};
+
+use crate::{layer, repack::partial_name, Error, Options, WhiteoutMode};
+
+/// Type of an overlay mount.
+pub const OVERLAY: &str = "overlay";
+
+/// Root filesystem made of per-layer directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayeredRootfs {
+    /// Directories of the extracted layers, in order from first (bottom-most) to last.
+    pub lower_dirs: Vec<PathBuf>,
+}
+
+/// Unpacks each layer of `manifest` in the image layout at `image` into its own directory under
+/// `layers`.
+///
+/// A layer is extracted into `<layers>/<algorithm>/<encoded>`, named after its digest. If the
+/// directory already exists, the layer is assumed to have been extracted and is not extracted
+/// again. If another process extracts the same layer concurrently, the directory of whichever
+/// finishes first is kept.
+///
+/// # Errors
+///
+/// If any of the layers cannot be read or extracted, `Err(Error)` is returned. Layers extracted
+/// before the failure are kept.
+pub fn unpack_layered(
+    image: &Path,
+    manifest: &Manifest,
+    layers: &Path,
+    options: &Options,
+) -> Result<LayeredRootfs, Error> {
+    let mut lower_dirs = Vec::with_capacity(manifest.layers.len());
+
+    for descriptor in &manifest.layers {
+        let digest = &descriptor.digest;
+        let alg_dir = layers.join(digest.algorithm.to_string());
+        let layer_dir = alg_dir.join(&digest.encoded);
+
+        if !layer_dir.is_dir() {
+            // Extract into a temporary directory first so that an interrupted extraction is never
+            // mistaken for a complete one
+            let partial_dir = alg_dir.join(format!("{}{}", digest.encoded, partial_name()));
+            fs::create_dir_all(&partial_dir)?;
+
+            let reader = layer::open(image, descriptor)?;
+            layer::apply(reader, &partial_dir, WhiteoutMode::Overlay, options)?;
+            if let Err(e) = fs::rename(&partial_dir, &layer_dir) {
+                if !layer_dir.is_dir() {
+                    return Err(e.into());
+                }
+                // Another process has extracted the layer in the meantime
+                fs::remove_dir_all(&partial_dir)?;
+            }
+        }
+
+        lower_dirs.push(layer_dir);
+    }
+
+    Ok(LayeredRootfs { lower_dirs })
+}
+
+impl LayeredRootfs {
+    /// Returns an overlay mount at `destination` stacking the layers under `upper_dir`.
+    ///
+    /// `upper_dir` receives changes made in the container, and `work_dir` MUST be an empty
+    /// directory on the same filesystem as `upper_dir`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use oci_image_unpack::overlay::LayeredRootfs;
+    /// use runtime_config::config::MountType;
+    ///
+    /// let rootfs = LayeredRootfs {
+    ///     lower_dirs: vec![PathBuf::from("/layers/base"), PathBuf::from("/layers/app")],
+    /// };
+    /// let mount = rootfs.mount(Path::new("/"), Path::new("/upper"), Path::new("/work"));
+    ///
+    /// assert_eq!(mount.type_, Some(MountType::Overlay));
+    /// assert_eq!(
+    ///     mount.options,
+    ///     vec![
+    ///         "lowerdir=/layers/app:/layers/base",
+    ///         "upperdir=/upper",
+    ///         "workdir=/work",
+    ///     ],
+    /// );
+    /// ```
+    pub fn mount(&self, destination: &Path, upper_dir: &Path, work_dir: &Path) -> Mount {
+        // overlayfs stacks lower directories from the top-most one
+        let lower_dirs = self
+            .lower_dirs
+            .iter()
+            .rev()
+            .map(|d| d.display().to_string())
+            .collect::<Vec<_>>()
+            .join(":");
+
+        Mount {
+            destination: destination.to_path_buf(),
+            type_: Some(MountType::Overlay),
+            source: Some(PathBuf::from(OVERLAY)),
+            options: vec![
+                format!("lowerdir={}", lower_dirs),
+                format!("upperdir={}", upper_dir.display()),
+                format!("workdir={}", work_dir.display()),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::tests::{image_with_layers, Entry};
+    use std::{os::unix::fs::FileTypeExt, thread};
+
+    #[test]
+    fn test_unpack_layered() {
+        let (image, manifest) = image_with_layers(&[
+            &[Entry::Dir("etc"), Entry::File("etc/passwd", "root")],
+            &[Entry::File("etc/.wh.passwd", "")],
+        ]);
+        let layers = image.path().join("layers");
+
+        let rootfs = unpack_layered(image.path(), &manifest, &layers, &Options::default()).unwrap();
+
+        let expected = manifest
+            .layers
+            .iter()
+            .map(|d| layers.join("sha256").join(&d.digest.encoded))
+            .collect::<Vec<_>>();
+        assert_eq!(rootfs.lower_dirs, expected);
+
+        assert_eq!(
+            fs::read_to_string(expected[0].join("etc/passwd")).unwrap(),
+            "root"
+        );
+        assert!(fs::symlink_metadata(expected[1].join("etc/passwd"))
+            .unwrap()
+            .file_type()
+            .is_char_device());
+    }
+
+    #[test]
+    fn test_unpack_layered_reuse() {
+        let (image, manifest) = image_with_layers(&[&[Entry::File("a", "")]]);
+        let layers = image.path().join("layers");
+
+        let rootfs = unpack_layered(image.path(), &manifest, &layers, &Options::default()).unwrap();
+        fs::write(rootfs.lower_dirs[0].join("marker"), "").unwrap();
+
+        // Already extracted layers are left untouched
+        let rootfs = unpack_layered(image.path(), &manifest, &layers, &Options::default()).unwrap();
+        assert!(rootfs.lower_dirs[0].join("marker").exists());
+    }
+
+    #[test]
+    fn test_unpack_layered_concurrent() {
+        let (image, manifest) = image_with_layers(&[&[Entry::File("a", "a")]]);
+        let layers = image.path().join("layers");
+
+        let threads = (0..4)
+            .map(|_| {
+                let (image, manifest, layers) =
+                    (image.path().to_owned(), manifest.clone(), layers.clone());
+                thread::spawn(move || {
+                    unpack_layered(&image, &manifest, &layers, &Options::default()).unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            let rootfs = thread.join().unwrap();
+            assert_eq!(
+                fs::read_to_string(rootfs.lower_dirs[0].join("a")).unwrap(),
+                "a"
+            );
+        }
+
+        // Partial directories of the extractions which lost the race are removed
+        assert_eq!(fs::read_dir(layers.join("sha256")).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,124 @@
+//! Policy for extracting entries of untrusted layers.
+//!
+//! Regardless of the policy, entries whose paths or link targets escape the destination
+//! directory are never extracted. The policy additionally controls how device nodes, setuid and
+//! setgid bits, and extended attributes are handled.
+//!
+//! Offending entries are skipped and reported together as [`Error::Violations`] after the whole
+//! layer is extracted.
+//!
+//! [`Error::Violations`]: ../enum.Error.html#variant.Violations
+
+use std::{fmt, path::PathBuf};
+
+/// Policy for extracting entries of layers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Policy {
+    /// How character and block device nodes are handled.
+    pub devices: Rule,
+
+    /// How setuid and setgid bits are handled.
+    ///
+    /// `Rule::Drop` clears the bits and extracts the entry.
+    pub setuid: Rule,
+
+    /// How extended attributes are handled.
+    ///
+    /// `Rule::Drop` extracts the entry without its extended attributes.
+    pub xattrs: Rule,
+}
+
+/// How an entry with a feature controlled by a `Policy` is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// Extract the entry as is.
+    Allow,
+    /// Silently drop the feature (or the entry itself).
+    Drop,
+    /// Skip the entry and report it as a violation.
+    Deny,
+}
+
+/// Entry which was not extracted because of a policy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Path of the entry in the layer.
+    pub path: PathBuf,
+    /// Why the entry was not extracted.
+    pub kind: ViolationKind,
+}
+
+/// Reasons why an entry was not extracted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// Path of the entry escapes the destination directory.
+    PathEscape,
+    /// Target of the hard link or symbolic link escapes the destination directory.
+    LinkEscape(PathBuf),
+    /// Entry is a device node.
+    Device,
+    /// Entry has setuid or setgid bits.
+    Setuid,
+    /// Entry has an extended attribute.
+    Xattr(String),
+}
+
+impl Policy {
+    /// Returns a policy which extracts entries as is.
+    pub fn permissive() -> Self {
+        Self {
+            devices: Rule::Allow,
+            setuid: Rule::Allow,
+            xattrs: Rule::Allow,
+        }
+    }
+
+    /// Returns a policy suitable for untrusted layers, which drops device nodes, setuid and setgid
+    /// bits, and extended attributes.
+    pub fn untrusted() -> Self {
+        Self {
+            devices: Rule::Drop,
+            setuid: Rule::Drop,
+            xattrs: Rule::Drop,
+        }
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self::permissive()
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.path.display())?;
+
+        match self.kind {
+            ViolationKind::PathEscape => f.write_str("path escapes the root"),
+            ViolationKind::LinkEscape(ref t) => {
+                write!(f, "link target {} escapes the root", t.display())
+            }
+            ViolationKind::Device => f.write_str("device node"),
+            ViolationKind::Setuid => f.write_str("setuid or setgid bit"),
+            ViolationKind::Xattr(ref n) => write!(f, "extended attribute {}", n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_violation_display() {
+        let violation = Violation {
+            path: PathBuf::from("etc/passwd"),
+            kind: ViolationKind::LinkEscape(PathBuf::from("../../etc/passwd")),
+        };
+        assert_eq!(
+            violation.to_string(),
+            "etc/passwd: link target ../../etc/passwd escapes the root"
+        );
+    }
+}
@@ -0,0 +1,710 @@
+//! Repacking a modified root filesystem into a new revision of an image.
+//!
+//! [`unpack_bundle`] unpacks an image into the `rootfs` directory of a bundle and records the
+//! state of the root filesystem in a [manifest](../mtree/index.html) next to it. After the root
+//! filesystem is modified, [`repack`] packs the changes into a new layer, and writes a new
+//! revision of the image referenced by a name in `index.json`.
+//!
+//! [`unpack_bundle`]: fn.unpack_bundle.html
+//! [`repack`]: fn.repack.html
+
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs,
+    io::{self, BufReader, Write},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use chrono::{Offset, Utc};
+use flate2::{write::GzEncoder, Compression};
+use image_spec::{
+    annotation_keys::REF_NAME,
+    builder::{BuiltImage, ImageBuilder},
+    config::History,
+    digest::Algorithm,
+    layout::{BLOBS, INDEX_JSON},
+    Descriptor, Digest, Image, Index, Manifest, MediaType,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tar::{Builder, EntryType, Header};
+
+use crate::{
+    blob_path,
+    layer::WHITEOUT_PREFIX,
+    mtree::{Change, Kind, Mtree},
+    Error, Options,
+};
+
+/// Name of the directory holding the root filesystem in a bundle.
+pub const ROOTFS: &str = "rootfs";
+
+/// File name of the manifest of the root filesystem in a bundle.
+pub const MTREE: &str = "rootfs.mtree";
+
+/// Number of partial files created by this process, which makes their names unique.
+static PARTIAL_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Unpacks all layers of `manifest` in the image layout at `image` into the `rootfs` directory of
+/// `bundle`, and records the state of the root filesystem for [`repack`].
+///
+/// # Errors
+///
+/// If the image cannot be unpacked or the manifest of the root filesystem cannot be written,
+/// `Err(Error)` is returned.
+///
+/// [`repack`]: fn.repack.html
+pub fn unpack_bundle(
+    image: &Path,
+    manifest: &Manifest,
+    bundle: &Path,
+    options: &Options,
+) -> Result<(), Error> {
+    let rootfs = bundle.join(ROOTFS);
+    crate::unpack(image, manifest, &rootfs, options)?;
+
+    let mtree = Mtree::walk(&rootfs, options)?;
+    write_mtree(bundle, &mtree)
+}
+
+/// Returns the descriptor of the manifest referenced by `ref_name` in the `index.json` of the
+/// image layout at `image`.
+///
+/// # Errors
+///
+/// If `index.json` cannot be read, or `ref_name` is not found in it, `Err(Error)` is returned.
+pub fn resolve_ref(image: &Path, ref_name: &str) -> Result<Descriptor, Error> {
+    let index = read_index(image)?;
+
+    index
+        .manifests
+        .into_iter()
+        .find(|d| d.annotations.get(REF_NAME).map(String::as_str) == Some(ref_name))
+        .ok_or_else(|| Error::RefNotFound(ref_name.to_string()))
+}
+
+/// Packs changes made to the root filesystem of `bundle` into a new revision of the image whose
+/// manifest is `base`, and references it as `ref_name` in `index.json`.
+///
+/// The changes since [`unpack_bundle`] (or the last repack) are written as a new gzip-compressed
+/// layer, appended to the layers of the manifest and the `diff_ids` of the configuration along
+/// with `history`. If nothing has changed, no layer is added and `history` is marked as an empty
+/// layer. Any other reference named `ref_name` is replaced.
+///
+/// The configuration is edited with [`ImageBuilder`], so history entries are added for layers of
+/// `base` without history. If `history` has no creation time, the current time is used, and the
+/// creation time of the image is updated to it.
+///
+/// Extended attributes are not recorded in the manifest of the root filesystem, so they are
+/// neither packed into the new layer nor detected as changes.
+///
+/// Returns the descriptor of the new manifest.
+///
+/// # Errors
+///
+/// If any of the blobs cannot be read or written, or the root filesystem cannot be packed,
+/// `Err(Error)` is returned.
+///
+/// [`unpack_bundle`]: fn.unpack_bundle.html
+/// [`ImageBuilder`]: ../../oci_image_spec/builder/struct.ImageBuilder.html
+pub fn repack(
+    image: &Path,
+    base: &Descriptor,
+    bundle: &Path,
+    ref_name: &str,
+    mut history: History,
+    options: &Options,
+) -> Result<Descriptor, Error> {
+    let manifest: Manifest = read_json_blob(image, base)?;
+    let config: Image = read_json_blob(image, &manifest.config)?;
+
+    let rootfs = bundle.join(ROOTFS);
+    let old = Mtree::read(BufReader::new(fs::File::open(bundle.join(MTREE))?))?;
+    let new = Mtree::walk(&rootfs, options)?;
+
+    // Changes of the root directory itself are not stored in layers
+    let changes = old
+        .diff(&new)
+        .into_iter()
+        .filter(|(path, _)| path.as_os_str() != "")
+        .collect::<Vec<_>>();
+
+    if history.created.is_none() {
+        let now = Utc::now();
+        history.created = Some(now.with_timezone(&now.offset().fix()));
+    }
+    let builder = ImageBuilder::new(config, manifest);
+    let builder = if changes.is_empty() {
+        builder.empty_layer(history)
+    } else {
+        let (layer, diff_id) = write_layer(image, &rootfs, &old, &new, &changes)?;
+        builder.layer(layer, diff_id, history)
+    };
+    let BuiltImage {
+        config_blob,
+        manifest,
+        ..
+    } = builder.build()?;
+
+    replace_file(&blob_path(image, &manifest.config.digest), &config_blob)?;
+    let mut descriptor = write_json_blob(image, MediaType::ImageManifest, &manifest)?;
+    descriptor.annotations = base.annotations.clone();
+    descriptor
+        .annotations
+        .insert(REF_NAME.to_string(), ref_name.to_string());
+    descriptor.platform = base.platform.clone();
+
+    let mut index = read_index(image)?;
+    index
+        .manifests
+        .retain(|d| d.annotations.get(REF_NAME).map(String::as_str) != Some(ref_name));
+    index.manifests.push(descriptor.clone());
+    replace_file(&image.join(INDEX_JSON), &serde_json::to_vec(&index)?)?;
+
+    write_mtree(bundle, &new)?;
+    Ok(descriptor)
+}
+
+/// Writer computing the SHA-256 digest and the size of the written content.
+struct HashingWriter<W> {
+    inner: W,
+    hasher: sha2::Sha256,
+    size: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        use sha2::Digest;
+
+        Self {
+            inner,
+            hasher: sha2::Sha256::new(),
+            size: 0,
+        }
+    }
+
+    fn finish(self) -> (W, Digest, u64) {
+        use sha2::Digest as _;
+
+        let digest = Digest {
+            algorithm: Algorithm::Sha256,
+            encoded: hex::encode(self.hasher.result()),
+        };
+        (self.inner, digest, self.size)
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        use sha2::Digest;
+
+        let n = self.inner.write(buf)?;
+        self.hasher.input(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes a layer holding `changes` of the root filesystem at `rootfs` from the state `old` to
+/// the current state `mtree`, as a blob. Returns the descriptor and the DiffID of the layer.
+fn write_layer(
+    image: &Path,
+    rootfs: &Path,
+    old: &Mtree,
+    mtree: &Mtree,
+    changes: &[(&Path, Change)],
+) -> Result<(Descriptor, Digest), Error> {
+    let partial_path = partial_blob_path(image)?;
+    let blob = HashingWriter::new(fs::File::create(&partial_path)?);
+    let encoder = GzEncoder::new(blob, Compression::default());
+    let mut builder = Builder::new(HashingWriter::new(encoder));
+
+    // Removed paths and directories replaced by other types of files
+    let mut removed = Vec::<&Path>::new();
+    // First paths of files with multiple hard links, by their device and inode numbers
+    let mut links = HashMap::<(u64, u64), PathBuf>::new();
+    let mut lower_links_found = false;
+
+    for &(path, change) in changes {
+        // Whiting out or replacing a directory removes its children as well
+        if removed.iter().any(|r| path.starts_with(r)) {
+            continue;
+        }
+
+        let mut header = Header::new_gnu();
+        header.set_size(0);
+
+        if change == Change::Removed {
+            let mut name = OsString::from(WHITEOUT_PREFIX);
+            name.push(path.file_name().unwrap_or_default());
+            let whiteout = path.with_file_name(name);
+            header.set_entry_type(EntryType::Regular);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, whiteout, io::empty())?;
+            removed.push(path);
+            continue;
+        }
+
+        let entry = &mtree.entries[path];
+        if change == Change::Modified
+            && old.entries[path].kind == Kind::Dir
+            && entry.kind != Kind::Dir
+        {
+            removed.push(path);
+        }
+        header.set_mode(entry.mode);
+        header.set_uid(u64::from(entry.uid));
+        header.set_gid(u64::from(entry.gid));
+        header.set_mtime(entry.time.0.max(0) as u64);
+
+        match entry.kind {
+            Kind::File => {
+                let full_path = rootfs.join(path);
+                let metadata = fs::symlink_metadata(&full_path)?;
+
+                if metadata.nlink() > 1 {
+                    // Unchanged files are already in the lower layers, so they are linked to
+                    if !lower_links_found {
+                        links.extend(lower_links(rootfs, mtree, changes)?);
+                        lower_links_found = true;
+                    }
+                    let key = (metadata.dev(), metadata.ino());
+                    if let Some(target) = links.get(&key) {
+                        header.set_entry_type(EntryType::Link);
+                        builder.append_link(&mut header, path, target)?;
+                        continue;
+                    }
+                    links.insert(key, path.to_path_buf());
+                }
+
+                header.set_entry_type(EntryType::Regular);
+                header.set_size(metadata.len());
+                builder.append_data(&mut header, path, fs::File::open(&full_path)?)?;
+            }
+            Kind::Link => {
+                header.set_entry_type(EntryType::Symlink);
+                let target = entry
+                    .link
+                    .as_ref()
+                    .map(|p| p.as_path())
+                    .unwrap_or_else(|| Path::new(""));
+                builder.append_link(&mut header, path, target)?;
+            }
+            kind => {
+                header.set_entry_type(match kind {
+                    Kind::Dir => EntryType::Directory,
+                    Kind::Char => EntryType::Char,
+                    Kind::Block => EntryType::Block,
+                    _ => EntryType::Fifo,
+                });
+                if let Some((major, minor)) = entry.device {
+                    header.set_device_major(major)?;
+                    header.set_device_minor(minor)?;
+                }
+                builder.append_data(&mut header, path, io::empty())?;
+            }
+        }
+    }
+
+    let (encoder, diff_id, _) = builder.into_inner()?.finish();
+    let (blob, digest, size) = encoder.finish()?.finish();
+    blob.sync_all()?;
+    fs::rename(&partial_path, blob_path(image, &digest))?;
+
+    let descriptor = Descriptor {
+        media_type: MediaType::LayerTarGzip,
+        digest,
+        size,
+        urls: vec![],
+        annotations: Default::default(),
+        platform: None,
+    };
+    Ok((descriptor, diff_id))
+}
+
+/// Returns the paths of unchanged files with multiple hard links in the root filesystem at
+/// `rootfs`, by their device and inode numbers.
+fn lower_links(
+    rootfs: &Path,
+    mtree: &Mtree,
+    changes: &[(&Path, Change)],
+) -> io::Result<HashMap<(u64, u64), PathBuf>> {
+    let changed = changes
+        .iter()
+        .map(|&(path, _)| path)
+        .collect::<HashSet<_>>();
+    let mut links = HashMap::new();
+
+    for (path, entry) in &mtree.entries {
+        if entry.kind != Kind::File || changed.contains(path.as_path()) {
+            continue;
+        }
+        let metadata = fs::symlink_metadata(rootfs.join(path))?;
+        if metadata.nlink() > 1 {
+            links
+                .entry((metadata.dev(), metadata.ino()))
+                .or_insert_with(|| path.clone());
+        }
+    }
+
+    Ok(links)
+}
+
+fn read_index(image: &Path) -> Result<Index, Error> {
+    let file = fs::File::open(image.join(INDEX_JSON))?;
+    Ok(serde_json::from_reader(BufReader::new(file))?)
+}
+
+fn read_json_blob<T: DeserializeOwned>(image: &Path, descriptor: &Descriptor) -> Result<T, Error> {
+    let file = fs::File::open(blob_path(image, &descriptor.digest))?;
+    Ok(serde_json::from_reader(BufReader::new(file))?)
+}
+
+fn write_json_blob(
+    image: &Path,
+    media_type: MediaType,
+    value: &impl Serialize,
+) -> Result<Descriptor, Error> {
+    let json = serde_json::to_vec(value)?;
+
+    let mut writer = HashingWriter::new(io::sink());
+    writer.write_all(&json)?;
+    let (_, digest, size) = writer.finish();
+    replace_file(&blob_path(image, &digest), &json)?;
+
+    Ok(Descriptor {
+        media_type,
+        digest,
+        size,
+        urls: vec![],
+        annotations: Default::default(),
+        platform: None,
+    })
+}
+
+/// Returns a path to write a blob to before it is renamed after its digest.
+fn partial_blob_path(image: &Path) -> io::Result<PathBuf> {
+    let dir = image.join(BLOBS).join(Algorithm::Sha256.to_string());
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join(partial_name()))
+}
+
+/// Returns a name of a partial file unique among processes and threads.
+pub(crate) fn partial_name() -> String {
+    let n = PARTIAL_FILES.fetch_add(1, Ordering::Relaxed);
+    format!(".{}.{}.partial", process::id(), n)
+}
+
+/// Replaces the content of the file at `path` atomically.
+fn replace_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    fs::create_dir_all(dir)?;
+
+    let partial_path = dir.join(partial_name());
+    let mut file = fs::File::create(&partial_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    fs::rename(&partial_path, path)
+}
+
+fn write_mtree(bundle: &Path, mtree: &Mtree) -> Result<(), Error> {
+    let mut buf = Vec::new();
+    mtree.write(&mut buf)?;
+    replace_file(&bundle.join(MTREE), &buf)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::tests::{build_layer, Entry};
+    use chrono::{DateTime, FixedOffset};
+    use image_spec::{layout::ImageLayout, SCHEMA_VERSION};
+    use std::{
+        ffi::OsStr,
+        os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    };
+    use tempfile::TempDir;
+
+    /// Returns rootless options, so that the tests also pass when unprivileged.
+    fn options() -> Options {
+        Options {
+            rootless: true,
+            ..Options::default()
+        }
+    }
+
+    /// Creates an image layout with a single layer referenced as "v1".
+    fn base_image(entries: &[Entry<'_>]) -> (TempDir, Descriptor) {
+        let image = tempfile::tempdir().unwrap();
+        let layout = ImageLayout {
+            image_layout_version: "1.0.0".to_string(),
+        };
+        fs::write(
+            image.path().join("oci-layout"),
+            serde_json::to_vec(&layout).unwrap(),
+        )
+        .unwrap();
+
+        let blob = build_layer(entries);
+        let partial_path = partial_blob_path(image.path()).unwrap();
+        let mut writer = HashingWriter::new(fs::File::create(&partial_path).unwrap());
+        writer.write_all(&blob).unwrap();
+        let (_, digest, size) = writer.finish();
+        fs::rename(&partial_path, blob_path(image.path(), &digest)).unwrap();
+
+        let config: Image = serde_json::from_value(serde_json::json!({
+            "created": "2019-08-01T00:00:00Z",
+            "architecture": "amd64",
+            "os": "linux",
+            "rootfs": { "type": "layers", "diff_ids": [digest.to_string()] },
+        }))
+        .unwrap();
+        let manifest = Manifest {
+            schema_version: SCHEMA_VERSION,
+            config: write_json_blob(image.path(), MediaType::ImageConfig, &config).unwrap(),
+            layers: vec![Descriptor {
+                media_type: MediaType::LayerTar,
+                digest,
+                size,
+                urls: vec![],
+                annotations: Default::default(),
+                platform: None,
+            }],
+            annotations: Default::default(),
+        };
+
+        let mut descriptor =
+            write_json_blob(image.path(), MediaType::ImageManifest, &manifest).unwrap();
+        descriptor
+            .annotations
+            .insert(REF_NAME.to_string(), "v1".to_string());
+        let index = Index {
+            schema_version: SCHEMA_VERSION,
+            manifests: vec![descriptor.clone()],
+            annotations: Default::default(),
+        };
+        fs::write(
+            image.path().join(INDEX_JSON),
+            serde_json::to_vec(&index).unwrap(),
+        )
+        .unwrap();
+
+        (image, descriptor)
+    }
+
+    fn time(s: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(s).unwrap()
+    }
+
+    fn history(comment: &str) -> History {
+        History {
+            created: None,
+            author: None,
+            created_by: None,
+            comment: Some(comment.to_string()),
+            empty_layer: None,
+        }
+    }
+
+    #[test]
+    fn test_repack() {
+        let (image, base) = base_image(&[
+            Entry::Dir("etc"),
+            Entry::File("etc/hostname", "base"),
+            Entry::File("etc/passwd", "root"),
+            Entry::File("etc/shadow", "root:*"),
+            Entry::Dir("var"),
+            Entry::Dir("var/cache"),
+            Entry::File("var/cache/a", ""),
+        ]);
+        let bundle = tempfile::tempdir().unwrap();
+        let base_manifest: Manifest = read_json_blob(image.path(), &base).unwrap();
+        unpack_bundle(image.path(), &base_manifest, bundle.path(), &options()).unwrap();
+
+        let rootfs = bundle.path().join(ROOTFS);
+        fs::write(rootfs.join("etc/hostname"), "new").unwrap();
+        fs::set_permissions(rootfs.join("etc/passwd"), fs::Permissions::from_mode(0o600)).unwrap();
+        fs::remove_dir_all(rootfs.join("var")).unwrap();
+        fs::write(rootfs.join("added"), "added").unwrap();
+        fs::hard_link(rootfs.join("added"), rootfs.join("linked")).unwrap();
+        fs::hard_link(rootfs.join("etc/shadow"), rootfs.join("shadow")).unwrap();
+
+        let descriptor = repack(
+            image.path(),
+            &base,
+            bundle.path(),
+            "v2",
+            history("repacked"),
+            &options(),
+        )
+        .unwrap();
+        assert_eq!(resolve_ref(image.path(), "v2").unwrap(), descriptor);
+        assert_eq!(resolve_ref(image.path(), "v1").unwrap(), base);
+
+        let manifest: Manifest = read_json_blob(image.path(), &descriptor).unwrap();
+        assert_eq!(manifest.layers.len(), 2);
+        let layer = &manifest.layers[1];
+        assert_eq!(layer.media_type, MediaType::LayerTarGzip);
+        let blob = fs::File::open(blob_path(image.path(), &layer.digest)).unwrap();
+        assert!(layer.digest.verify(blob).unwrap());
+
+        let config: Image = read_json_blob(image.path(), &manifest.config).unwrap();
+        assert_eq!(config.rootfs.diff_ids.len(), 2);
+        let blob = fs::File::open(blob_path(image.path(), &layer.digest)).unwrap();
+        assert!(config.rootfs.diff_ids[1]
+            .verify(flate2::read::GzDecoder::new(blob))
+            .unwrap());
+        // History of the base layer is added
+        assert_eq!(config.history.len(), 2);
+        assert_eq!(config.history[0].created_by, None);
+        assert_eq!(config.history[1].comment, Some("repacked".to_string()));
+        assert_eq!(config.history[1].created, Some(config.created));
+        assert!(config.created > time("2019-08-01T00:00:00Z"));
+
+        // Unpacking the new revision reproduces the modified root filesystem
+        let unpacked = tempfile::tempdir().unwrap();
+        crate::unpack(image.path(), &manifest, unpacked.path(), &options()).unwrap();
+        let unpacked = unpacked.path();
+        assert_eq!(
+            fs::read_to_string(unpacked.join("etc/hostname")).unwrap(),
+            "new"
+        );
+        assert_eq!(
+            fs::metadata(unpacked.join("etc/passwd")).unwrap().mode() & 0o7777,
+            0o600
+        );
+        assert!(!unpacked.join("var").exists());
+        assert_eq!(
+            fs::metadata(unpacked.join("linked")).unwrap().ino(),
+            fs::metadata(unpacked.join("added")).unwrap().ino()
+        );
+        // A new link to an unchanged file links to the file in the lower layer
+        assert_eq!(
+            fs::metadata(unpacked.join("shadow")).unwrap().ino(),
+            fs::metadata(unpacked.join("etc/shadow")).unwrap().ino()
+        );
+    }
+
+    #[test]
+    fn test_repack_non_utf8_whiteout() {
+        let (image, base) = base_image(&[Entry::File("a", "")]);
+        let bundle = tempfile::tempdir().unwrap();
+        let base_manifest: Manifest = read_json_blob(image.path(), &base).unwrap();
+        unpack_bundle(image.path(), &base_manifest, bundle.path(), &options()).unwrap();
+
+        let name = Path::new(OsStr::from_bytes(b"caf\xe9"));
+        let rootfs = bundle.path().join(ROOTFS);
+        fs::write(rootfs.join(name), "").unwrap();
+        let v2 = repack(
+            image.path(),
+            &base,
+            bundle.path(),
+            "v2",
+            history("added"),
+            &options(),
+        )
+        .unwrap();
+
+        fs::remove_file(rootfs.join(name)).unwrap();
+        let v3 = repack(
+            image.path(),
+            &v2,
+            bundle.path(),
+            "v3",
+            history("removed"),
+            &options(),
+        )
+        .unwrap();
+
+        let manifest: Manifest = read_json_blob(image.path(), &v3).unwrap();
+        let unpacked = tempfile::tempdir().unwrap();
+        crate::unpack(image.path(), &manifest, unpacked.path(), &options()).unwrap();
+        assert!(unpacked.path().join("a").exists());
+        assert!(!unpacked.path().join(name).exists());
+    }
+
+    #[test]
+    fn test_repack_dir_replaced() {
+        let (image, base) = base_image(&[
+            Entry::Dir("a"),
+            Entry::File("a/b", ""),
+            Entry::Dir("c"),
+            Entry::File("c/d", ""),
+        ]);
+        let bundle = tempfile::tempdir().unwrap();
+        let base_manifest: Manifest = read_json_blob(image.path(), &base).unwrap();
+        unpack_bundle(image.path(), &base_manifest, bundle.path(), &options()).unwrap();
+
+        let rootfs = bundle.path().join(ROOTFS);
+        fs::remove_dir_all(rootfs.join("a")).unwrap();
+        fs::write(rootfs.join("a"), "file").unwrap();
+        fs::remove_dir_all(rootfs.join("c")).unwrap();
+        std::os::unix::fs::symlink("a", rootfs.join("c")).unwrap();
+
+        let descriptor = repack(
+            image.path(),
+            &base,
+            bundle.path(),
+            "v2",
+            history("replaced"),
+            &options(),
+        )
+        .unwrap();
+
+        let manifest: Manifest = read_json_blob(image.path(), &descriptor).unwrap();
+        let unpacked = tempfile::tempdir().unwrap();
+        crate::unpack(image.path(), &manifest, unpacked.path(), &options()).unwrap();
+        let unpacked = unpacked.path();
+        assert_eq!(fs::read_to_string(unpacked.join("a")).unwrap(), "file");
+        assert_eq!(fs::read_link(unpacked.join("c")).unwrap(), Path::new("a"));
+    }
+
+    #[test]
+    fn test_repack_unchanged() {
+        let (image, base) = base_image(&[Entry::File("a", "")]);
+        let bundle = tempfile::tempdir().unwrap();
+        let base_manifest: Manifest = read_json_blob(image.path(), &base).unwrap();
+        unpack_bundle(image.path(), &base_manifest, bundle.path(), &options()).unwrap();
+
+        // Repacking into the same reference replaces it
+        let descriptor = repack(
+            image.path(),
+            &base,
+            bundle.path(),
+            "v1",
+            history("noop"),
+            &options(),
+        )
+        .unwrap();
+        let index = read_index(image.path()).unwrap();
+        assert_eq!(index.manifests, vec![descriptor.clone()]);
+
+        let manifest: Manifest = read_json_blob(image.path(), &descriptor).unwrap();
+        assert_eq!(manifest.layers, base_manifest.layers);
+        let config: Image = read_json_blob(image.path(), &manifest.config).unwrap();
+        assert_eq!(config.history.len(), 2);
+        assert_eq!(config.history[1].empty_layer, Some(true));
+    }
+
+    #[test]
+    fn test_partial_name() {
+        assert_ne!(partial_name(), partial_name());
+    }
+
+    #[test]
+    fn err_resolve_ref() {
+        let (image, _) = base_image(&[]);
+        match resolve_ref(image.path(), "missing") {
+            Err(Error::RefNotFound(r)) => assert_eq!(r, "missing"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
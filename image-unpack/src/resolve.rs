@@ -0,0 +1,171 @@
+//! Resolving paths of entries without escaping the destination directory.
+
+use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Maximum number of symbolic links followed in resolving a path, same as Linux.
+const MAX_SYMLINKS: usize = 40;
+
+/// Error type that can be returned when a path escapes the root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Escape;
+
+enum Step {
+    Root,
+    Parent,
+    Normal(OsString),
+}
+
+/// Normalizes `path` lexically into a path relative to the root, as if `path` was relative to
+/// `base`.
+///
+/// Absolute paths are relative to the root.
+pub(crate) fn lexical(base: &Path, path: &Path) -> Result<PathBuf, Escape> {
+    let mut normalized = PathBuf::new();
+
+    for component in base.components().chain(path.components()) {
+        match component {
+            Component::Normal(c) => normalized.push(c),
+            Component::RootDir => normalized = PathBuf::new(),
+            Component::CurDir => {}
+            Component::ParentDir | Component::Prefix(_) => {
+                if !normalized.pop() {
+                    return Err(Escape);
+                }
+            }
+        }
+    }
+
+    Ok(normalized)
+}
+
+/// Resolves `path`, relative to `root`, following symbolic links in its parent directories.
+///
+/// Symbolic links are resolved as if `root` was the root directory. The last component of `path`
+/// is not followed. Returns the resolved path relative to `root`.
+pub(crate) fn in_root(root: &Path, path: &Path) -> Result<PathBuf, Escape> {
+    let file_name = match path.file_name() {
+        Some(n) => n,
+        None => return Ok(PathBuf::new()),
+    };
+
+    let mut steps = path
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .filter_map(step)
+        .collect::<VecDeque<_>>();
+    let mut resolved = PathBuf::new();
+    let mut followed = 0;
+
+    while let Some(s) = steps.pop_front() {
+        match s {
+            Step::Root => resolved = PathBuf::new(),
+            Step::Parent => {
+                if !resolved.pop() {
+                    return Err(Escape);
+                }
+            }
+            Step::Normal(name) => {
+                let candidate = resolved.join(&name);
+                let full_path = root.join(&candidate);
+
+                match fs::symlink_metadata(&full_path) {
+                    Ok(ref m) if m.file_type().is_symlink() => {
+                        followed += 1;
+                        if followed > MAX_SYMLINKS {
+                            return Err(Escape);
+                        }
+
+                        let target = fs::read_link(&full_path).map_err(|_| Escape)?;
+                        for s in target.components().filter_map(step).rev() {
+                            steps.push_front(s);
+                        }
+                    }
+                    _ => resolved = candidate,
+                }
+            }
+        }
+    }
+
+    Ok(resolved.join(file_name))
+}
+
+fn step(component: Component<'_>) -> Option<Step> {
+    match component {
+        Component::Normal(c) => Some(Step::Normal(c.to_os_string())),
+        Component::RootDir => Some(Step::Root),
+        Component::ParentDir | Component::Prefix(_) => Some(Step::Parent),
+        Component::CurDir => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_lexical() {
+        let test_cases = &[
+            ("", "./a/b/", "a/b"),
+            ("", "/a/../b", "b"),
+            ("a/b", "../c", "a/c"),
+            ("a/b", "/c", "c"),
+        ];
+
+        for &(base, path, expected) in test_cases {
+            assert_eq!(
+                lexical(Path::new(base), Path::new(path)),
+                Ok(PathBuf::from(expected))
+            );
+        }
+    }
+
+    #[test]
+    fn err_lexical() {
+        assert_eq!(lexical(Path::new(""), Path::new("..")), Err(Escape));
+        assert_eq!(lexical(Path::new("a"), Path::new("../../b")), Err(Escape));
+    }
+
+    #[test]
+    fn test_in_root() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("usr/lib")).unwrap();
+        symlink("usr/lib", root.path().join("lib")).unwrap();
+        symlink("/usr", root.path().join("abs")).unwrap();
+        symlink("../usr/lib", root.path().join("usr/rel")).unwrap();
+
+        let test_cases = &[
+            ("lib/libc.so", "usr/lib/libc.so"),
+            ("abs/lib/libc.so", "usr/lib/libc.so"),
+            ("usr/rel/libc.so", "usr/lib/libc.so"),
+            ("lib", "lib"), // last component is not followed
+            ("missing/file", "missing/file"),
+        ];
+
+        for &(path, expected) in test_cases {
+            assert_eq!(
+                in_root(root.path(), Path::new(path)),
+                Ok(PathBuf::from(expected))
+            );
+        }
+    }
+
+    #[test]
+    fn err_in_root() {
+        let root = tempfile::tempdir().unwrap();
+        symlink("../..", root.path().join("up")).unwrap();
+        symlink("loop", root.path().join("loop")).unwrap();
+
+        assert_eq!(
+            in_root(root.path(), Path::new("up/etc/passwd")),
+            Err(Escape)
+        );
+        assert_eq!(in_root(root.path(), Path::new("loop/a")), Err(Escape));
+    }
+}
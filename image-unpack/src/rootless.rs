@@ -0,0 +1,163 @@
+//! Ownership of files unpacked without privileges.
+//!
+//! An unprivileged user cannot change the ownership of extracted files. Instead, the ownership
+//! recorded in a layer is stored in the `user.rootlesscontainers` extended attribute, as defined
+//! by the [rootlesscontainers proto]. A file without the attribute is owned by root in the
+//! container.
+//!
+//! [rootlesscontainers proto]: https://github.com/rootless-containers/proto
+
+use std::{error::Error, fmt};
+
+/// Name of the extended attribute holding the ownership of a file.
+pub const ROOTLESS_XATTR: &str = "user.rootlesscontainers";
+
+/// ID meaning that the ownership is not changed.
+pub const NOOP_ID: u32 = std::u32::MAX;
+
+/// Ownership of a file in the container, encoded in the `user.rootlesscontainers` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Resource {
+    /// UID of the owner, or `NOOP_ID`.
+    pub uid: u32,
+    /// GID of the owner, or `NOOP_ID`.
+    pub gid: u32,
+}
+
+/// Error type for decoding a `Resource` from the value of an extended attribute.
+///
+/// In a future version, this struct may have fields that convey the cause of error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError;
+
+// Protocol buffers keys of the fields (field number << 3 | wire type "varint")
+const UID_KEY: u8 = 1 << 3;
+const GID_KEY: u8 = 2 << 3;
+
+impl Resource {
+    /// Returns whether this resource leaves the ownership of a file unchanged.
+    pub fn is_noop(&self) -> bool {
+        self.uid == NOOP_ID && self.gid == NOOP_ID
+    }
+
+    /// Encodes this resource in the protocol buffers wire format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oci_image_unpack::rootless::Resource;
+    ///
+    /// let resource = Resource { uid: 1000, gid: 0 };
+    /// assert_eq!(resource.encode(), vec![0x08, 0xe8, 0x07]);
+    /// assert_eq!(Resource::decode(&resource.encode()), Ok(resource));
+    /// ```
+    pub fn encode(&self) -> Vec<u8> {
+        fn encode_varint(buf: &mut Vec<u8>, mut value: u32) {
+            while value >= 0x80 {
+                buf.push((value & 0x7f) as u8 | 0x80);
+                value >>= 7;
+            }
+            buf.push(value as u8);
+        }
+
+        let mut buf = Vec::new();
+
+        // Fields with the default value (zero) are omitted
+        if self.uid != 0 {
+            buf.push(UID_KEY);
+            encode_varint(&mut buf, self.uid);
+        }
+        if self.gid != 0 {
+            buf.push(GID_KEY);
+            encode_varint(&mut buf, self.gid);
+        }
+
+        buf
+    }
+
+    /// Decodes a resource from the protocol buffers wire format.
+    ///
+    /// # Errors
+    ///
+    /// If `buf` is not a valid encoding of a resource, `Err(DecodeError)` is returned.
+    pub fn decode(buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut resource = Resource { uid: 0, gid: 0 };
+        let mut bytes = buf.iter();
+
+        while let Some(&key) = bytes.next() {
+            let mut value = 0u64;
+            let mut shift = 0;
+            loop {
+                let &b = bytes.next().ok_or(DecodeError)?;
+                if shift >= 64 {
+                    return Err(DecodeError);
+                }
+                value |= u64::from(b & 0x7f) << shift;
+                shift += 7;
+                if b & 0x80 == 0 {
+                    break;
+                }
+            }
+
+            // Unknown fields are not expected in this message
+            let value = value as u32;
+            match key {
+                UID_KEY => resource.uid = value,
+                GID_KEY => resource.gid = value,
+                _ => return Err(DecodeError),
+            }
+        }
+
+        Ok(resource)
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Failed to decode rootlesscontainers resource")
+    }
+}
+
+impl Error for DecodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resource_encode() {
+        assert_eq!(Resource { uid: 0, gid: 0 }.encode(), Vec::<u8>::new());
+        assert_eq!(Resource { uid: 0, gid: 5 }.encode(), vec![0x10, 0x05]);
+        assert_eq!(
+            Resource {
+                uid: NOOP_ID,
+                gid: 1
+            }
+            .encode(),
+            vec![0x08, 0xff, 0xff, 0xff, 0xff, 0x0f, 0x10, 0x01]
+        );
+    }
+
+    #[test]
+    fn test_resource_decode() {
+        assert_eq!(Resource::decode(&[]), Ok(Resource { uid: 0, gid: 0 }));
+        assert_eq!(
+            Resource::decode(&[0x10, 0x05, 0x08, 0xe8, 0x07]),
+            Ok(Resource { uid: 1000, gid: 5 })
+        );
+    }
+
+    #[test]
+    fn err_resource_decode() {
+        let test_cases: &[&[u8]] = &[
+            &[0x08],             // missing value
+            &[0x08, 0x80],       // truncated varint
+            &[0x18, 0x01],       // unknown field
+            &[0x0a, 0x01, 0x00], // unexpected wire type
+        ];
+
+        for case in test_cases {
+            assert_eq!(Resource::decode(case), Err(DecodeError));
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Thin wrappers of system calls not exposed by `std`.
+
+use std::{
+    ffi::CString,
+    io,
+    os::{raw::c_int, unix::ffi::OsStrExt},
+    path::Path,
+};
+
+fn cstr(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+fn check(ret: c_int) -> io::Result<()> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Changes the ownership of `path` without following symbolic links.
+pub(crate) fn lchown(path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+    let path = cstr(path)?;
+    check(unsafe { libc::lchown(path.as_ptr(), uid, gid) })
+}
+
+/// Creates a filesystem node of the given `mode` (file type and permissions) and device number.
+pub(crate) fn mknod(path: &Path, mode: libc::mode_t, major: u32, minor: u32) -> io::Result<()> {
+    let path = cstr(path)?;
+    let dev = libc::makedev(major, minor);
+    check(unsafe { libc::mknod(path.as_ptr(), mode, dev) })
+}
+
+/// Sets an extended attribute of `path` without following symbolic links.
+pub(crate) fn lsetxattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    let path = cstr(path)?;
+    let name = CString::new(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    check(unsafe {
+        libc::lsetxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr() as *const libc::c_void,
+            value.len(),
+            0,
+        )
+    })
+}
+
+/// Reads an extended attribute of `path` without following symbolic links.
+///
+/// Returns `Ok(None)` if the attribute does not exist or the filesystem does not support extended
+/// attributes.
+pub(crate) fn lgetxattr(path: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+    let path = cstr(path)?;
+    let name = CString::new(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let get = |buf: &mut [u8]| {
+        let len = unsafe {
+            libc::lgetxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+            )
+        };
+        if len == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(len as usize)
+        }
+    };
+
+    loop {
+        // An empty buffer queries the size of the value
+        let result = get(&mut []).and_then(|size| {
+            let mut buf = vec![0u8; size];
+            let len = get(&mut buf)?;
+            buf.truncate(len);
+            Ok(buf)
+        });
+        match result {
+            Ok(buf) => return Ok(Some(buf)),
+            Err(e) => match e.raw_os_error() {
+                Some(libc::ENODATA) | Some(libc::ENOTSUP) => return Ok(None),
+                // The value has grown since its size was queried
+                Some(libc::ERANGE) => continue,
+                _ => return Err(e),
+            },
+        }
+    }
+}
+
+/// Sets the modification and access times of `path` without following symbolic links.
+pub(crate) fn lutimes(path: &Path, secs: libc::time_t) -> io::Result<()> {
+    let path = cstr(path)?;
+    let time = libc::timespec {
+        tv_sec: secs,
+        tv_nsec: 0,
+    };
+    let times = [time, time];
+    check(unsafe {
+        libc::utimensat(
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            times.as_ptr(),
+            libc::AT_SYMLINK_NOFOLLOW,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lgetxattr() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let value = vec![b'x'; 1000];
+        lsetxattr(file.path(), "user.test", &value).unwrap();
+
+        assert_eq!(lgetxattr(file.path(), "user.test").unwrap(), Some(value));
+        assert_eq!(lgetxattr(file.path(), "user.none").unwrap(), None);
+        assert!(lgetxattr(Path::new("/nonexistent"), "user.test").is_err());
+    }
+}
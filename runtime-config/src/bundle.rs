@@ -0,0 +1,305 @@
+//! Filesystem bundles.
+//!
+//! A bundle is a directory holding the config of a container in `config.json`, and usually its
+//! root filesystem. See [the runtime spec] for more information about bundles.
+//!
+//! [the runtime spec]: https://github.com/opencontainers/runtime-spec/blob/v1.0.2/bundle.md
+
+use std::{
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{Config, State};
+
+/// File name of the config in a bundle.
+pub const CONFIG: &str = "config.json";
+
+/// Bundle loaded from a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bundle {
+    /// Canonicalized absolute path to the bundle directory.
+    pub path: PathBuf,
+
+    /// Config of the container.
+    pub config: Config,
+}
+
+/// Error type that can be returned when a bundle is loaded.
+#[derive(Debug)]
+pub enum BundleError {
+    /// Failed to access a file in the bundle.
+    Io(PathBuf, io::Error),
+    /// Failed to deserialize the config.
+    Deserialize(serde_json::Error),
+    /// The config has no `root`.
+    NoRoot,
+    /// The root filesystem does not exist.
+    RootfsNotFound(PathBuf),
+    /// The root filesystem is not a directory.
+    RootfsNotDirectory(PathBuf),
+}
+
+impl Bundle {
+    /// Loads the bundle at `path`.
+    ///
+    /// # Errors
+    ///
+    /// If `config.json` in the bundle cannot be read or deserialized, or the root filesystem
+    /// specified by the config is not a directory, `Err(BundleError)` is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::fs;
+    ///
+    /// use oci_runtime_config::{bundle::Bundle, Config};
+    ///
+    /// let dir = tempfile::tempdir().unwrap();
+    /// fs::create_dir(dir.path().join("rootfs")).unwrap();
+    /// let config = serde_json::to_vec(&Config::default_linux()).unwrap();
+    /// fs::write(dir.path().join("config.json"), config).unwrap();
+    ///
+    /// let bundle = Bundle::open(dir.path()).unwrap();
+    ///
+    /// assert_eq!(bundle.rootfs(), bundle.path.join("rootfs"));
+    /// assert!(bundle.is_readonly());
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, BundleError> {
+        let path = path.as_ref();
+        let path = path
+            .canonicalize()
+            .map_err(|e| BundleError::Io(path.to_path_buf(), e))?;
+
+        let config_path = path.join(CONFIG);
+        let config = fs::read(&config_path).map_err(|e| BundleError::Io(config_path, e))?;
+        let config = serde_json::from_slice(&config).map_err(BundleError::Deserialize)?;
+
+        let bundle = Bundle { path, config };
+        bundle.check_rootfs()?;
+        Ok(bundle)
+    }
+
+    /// Returns the path to the root filesystem.
+    ///
+    /// A relative `root.path` is resolved against the bundle directory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the config has no `root`, which never happens for a bundle returned by [`open`].
+    ///
+    /// [`open`]: #method.open
+    pub fn rootfs(&self) -> PathBuf {
+        let root = self.config.root.as_ref().expect("config has no root");
+        self.path.join(&root.path)
+    }
+
+    /// Returns whether the root filesystem MUST be read-only inside the container.
+    pub fn is_readonly(&self) -> bool {
+        self.config
+            .root
+            .as_ref()
+            .and_then(|root| root.readonly)
+            .unwrap_or(false)
+    }
+
+    /// Returns the sources of `config.mounts`, in order.
+    ///
+    /// Sources of bind mounts are resolved against the bundle directory and canonicalized. Other
+    /// sources, such as device names, are returned as they are.
+    ///
+    /// # Errors
+    ///
+    /// If the source of a bind mount cannot be canonicalized, e.g. it does not exist,
+    /// `Err(BundleError)` is returned.
+    pub fn mount_sources(&self) -> Result<Vec<Option<PathBuf>>, BundleError> {
+        self.config
+            .mounts
+            .iter()
+            .map(|mount| match mount.source {
+                Some(ref source) if mount.parse_options().is_bind() => {
+                    let source = self.path.join(source);
+                    source
+                        .canonicalize()
+                        .map(Some)
+                        .map_err(|e| BundleError::Io(source, e))
+                }
+                ref source => Ok(source.clone()),
+            })
+            .collect()
+    }
+
+    /// Returns the state of a new container `id` created from this bundle.
+    pub fn state<S: Into<String>>(&self, id: S) -> State {
+        let mut state = State::new(id, &self.path);
+        state.annotations = self.config.annotations.clone();
+        state
+    }
+
+    fn check_rootfs(&self) -> Result<(), BundleError> {
+        if self.config.root.is_none() {
+            return Err(BundleError::NoRoot);
+        }
+
+        let rootfs = self.rootfs();
+        match fs::metadata(&rootfs) {
+            Ok(ref metadata) if metadata.is_dir() => Ok(()),
+            Ok(_) => Err(BundleError::RootfsNotDirectory(rootfs)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                Err(BundleError::RootfsNotFound(rootfs))
+            }
+            Err(e) => Err(BundleError::Io(rootfs, e)),
+        }
+    }
+}
+
+impl fmt::Display for BundleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            BundleError::Deserialize(e) => write!(f, "Failed to deserialize config: {}", e),
+            BundleError::NoRoot => f.write_str("Config has no root"),
+            BundleError::RootfsNotFound(path) => {
+                write!(f, "Root filesystem {} does not exist", path.display())
+            }
+            BundleError::RootfsNotDirectory(path) => {
+                write!(f, "Root filesystem {} is not a directory", path.display())
+            }
+        }
+    }
+}
+
+impl Error for BundleError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BundleError::Io(_, e) => Some(e),
+            BundleError::Deserialize(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Mount, MountType, Root};
+    use tempfile::TempDir;
+
+    fn bundle(config: &Config) -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(CONFIG), serde_json::to_vec(config).unwrap()).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_open() {
+        let mut config = Config::default_linux();
+        config
+            .annotations
+            .insert(String::from("foo"), String::from("bar"));
+        config.mounts.push(Mount {
+            destination: PathBuf::from("/data"),
+            type_: Some(MountType::Bind),
+            source: Some(PathBuf::from("data/../data")),
+            options: vec![],
+        });
+        let dir = bundle(&config);
+        fs::create_dir(dir.path().join("rootfs")).unwrap();
+        fs::create_dir(dir.path().join("data")).unwrap();
+
+        let bundle = Bundle::open(dir.path().join(".")).unwrap();
+        let path = dir.path().canonicalize().unwrap();
+        assert_eq!(bundle.path, path);
+        assert_eq!(bundle.config, config);
+        assert_eq!(bundle.rootfs(), path.join("rootfs"));
+        assert!(bundle.is_readonly());
+
+        let sources = bundle.mount_sources().unwrap();
+        let source_of = |destination: &str| {
+            config
+                .mounts
+                .iter()
+                .zip(&sources)
+                .find(|(m, _)| m.destination == Path::new(destination))
+                .map(|(_, s)| s.clone())
+                .unwrap()
+        };
+        assert_eq!(source_of("/proc"), Some(PathBuf::from("proc")));
+        assert_eq!(source_of("/data"), Some(path.join("data")));
+
+        let state = bundle.state("foo");
+        assert_eq!(state.bundle, path);
+        assert_eq!(state.annotations["foo"], "bar");
+    }
+
+    #[test]
+    fn test_open_absolute_root() {
+        let rootfs = tempfile::tempdir().unwrap();
+        let mut config = Config::default_linux();
+        config.root = Some(Root {
+            path: rootfs.path().to_path_buf(),
+            readonly: None,
+        });
+        let dir = bundle(&config);
+
+        let bundle = Bundle::open(dir.path()).unwrap();
+        assert_eq!(bundle.rootfs(), rootfs.path());
+        assert!(!bundle.is_readonly());
+    }
+
+    #[test]
+    fn err_open() {
+        let mut config = Config::default_linux();
+        let dir = bundle(&config);
+        let rootfs = dir.path().canonicalize().unwrap().join("rootfs");
+        assert_eq!(
+            Bundle::open(dir.path()).unwrap_err().to_string(),
+            format!("Root filesystem {} does not exist", rootfs.display())
+        );
+
+        fs::write(&rootfs, "").unwrap();
+        assert_eq!(
+            Bundle::open(dir.path()).unwrap_err().to_string(),
+            format!("Root filesystem {} is not a directory", rootfs.display())
+        );
+
+        config.root = None;
+        let dir = bundle(&config);
+        match Bundle::open(dir.path()) {
+            Err(BundleError::NoRoot) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        fs::write(dir.path().join(CONFIG), "{}").unwrap();
+        match Bundle::open(dir.path()) {
+            Err(BundleError::Deserialize(_)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        fs::remove_file(dir.path().join(CONFIG)).unwrap();
+        match Bundle::open(dir.path()) {
+            Err(BundleError::Io(..)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn err_mount_sources() {
+        let mut config = Config::default_linux();
+        config.mounts.push(Mount {
+            destination: PathBuf::from("/data"),
+            type_: None,
+            source: Some(PathBuf::from("data")),
+            options: vec![String::from("rbind")],
+        });
+        let dir = bundle(&config);
+        fs::create_dir(dir.path().join("rootfs")).unwrap();
+
+        let bundle = Bundle::open(dir.path()).unwrap();
+        match bundle.mount_sources() {
+            Err(BundleError::Io(..)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
@@ -1,8 +1,10 @@
+use std::{convert::Infallible, error::Error, fmt, iter::FromIterator, ops, str::FromStr};
+
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 /// Set of capabilities for a container process.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Capabilities {
     /// Effective capabilities that are kept for the process.
@@ -45,160 +47,670 @@ pub struct Capabilities {
 ///
 /// When the feature `serde` is enabled, `Capability` can be serialized to / deserialized from a
 /// capability name as defined in the [capabilities(7)] man page (e.g. `CAP_AUDIT_CONTROL`).
+/// Names unknown to this crate are deserialized to `Unknown`, so that configs for newer kernels
+/// can be parsed.
 ///
 /// [capabilities(7)]: http://man7.org/linux/man-pages/man7/capabilities.7.html
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Capability {
     /// `CAP_AUDIT_CONTROL`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_AUDIT_CONTROL"))]
     AuditControl,
 
     /// `CAP_AUDIT_READ`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_AUDIT_READ"))]
     AuditRead,
 
     /// `CAP_AUDIT_WRITE`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_AUDIT_WRITE"))]
     AuditWrite,
 
     /// `CAP_BLOCK_SUSPEND`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_BLOCK_SUSPEND"))]
     BlockSuspend,
 
+    /// `CAP_BPF`
+    Bpf,
+
+    /// `CAP_CHECKPOINT_RESTORE`
+    CheckpointRestore,
+
     /// `CAP_CHOWN`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_CHOWN"))]
     Chown,
 
     /// `CAP_DAC_OVERRIDE`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_DAC_OVERRIDE"))]
     DacOverride,
 
     /// `CAP_DAC_READ_SEARCH`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_DAC_READ_SEARCH"))]
     DacReadSearch,
 
     /// `CAP_FOWNER`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_FOWNER"))]
     Fowner,
 
     /// `CAP_FSETID`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_FSETID"))]
     Fsetid,
 
     /// `CAP_IPC_LOCK`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_IPC_LOCK"))]
     IpcLock,
 
     /// `CAP_IPC_OWNER`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_IPC_OWNER"))]
     IpcOwner,
 
     /// `CAP_KILL`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_KILL"))]
     Kill,
 
     /// `CAP_LEASE`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_LEASE"))]
     Lease,
 
     /// `CAP_LINUX_IMMUTABLE`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_LINUX_IMMUTABLE"))]
     LinuxImmutable,
 
     /// `CAP_MAC_ADMIN`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_MAC_ADMIN"))]
     MacAdmin,
 
     /// `CAP_MAC_OVERRIDE`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_MAC_OVERRIDE"))]
     MacOverride,
 
     /// `CAP_MKNOD`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_MKNOD"))]
     Mknod,
 
     /// `CAP_NET_ADMIN`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_NET_ADMIN"))]
     NetAdmin,
 
     /// `CAP_NET_BIND_SERVICE`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_NET_BIND_SERVICE"))]
     NetBindService,
 
     /// `CAP_NET_BROADCAST`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_NET_BROADCAST"))]
     NetBroadcast,
 
     /// `CAP_NET_RAW`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_NET_RAW"))]
     NetRaw,
 
+    /// `CAP_PERFMON`
+    Perfmon,
+
     /// `CAP_SETFCAP`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SETFCAP"))]
     Setfcap,
 
     /// `CAP_SETGID`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SETGID"))]
     Setgid,
 
     /// `CAP_SETPCAP`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SETPCAP"))]
     Setpcap,
 
     /// `CAP_SETUID`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SETUID"))]
     Setuid,
 
     /// `CAP_SYSLOG`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SYSLOG"))]
     Syslog,
 
     /// `CAP_SYS_ADMIN`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SYS_ADMIN"))]
     SysAdmin,
 
     /// `CAP_SYS_BOOT`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SYS_BOOT"))]
     SysBoot,
 
     /// `CAP_SYS_CHROOT`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SYS_CHROOT"))]
     SysChroot,
 
     /// `CAP_SYS_MODULE`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SYS_MODULE"))]
     SysModule,
 
     /// `CAP_SYS_NICE`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SYS_NICE"))]
     SysNice,
 
     /// `CAP_SYS_PACCT`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SYS_PACCT"))]
     SysPacct,
 
     /// `CAP_SYS_PTRACE`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SYS_PTRACE"))]
     SysPtrace,
 
     /// `CAP_SYS_RAWIO`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SYS_RAWIO"))]
     SysRawio,
 
     /// `CAP_SYS_RESOURCE`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SYS_RESOURCE"))]
     SysResource,
 
     /// `CAP_SYS_TIME`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SYS_TIME"))]
     SysTime,
 
     /// `CAP_SYS_TTY_CONFIG`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_SYS_TTY_CONFIG"))]
     SysTtyConfig,
 
     /// `CAP_WAKE_ALARM`
-    #[cfg_attr(feature = "serde", serde(rename = "CAP_WAKE_ALARM"))]
     WakeAlarm,
+
+    /// Capability unknown to this crate, with its name.
+    Unknown(String),
+}
+
+/// Capabilities with their names, indexed by the capability numbers of the kernel.
+const CAPABILITIES: &[(Capability, &str)] = &[
+    (Capability::Chown, "CAP_CHOWN"),
+    (Capability::DacOverride, "CAP_DAC_OVERRIDE"),
+    (Capability::DacReadSearch, "CAP_DAC_READ_SEARCH"),
+    (Capability::Fowner, "CAP_FOWNER"),
+    (Capability::Fsetid, "CAP_FSETID"),
+    (Capability::Kill, "CAP_KILL"),
+    (Capability::Setgid, "CAP_SETGID"),
+    (Capability::Setuid, "CAP_SETUID"),
+    (Capability::Setpcap, "CAP_SETPCAP"),
+    (Capability::LinuxImmutable, "CAP_LINUX_IMMUTABLE"),
+    (Capability::NetBindService, "CAP_NET_BIND_SERVICE"),
+    (Capability::NetBroadcast, "CAP_NET_BROADCAST"),
+    (Capability::NetAdmin, "CAP_NET_ADMIN"),
+    (Capability::NetRaw, "CAP_NET_RAW"),
+    (Capability::IpcLock, "CAP_IPC_LOCK"),
+    (Capability::IpcOwner, "CAP_IPC_OWNER"),
+    (Capability::SysModule, "CAP_SYS_MODULE"),
+    (Capability::SysRawio, "CAP_SYS_RAWIO"),
+    (Capability::SysChroot, "CAP_SYS_CHROOT"),
+    (Capability::SysPtrace, "CAP_SYS_PTRACE"),
+    (Capability::SysPacct, "CAP_SYS_PACCT"),
+    (Capability::SysAdmin, "CAP_SYS_ADMIN"),
+    (Capability::SysBoot, "CAP_SYS_BOOT"),
+    (Capability::SysNice, "CAP_SYS_NICE"),
+    (Capability::SysResource, "CAP_SYS_RESOURCE"),
+    (Capability::SysTime, "CAP_SYS_TIME"),
+    (Capability::SysTtyConfig, "CAP_SYS_TTY_CONFIG"),
+    (Capability::Mknod, "CAP_MKNOD"),
+    (Capability::Lease, "CAP_LEASE"),
+    (Capability::AuditWrite, "CAP_AUDIT_WRITE"),
+    (Capability::AuditControl, "CAP_AUDIT_CONTROL"),
+    (Capability::Setfcap, "CAP_SETFCAP"),
+    (Capability::MacOverride, "CAP_MAC_OVERRIDE"),
+    (Capability::MacAdmin, "CAP_MAC_ADMIN"),
+    (Capability::Syslog, "CAP_SYSLOG"),
+    (Capability::WakeAlarm, "CAP_WAKE_ALARM"),
+    (Capability::BlockSuspend, "CAP_BLOCK_SUSPEND"),
+    (Capability::AuditRead, "CAP_AUDIT_READ"),
+    (Capability::Perfmon, "CAP_PERFMON"),
+    (Capability::Bpf, "CAP_BPF"),
+    (Capability::CheckpointRestore, "CAP_CHECKPOINT_RESTORE"),
+];
+
+/// Capabilities granted by Docker by default.
+const DOCKER_DEFAULT: &[Capability] = &[
+    Capability::Chown,
+    Capability::DacOverride,
+    Capability::Fsetid,
+    Capability::Fowner,
+    Capability::Mknod,
+    Capability::NetRaw,
+    Capability::Setgid,
+    Capability::Setuid,
+    Capability::Setfcap,
+    Capability::Setpcap,
+    Capability::NetBindService,
+    Capability::SysChroot,
+    Capability::Kill,
+    Capability::AuditWrite,
+];
+
+/// Set of capabilities, represented as a bitmask of the capability numbers of the kernel.
+///
+/// # Examples
+///
+/// ```
+/// use oci_runtime_config::config::{Capability, CapabilitySet};
+///
+/// let set = CapabilitySet::parse(&["ALL"]).unwrap() - CapabilitySet::docker_default();
+///
+/// assert!(set.contains(&Capability::SysAdmin));
+/// assert!(!set.contains(&Capability::Chown));
+/// assert_eq!(CapabilitySet::from_bits(1 << 21), Capability::SysAdmin.into());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CapabilitySet(u64);
+
+/// Error type that can be returned when a capability name is unknown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseCapabilityError(String);
+
+impl Capability {
+    /// Returns the capability number of the kernel, as defined in `linux/capability.h`, or `None`
+    /// for `Unknown`.
+    pub fn number(&self) -> Option<u32> {
+        CAPABILITIES
+            .iter()
+            .position(|(c, _)| c == self)
+            .map(|n| n as u32)
+    }
+
+    /// Returns the capability of the kernel number, or `None` if the number is unknown.
+    pub fn from_number(number: u32) -> Option<Capability> {
+        CAPABILITIES.get(number as usize).map(|(c, _)| c.clone())
+    }
+
+    /// Returns the known capability named `name` case-insensitively, with or without the `CAP_`
+    /// prefix.
+    fn from_name(name: &str) -> Option<Capability> {
+        let upper = name.to_ascii_uppercase();
+        let name = if upper.starts_with("CAP_") {
+            &upper[4..]
+        } else {
+            &upper
+        };
+        CAPABILITIES
+            .iter()
+            .find(|(_, n)| &n[4..] == name)
+            .map(|(c, _)| c.clone())
+    }
+}
+
+impl FromStr for Capability {
+    type Err = Infallible;
+
+    /// Parses a capability name case-insensitively, with or without the `CAP_` prefix.
+    ///
+    /// Unknown names are parsed as `Unknown` as they are.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::from_name(s).unwrap_or_else(|| Capability::Unknown(s.to_string())))
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capability::Unknown(name) => f.write_str(name),
+            cap => {
+                let &(_, name) = CAPABILITIES.iter().find(|(c, _)| c == cap).unwrap();
+                f.write_str(name)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Capability {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Capability {
+    fn deserialize<D: serde::Deserializer<'de>>(deser: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deser)?;
+        Ok(s.parse().unwrap())
+    }
+}
+
+impl CapabilitySet {
+    /// Set of no capabilities.
+    pub const EMPTY: CapabilitySet = CapabilitySet(0);
+
+    /// Returns the set of all capabilities known to this crate.
+    pub fn all() -> Self {
+        CapabilitySet((1 << CAPABILITIES.len()) - 1)
+    }
+
+    /// Returns the set of capabilities granted by Docker to a container by default.
+    pub fn docker_default() -> Self {
+        DOCKER_DEFAULT.iter().cloned().collect()
+    }
+
+    /// Returns the set of the bitmask `bits`, ignoring unknown capabilities.
+    pub fn from_bits(bits: u64) -> Self {
+        CapabilitySet(bits & Self::all().0)
+    }
+
+    /// Returns the bitmask of this set.
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Parses capability names as in `docker run --cap-add`, where `ALL` means all capabilities.
+    ///
+    /// # Errors
+    ///
+    /// If any of `names` is unknown, `Err(ParseCapabilityError)` is returned.
+    pub fn parse<S: AsRef<str>>(names: &[S]) -> Result<Self, ParseCapabilityError> {
+        let mut set = Self::EMPTY;
+        for name in names {
+            let name = name.as_ref();
+            if name.eq_ignore_ascii_case("ALL") {
+                set = Self::all();
+            } else {
+                let cap = Capability::from_name(name)
+                    .ok_or_else(|| ParseCapabilityError(name.to_string()))?;
+                set.insert(&cap);
+            }
+        }
+        Ok(set)
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the number of capabilities in the set.
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Returns whether `cap` is in the set.
+    pub fn contains(self, cap: &Capability) -> bool {
+        cap.number().map_or(false, |n| self.0 & (1 << n) != 0)
+    }
+
+    /// Adds `cap` to the set. `Unknown` is ignored.
+    pub fn insert(&mut self, cap: &Capability) {
+        if let Some(n) = cap.number() {
+            self.0 |= 1 << n;
+        }
+    }
+
+    /// Removes `cap` from the set.
+    pub fn remove(&mut self, cap: &Capability) {
+        if let Some(n) = cap.number() {
+            self.0 &= !(1 << n);
+        }
+    }
+
+    /// Returns an iterator over the capabilities in the set, in the order of their numbers.
+    pub fn iter(self) -> impl Iterator<Item = Capability> {
+        CAPABILITIES
+            .iter()
+            .filter(move |(c, _)| self.contains(c))
+            .map(|(c, _)| c.clone())
+    }
+}
+
+impl From<Capability> for CapabilitySet {
+    fn from(cap: Capability) -> Self {
+        let mut set = Self::EMPTY;
+        set.insert(&cap);
+        set
+    }
+}
+
+impl FromIterator<Capability> for CapabilitySet {
+    /// Collects capabilities into a set, ignoring `Unknown`.
+    fn from_iter<I: IntoIterator<Item = Capability>>(iter: I) -> Self {
+        let mut set = Self::EMPTY;
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<Capability> for CapabilitySet {
+    fn extend<I: IntoIterator<Item = Capability>>(&mut self, iter: I) {
+        for cap in iter {
+            self.insert(&cap);
+        }
+    }
+}
+
+impl ops::BitOr for CapabilitySet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        CapabilitySet(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for CapabilitySet {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl ops::BitAnd for CapabilitySet {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        CapabilitySet(self.0 & rhs.0)
+    }
+}
+
+impl ops::Sub for CapabilitySet {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        CapabilitySet(self.0 & !rhs.0)
+    }
+}
+
+impl ops::SubAssign for CapabilitySet {
+    fn sub_assign(&mut self, rhs: Self) {
+        self.0 &= !rhs.0;
+    }
+}
+
+impl fmt::Display for CapabilitySet {
+    /// Formats the capability names separated by commas.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, cap) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", cap)?;
+        }
+        Ok(())
+    }
+}
+
+impl Capabilities {
+    /// Returns the capabilities where the bounding, permitted, and effective sets are `set`, and
+    /// the inheritable and ambient sets are empty, as Docker grants them to a container.
+    pub fn from_set(set: CapabilitySet) -> Self {
+        let caps: Vec<_> = set.iter().collect();
+        Capabilities {
+            effective: caps.clone(),
+            bounding: caps.clone(),
+            inheritable: vec![],
+            permitted: caps,
+            ambient: vec![],
+        }
+    }
+
+    /// Adds `set` to the bounding, permitted, and effective sets.
+    ///
+    /// The inheritable and ambient sets are not changed, as they pass capabilities to programs
+    /// executed by the process.
+    pub fn add_caps(&mut self, set: CapabilitySet) {
+        for caps in &mut [&mut self.bounding, &mut self.permitted, &mut self.effective] {
+            for cap in set.iter() {
+                if !caps.contains(&cap) {
+                    caps.push(cap);
+                }
+            }
+        }
+    }
+
+    /// Removes `set` from all of the sets.
+    pub fn remove_caps(&mut self, set: CapabilitySet) {
+        for caps in self.sets_mut() {
+            caps.retain(|cap| !set.contains(cap));
+        }
+    }
+
+    /// Applies `docker run --cap-add` and `--cap-drop` options.
+    ///
+    /// The options are applied in the same way as Docker:
+    ///
+    /// * If `add` contains `ALL`, all capabilities are added, and then the other capabilities in
+    ///   `drop` are removed.
+    /// * Otherwise, if `drop` contains `ALL`, all capabilities are removed, and then the
+    ///   capabilities in `add` are added.
+    /// * Otherwise, the capabilities in `drop` are removed, and then the capabilities in `add` are
+    ///   added, so capabilities in both `add` and `drop` are added.
+    ///
+    /// # Errors
+    ///
+    /// If any name is unknown, `Err(ParseCapabilityError)` is returned without changing the
+    /// capabilities.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oci_runtime_config::config::{Capabilities, Capability, CapabilitySet};
+    ///
+    /// let mut caps = Capabilities::from_set(CapabilitySet::docker_default());
+    /// caps.apply_cap_changes(&["NET_ADMIN"], &["ALL"]).unwrap();
+    ///
+    /// assert_eq!(caps.bounding, [Capability::NetAdmin]);
+    /// assert_eq!(caps.effective, [Capability::NetAdmin]);
+    /// ```
+    pub fn apply_cap_changes<S: AsRef<str>>(
+        &mut self,
+        add: &[S],
+        drop: &[S],
+    ) -> Result<(), ParseCapabilityError> {
+        let (add_all, add) = parse_changes(add)?;
+        let (drop_all, drop) = parse_changes(drop)?;
+        if add_all {
+            self.add_caps(CapabilitySet::all());
+            self.remove_caps(drop);
+        } else if drop_all {
+            self.remove_caps(CapabilitySet::all());
+            self.add_caps(add);
+        } else {
+            self.remove_caps(drop - add);
+            self.add_caps(add);
+        }
+        Ok(())
+    }
+
+    fn sets_mut(&mut self) -> [&mut Vec<Capability>; 5] {
+        [
+            &mut self.effective,
+            &mut self.bounding,
+            &mut self.inheritable,
+            &mut self.permitted,
+            &mut self.ambient,
+        ]
+    }
+}
+
+/// Parses `--cap-add` or `--cap-drop` options into whether `ALL` is given and the set of the
+/// other capabilities.
+fn parse_changes<S: AsRef<str>>(
+    names: &[S],
+) -> Result<(bool, CapabilitySet), ParseCapabilityError> {
+    let (all, names): (Vec<_>, Vec<_>) = names
+        .iter()
+        .map(AsRef::as_ref)
+        .partition(|name| name.eq_ignore_ascii_case("ALL"));
+    Ok((!all.is_empty(), CapabilitySet::parse(&names)?))
+}
+
+impl fmt::Display for ParseCapabilityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown capability: {:?}", self.0)
+    }
+}
+
+impl Error for ParseCapabilityError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capability_number() {
+        assert_eq!(Capability::Chown.number(), Some(0));
+        assert_eq!(Capability::SysAdmin.number(), Some(21));
+        assert_eq!(Capability::CheckpointRestore.number(), Some(40));
+        assert_eq!(Capability::Unknown(String::from("CAP_FOO")).number(), None);
+        assert_eq!(Capability::from_number(39), Some(Capability::Bpf));
+        assert_eq!(Capability::from_number(64), None);
+        for n in 0..CAPABILITIES.len() as u32 {
+            assert_eq!(Capability::from_number(n).unwrap().number(), Some(n));
+        }
+    }
+
+    #[test]
+    fn test_capability_parse() {
+        for s in &["CAP_NET_ADMIN", "NET_ADMIN", "cap_net_admin", "net_admin"] {
+            assert_eq!(s.parse(), Ok(Capability::NetAdmin));
+        }
+        assert_eq!(Capability::NetAdmin.to_string(), "CAP_NET_ADMIN");
+
+        let unknown = Capability::Unknown(String::from("CAP_FOO"));
+        assert_eq!("CAP_FOO".parse(), Ok(unknown.clone()));
+        assert_eq!(unknown.to_string(), "CAP_FOO");
+        assert_eq!(
+            CapabilitySet::parse(&["CAP_FOO"]).unwrap_err().to_string(),
+            "Unknown capability: \"CAP_FOO\""
+        );
+    }
+
+    #[test]
+    fn test_capability_set() {
+        let mut set = CapabilitySet::parse(&["KILL", "CAP_CHOWN"]).unwrap();
+        assert_eq!(set.bits(), 0x21);
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.to_string(), "CAP_CHOWN,CAP_KILL");
+        set.remove(&Capability::Chown);
+        assert_eq!(set, Capability::Kill.into());
+
+        let all = CapabilitySet::parse(&["all"]).unwrap();
+        assert_eq!(all, CapabilitySet::all());
+        assert_eq!(all.len(), CAPABILITIES.len());
+        assert_eq!(CapabilitySet::from_bits(std::u64::MAX), all);
+        assert_eq!(all.iter().count(), all.len());
+
+        let docker = CapabilitySet::docker_default();
+        assert_eq!(docker.len(), 14);
+        assert_eq!(docker | all, all);
+        assert_eq!(docker & all, docker);
+        assert!((docker - all).is_empty());
+    }
+
+    #[test]
+    fn test_apply_cap_changes() {
+        let mut caps = Capabilities {
+            ambient: vec![Capability::Kill],
+            inheritable: vec![Capability::Kill],
+            ..Capabilities::from_set(CapabilitySet::docker_default())
+        };
+        caps.apply_cap_changes(&["SYS_ADMIN", "NET_RAW"], &["NET_RAW", "KILL"])
+            .unwrap();
+
+        for set in &[&caps.bounding, &caps.permitted, &caps.effective] {
+            assert!(set.contains(&Capability::SysAdmin));
+            assert!(set.contains(&Capability::NetRaw));
+            assert!(!set.contains(&Capability::Kill));
+            assert_eq!(set.len(), 14);
+        }
+        assert!(caps.inheritable.is_empty());
+        assert!(caps.ambient.is_empty());
+
+        let mut all = Capabilities::default();
+        all.apply_cap_changes(&["ALL"], &["ALL"]).unwrap();
+        assert_eq!(all, Capabilities::from_set(CapabilitySet::all()));
+
+        // Dropped capabilities are removed after adding ALL
+        let mut caps = Capabilities::from_set(CapabilitySet::docker_default());
+        caps.apply_cap_changes(&["ALL"], &["NET_RAW"]).unwrap();
+        let expected = CapabilitySet::all() - Capability::NetRaw.into();
+        for set in &[&caps.bounding, &caps.permitted, &caps.effective] {
+            assert_eq!(set.iter().cloned().collect::<CapabilitySet>(), expected);
+        }
+
+        // Only added capabilities are left after dropping ALL
+        let mut caps = Capabilities::from_set(CapabilitySet::docker_default());
+        caps.apply_cap_changes(&["CHOWN", "KILL"], &["ALL", "KILL"])
+            .unwrap();
+        assert_eq!(caps.effective, [Capability::Chown, Capability::Kill]);
+
+        assert!(all.apply_cap_changes(&["CHOWN"], &["FOO"]).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_capability_serde() {
+        let json = r#"["CAP_BPF","CAP_PERFMON","CAP_CHECKPOINT_RESTORE","CAP_FUTURE"]"#;
+        let caps: Vec<Capability> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            caps,
+            vec![
+                Capability::Bpf,
+                Capability::Perfmon,
+                Capability::CheckpointRestore,
+                Capability::Unknown(String::from("CAP_FUTURE")),
+            ]
+        );
+        assert_eq!(serde_json::to_string(&caps).unwrap(), json);
+    }
 }
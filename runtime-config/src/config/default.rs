@@ -0,0 +1,280 @@
+//! Default configs generated by `runc spec`.
+//!
+//! The configs follow [the example spec of runc].
+//!
+//! [the example spec of runc]: https://github.com/opencontainers/runc/blob/v1.0.0/libcontainer/specconv/example.go
+
+use std::path::PathBuf;
+
+use environ_str::EnvVar;
+
+use crate::linux::{
+    resources::{Device, DeviceAccess},
+    Linux, Namespace, NamespaceType, Resources,
+};
+
+use super::{
+    Capabilities, Capability, Config, Mount, MountType, Process, Rlimit, RlimitType, Root,
+    Rootless, User,
+};
+
+/// Paths masked in the container.
+const MASKED_PATHS: &[&str] = &[
+    "/proc/acpi",
+    "/proc/asound",
+    "/proc/kcore",
+    "/proc/keys",
+    "/proc/latency_stats",
+    "/proc/timer_list",
+    "/proc/timer_stats",
+    "/proc/sched_debug",
+    "/sys/firmware",
+    "/proc/scsi",
+];
+
+/// Paths mounted read-only in the container.
+const READONLY_PATHS: &[&str] = &[
+    "/proc/bus",
+    "/proc/fs",
+    "/proc/irq",
+    "/proc/sys",
+    "/proc/sysrq-trigger",
+];
+
+impl Config {
+    /// Returns the config generated by `runc spec`.
+    ///
+    /// The container runs `sh` in a terminal as root on the root filesystem `rootfs`, with the
+    /// standard filesystems mounted, new PID, network, IPC, UTS, and mount namespaces, and the
+    /// capabilities `CAP_AUDIT_WRITE`, `CAP_KILL`, and `CAP_NET_BIND_SERVICE`. Access to all
+    /// devices is denied by the devices cgroup.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oci_runtime_config::Config;
+    ///
+    /// let config = Config::default_linux();
+    ///
+    /// assert!(config.validate().is_ok());
+    /// assert_eq!(config.process.unwrap().args, ["sh"]);
+    /// ```
+    pub fn default_linux() -> Self {
+        let capabilities = vec![
+            Capability::AuditWrite,
+            Capability::Kill,
+            Capability::NetBindService,
+        ];
+
+        Config {
+            oci_version: crate::OCI_VERSION.to_string(),
+            root: Some(Root {
+                path: PathBuf::from("rootfs"),
+                readonly: Some(true),
+            }),
+            mounts: vec![
+                mount("/proc", MountType::Proc, "proc", &[]),
+                mount(
+                    "/dev",
+                    MountType::Tmpfs,
+                    "tmpfs",
+                    &["nosuid", "strictatime", "mode=755", "size=65536k"],
+                ),
+                mount(
+                    "/dev/pts",
+                    MountType::Devpts,
+                    "devpts",
+                    &[
+                        "nosuid",
+                        "noexec",
+                        "newinstance",
+                        "ptmxmode=0666",
+                        "mode=0620",
+                        "gid=5",
+                    ],
+                ),
+                mount(
+                    "/dev/shm",
+                    MountType::Tmpfs,
+                    "shm",
+                    &["nosuid", "noexec", "nodev", "mode=1777", "size=65536k"],
+                ),
+                mount(
+                    "/dev/mqueue",
+                    MountType::Mqueue,
+                    "mqueue",
+                    &["nosuid", "noexec", "nodev"],
+                ),
+                mount(
+                    "/sys",
+                    MountType::Sysfs,
+                    "sysfs",
+                    &["nosuid", "noexec", "nodev", "ro"],
+                ),
+                mount(
+                    "/sys/fs/cgroup",
+                    MountType::Cgroup,
+                    "cgroup",
+                    &["nosuid", "noexec", "nodev", "relatime", "ro"],
+                ),
+            ],
+            process: Some(Process {
+                terminal: Some(true),
+                console_size: None,
+                user: User::Posix {
+                    uid: 0,
+                    gid: 0,
+                    additional_gids: vec![],
+                },
+                cwd: PathBuf::from("/"),
+                env: vec![
+                    env(
+                        "PATH",
+                        "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin",
+                    ),
+                    env("TERM", "xterm"),
+                ],
+                args: vec![String::from("sh")],
+                rlimits: vec![Rlimit {
+                    type_: RlimitType::Nofile,
+                    soft: 1024,
+                    hard: 1024,
+                }],
+                apparmor_profile: None,
+                capabilities: Some(Capabilities {
+                    effective: capabilities.clone(),
+                    bounding: capabilities.clone(),
+                    inheritable: capabilities.clone(),
+                    permitted: capabilities.clone(),
+                    ambient: capabilities,
+                }),
+                no_new_privileges: Some(true),
+                oom_score_adj: None,
+                selinux_label: None,
+            }),
+            hostname: Some(String::from("runc")),
+            hooks: None,
+            annotations: Default::default(),
+            linux: Some(Linux {
+                namespaces: [
+                    NamespaceType::Pid,
+                    NamespaceType::Network,
+                    NamespaceType::Ipc,
+                    NamespaceType::Uts,
+                    NamespaceType::Mount,
+                ]
+                .iter()
+                .map(|&type_| Namespace { type_, path: None })
+                .collect(),
+                resources: Some(Resources {
+                    devices: vec![Device {
+                        allow: false,
+                        type_: None,
+                        major: None,
+                        minor: None,
+                        access: Some(DeviceAccess::ALL),
+                    }],
+                    ..Default::default()
+                }),
+                masked_paths: MASKED_PATHS.iter().map(PathBuf::from).collect(),
+                readonly_paths: READONLY_PATHS.iter().map(PathBuf::from).collect(),
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Returns the config generated by `runc spec --rootless` for the user `uid` and the group
+    /// `gid` on the host.
+    ///
+    /// Unlike [`default_linux`], the container has a user namespace where root is mapped to `uid`
+    /// and `gid`, and shares the network namespace with the host. `/sys` is bind-mounted from the
+    /// host, `gid=` and `uid=` mount options are removed, and no resource limits are set.
+    ///
+    /// See [`make_rootless`] for details.
+    ///
+    /// [`default_linux`]: #method.default_linux
+    /// [`make_rootless`]: #method.make_rootless
+    pub fn default_linux_rootless(uid: u32, gid: u32) -> Self {
+        let mut config = Self::default_linux();
+        config.make_rootless(&Rootless {
+            uid,
+            gid,
+            keep_network: false,
+        });
+        config
+    }
+}
+
+fn mount(destination: &str, type_: MountType, source: &str, options: &[&str]) -> Mount {
+    Mount {
+        destination: PathBuf::from(destination),
+        type_: Some(type_),
+        source: Some(PathBuf::from(source)),
+        options: options.iter().map(|o| o.to_string()).collect(),
+    }
+}
+
+fn env(name: &str, value: &str) -> EnvVar {
+    EnvVar {
+        name: name.to_string(),
+        value: value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn mount_of<'a>(config: &'a Config, destination: &str) -> &'a Mount {
+        config
+            .mounts
+            .iter()
+            .find(|m| m.destination == Path::new(destination))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_default_linux() {
+        let config = Config::default_linux();
+        config.validate().unwrap();
+
+        let destinations: Vec<_> = config
+            .mounts
+            .iter()
+            .map(|m| m.destination.to_str().unwrap())
+            .collect();
+        assert_eq!(
+            destinations,
+            vec![
+                "/proc",
+                "/dev",
+                "/dev/pts",
+                "/dev/shm",
+                "/dev/mqueue",
+                "/sys",
+                "/sys/fs/cgroup",
+            ]
+        );
+        assert!(mount_of(&config, "/dev/pts")
+            .options
+            .contains(&String::from("gid=5")));
+
+        let linux = config.linux.unwrap();
+        assert_eq!(linux.namespaces.len(), 5);
+        assert!(linux.uid_mappings.is_empty());
+        assert!(linux.masked_paths.contains(&PathBuf::from("/proc/kcore")));
+        assert!(linux.readonly_paths.contains(&PathBuf::from("/proc/sys")));
+    }
+
+    #[test]
+    fn test_default_linux_rootless() {
+        let mut expected = Config::default_linux();
+        expected.make_rootless(&Rootless {
+            uid: 1000,
+            gid: 100,
+            keep_network: false,
+        });
+        assert_eq!(Config::default_linux_rootless(1000, 100), expected);
+    }
+}
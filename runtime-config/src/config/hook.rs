@@ -0,0 +1,452 @@
+//! Execution of lifecycle hooks.
+//!
+//! Hooks are run as described in [the runtime spec]: each hook is executed with its `args` and
+//! `env`, and receives the state of the container on its stdin.
+//!
+//! [the runtime spec]: https://github.com/opencontainers/runtime-spec/blob/v1.0.2/config.md#posix-platform-hooks
+
+use std::{
+    error::Error,
+    ffi::CString,
+    fmt,
+    io::{self, Read, Write},
+    os::{
+        raw::c_char,
+        unix::{
+            ffi::OsStrExt,
+            process::{CommandExt, ExitStatusExt},
+        },
+    },
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Stdio},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use super::{Hook, Hooks};
+
+/// Interval to poll a running hook for its exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Phases of the container lifecycle at which hooks are run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookPhase {
+    /// During the create operation, after the runtime environment has been created and before
+    /// `CreateRuntime` hooks. Deprecated in favor of `CreateRuntime`.
+    Prestart,
+    /// During the create operation, after the runtime environment has been created.
+    CreateRuntime,
+    /// During the create operation, after `CreateRuntime` hooks, in the container namespace.
+    CreateContainer,
+    /// During the start operation, before the user-specified program is executed.
+    StartContainer,
+    /// After the user-specified process is executed but before the start operation returns.
+    Poststart,
+    /// After the container is deleted but before the delete operation returns.
+    Poststop,
+}
+
+/// Output of a hook which has exited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookOutput {
+    /// Exit status of the hook.
+    pub status: ExitStatus,
+    /// Data the hook has written to stdout.
+    pub stdout: Vec<u8>,
+    /// Data the hook has written to stderr.
+    pub stderr: Vec<u8>,
+}
+
+/// Error type that can be returned when a hook is run.
+#[derive(Debug)]
+pub enum HookError {
+    /// The hook could not be executed.
+    Spawn(PathBuf, io::Error),
+    /// Waiting for the hook failed.
+    Io(PathBuf, io::Error),
+    /// The hook did not exit within its timeout in seconds, and was killed.
+    Timeout(PathBuf, u32),
+    /// The hook exited with a non-zero status.
+    Failed(PathBuf, HookOutput),
+}
+
+impl HookPhase {
+    /// All phases in the order they occur in the lifecycle.
+    pub const ALL: [HookPhase; 6] = [
+        HookPhase::Prestart,
+        HookPhase::CreateRuntime,
+        HookPhase::CreateContainer,
+        HookPhase::StartContainer,
+        HookPhase::Poststart,
+        HookPhase::Poststop,
+    ];
+
+    /// Returns whether a failing hook in this phase aborts the operation.
+    ///
+    /// Failures of `Poststart` and `Poststop` hooks are only logged, and the remaining hooks are
+    /// still run.
+    pub fn is_fatal(self) -> bool {
+        match self {
+            HookPhase::Poststart | HookPhase::Poststop => false,
+            _ => true,
+        }
+    }
+}
+
+impl Hooks {
+    /// Returns the hooks run in `phase`.
+    pub fn get(&self, phase: HookPhase) -> &[Hook] {
+        match phase {
+            HookPhase::Prestart => &self.prestart,
+            HookPhase::CreateRuntime => &self.create_runtime,
+            HookPhase::CreateContainer => &self.create_container,
+            HookPhase::StartContainer => &self.start_container,
+            HookPhase::Poststart => &self.poststart,
+            HookPhase::Poststop => &self.poststop,
+        }
+    }
+
+    /// Runs the hooks of `phase` in order, passing `state` to each hook.
+    ///
+    /// Returns the result of each hook which has been run. If the phase is fatal (see
+    /// [`HookPhase::is_fatal`]), no more hooks are run after the first error, which is the last
+    /// result.
+    ///
+    /// [`HookPhase::is_fatal`]: enum.HookPhase.html#method.is_fatal
+    pub fn run(&self, phase: HookPhase, state: &[u8]) -> Vec<Result<HookOutput, HookError>> {
+        let mut results = Vec::new();
+        for hook in self.get(phase) {
+            let result = hook.run(state);
+            let failed = result.is_err();
+            results.push(result);
+            if failed && phase.is_fatal() {
+                break;
+            }
+        }
+        results
+    }
+}
+
+impl Hook {
+    /// Runs this hook, writing `state` to its stdin, and waits for it to exit.
+    ///
+    /// The hook is executed at `path` with `args` as its `argv` (including `argv[0]`) and only
+    /// `env` as its environment. If `timeout` is set and the hook is still running after that many
+    /// seconds, it is killed along with its process group and `HookError::Timeout` is returned.
+    /// The hook is run in a new process group for this purpose.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oci_runtime_config::config::Hook;
+    ///
+    /// let hook = Hook {
+    ///     path: "/bin/cat".into(),
+    ///     args: vec![],
+    ///     env: vec![],
+    ///     timeout: Some(5),
+    /// };
+    /// let output = hook.run(br#"{"id":"foo"}"#).unwrap();
+    ///
+    /// assert!(output.status.success());
+    /// assert_eq!(output.stdout, br#"{"id":"foo"}"#);
+    /// ```
+    pub fn run(&self, state: &[u8]) -> Result<HookOutput, HookError> {
+        let exec = self
+            .exec_args()
+            .map_err(|e| HookError::Spawn(self.path.clone(), e))?;
+        let mut command = Command::new(&self.path);
+        command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        unsafe {
+            command.pre_exec(move || exec.exec());
+        }
+
+        let mut child = command
+            .spawn()
+            .map_err(|e| HookError::Spawn(self.path.clone(), e))?;
+
+        let mut stdin = child.stdin.take().unwrap();
+        let state = state.to_vec();
+        let writer = thread::spawn(move || match stdin.write_all(&state) {
+            // The hook may exit without reading the state
+            Err(ref e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            r => r,
+        });
+        let stdout = read_to_end(child.stdout.take().unwrap());
+        let stderr = read_to_end(child.stderr.take().unwrap());
+
+        let status = self
+            .wait(&mut child)
+            .map_err(|e| HookError::Io(self.path.clone(), e))?;
+        let status = match status {
+            Some(status) => status,
+            None => {
+                // Descendants of the hook which have left its process group may still hold the
+                // output pipes open, so the readers are detached rather than joined.
+                return Err(HookError::Timeout(
+                    self.path.clone(),
+                    self.timeout.unwrap_or_default(),
+                ));
+            }
+        };
+
+        let io_error = |e| HookError::Io(self.path.clone(), e);
+        join(writer).map_err(io_error)?;
+        let output = HookOutput {
+            status,
+            stdout: join(stdout).map_err(io_error)?,
+            stderr: join(stderr).map_err(io_error)?,
+        };
+
+        if output.status.success() {
+            Ok(output)
+        } else {
+            Err(HookError::Failed(self.path.clone(), output))
+        }
+    }
+
+    /// Returns the arguments of execve(2) to execute this hook.
+    fn exec_args(&self) -> io::Result<ExecArgs> {
+        let path = cstring(self.path.as_os_str().as_bytes())?;
+        let argv = if self.args.is_empty() {
+            vec![path.clone()]
+        } else {
+            self.args
+                .iter()
+                .map(|a| cstring(a.as_bytes()))
+                .collect::<io::Result<_>>()?
+        };
+        let envp = self
+            .env
+            .iter()
+            .map(|e| cstring(e.to_string().as_bytes()))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(ExecArgs {
+            argv_ptrs: null_terminated(&argv),
+            envp_ptrs: null_terminated(&envp),
+            path,
+            _argv: argv,
+            _envp: envp,
+        })
+    }
+
+    /// Waits for `child` to exit, or kills its process group after the timeout and returns `None`.
+    fn wait(&self, child: &mut Child) -> io::Result<Option<ExitStatus>> {
+        let deadline = match self.timeout {
+            Some(timeout) => Instant::now() + Duration::from_secs(u64::from(timeout)),
+            None => return child.wait().map(Some),
+        };
+
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok(Some(status));
+            }
+            if Instant::now() >= deadline {
+                // The hook is the leader of its process group, so the group ID is its PID
+                if unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                child.wait()?;
+                return Ok(None);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Arguments of execve(2), which are prepared before fork(2) as the child must not allocate.
+struct ExecArgs {
+    path: CString,
+    argv_ptrs: Vec<*const c_char>,
+    envp_ptrs: Vec<*const c_char>,
+    // Owners of the strings pointed to by `argv_ptrs` and `envp_ptrs`
+    _argv: Vec<CString>,
+    _envp: Vec<CString>,
+}
+
+// The pointers only point to the strings owned by `ExecArgs`, which are never modified.
+unsafe impl Send for ExecArgs {}
+unsafe impl Sync for ExecArgs {}
+
+impl ExecArgs {
+    /// Moves the calling process into a new process group and executes the hook, which only
+    /// returns on failure.
+    fn exec(&self) -> io::Result<()> {
+        unsafe {
+            if libc::setpgid(0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            libc::execve(
+                self.path.as_ptr(),
+                self.argv_ptrs.as_ptr(),
+                self.envp_ptrs.as_ptr(),
+            );
+        }
+        Err(io::Error::last_os_error())
+    }
+}
+
+fn cstring(bytes: &[u8]) -> io::Result<CString> {
+    CString::new(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+fn null_terminated(strings: &[CString]) -> Vec<*const c_char> {
+    strings
+        .iter()
+        .map(|s| s.as_ptr())
+        .chain(Some(std::ptr::null()))
+        .collect()
+}
+
+fn join<T>(handle: JoinHandle<io::Result<T>>) -> io::Result<T> {
+    handle
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::Other, "thread panicked")))
+}
+
+fn read_to_end<R: Read + Send + 'static>(mut reader: R) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf)?;
+        Ok(buf)
+    })
+}
+
+impl fmt::Display for HookPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HookPhase::Prestart => "prestart",
+            HookPhase::CreateRuntime => "createRuntime",
+            HookPhase::CreateContainer => "createContainer",
+            HookPhase::StartContainer => "startContainer",
+            HookPhase::Poststart => "poststart",
+            HookPhase::Poststop => "poststop",
+        })
+    }
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookError::Spawn(path, e) => write!(f, "Failed to execute {}: {}", path.display(), e),
+            HookError::Io(path, e) => write!(f, "Failed to run {}: {}", path.display(), e),
+            HookError::Timeout(path, timeout) => {
+                write!(f, "{} timed out after {}s", path.display(), timeout)
+            }
+            HookError::Failed(path, output) => match output.status.code() {
+                Some(code) => write!(f, "{} exited with status {}", path.display(), code),
+                None => write!(
+                    f,
+                    "{} was killed by signal {}",
+                    path.display(),
+                    output.status.signal().unwrap_or_default()
+                ),
+            },
+        }
+    }
+}
+
+impl Error for HookError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HookError::Spawn(_, e) | HookError::Io(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use environ_str::EnvVar;
+
+    fn sh(script: &str, timeout: Option<u32>) -> Hook {
+        Hook {
+            path: PathBuf::from("/bin/sh"),
+            args: vec![String::from("hook"), String::from("-c"), script.to_string()],
+            env: vec![EnvVar {
+                name: String::from("FOO"),
+                value: String::from("bar"),
+            }],
+            timeout,
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let hook = sh(
+            r#"read state; echo "$0 $FOO $HOME $state"; echo err >&2"#,
+            None,
+        );
+        let output = hook.run(b"{}\n").unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hook bar  {}\n");
+        assert_eq!(output.stderr, b"err\n");
+
+        // A hook which does not read the state
+        let output = sh("true", Some(5)).run(&[0; 1 << 20]).unwrap();
+        assert!(output.stdout.is_empty());
+    }
+
+    #[test]
+    fn test_run_phase() {
+        let hooks = Hooks {
+            create_runtime: vec![sh("exit 0", None), sh("exit 1", None), sh("exit 0", None)],
+            poststop: vec![sh("exit 1", None), sh("exit 0", None)],
+            ..Default::default()
+        };
+
+        let results = hooks.run(HookPhase::CreateRuntime, b"{}");
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert_eq!(
+            results[1].as_ref().unwrap_err().to_string(),
+            "/bin/sh exited with status 1"
+        );
+
+        let results = hooks.run(HookPhase::Poststop, b"{}");
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_err());
+        assert!(results[1].is_ok());
+
+        assert!(hooks.run(HookPhase::Prestart, b"{}").is_empty());
+    }
+
+    #[test]
+    fn err_run() {
+        let start = Instant::now();
+        let err = sh("sleep 10", Some(1)).run(b"{}").unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(err.to_string(), "/bin/sh timed out after 1s");
+
+        // Descendants of the hook are killed with it
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let script = format!("sleep 10 & echo $! > {}; wait", pid_file.display());
+        sh(&script, Some(1)).run(b"{}").unwrap_err();
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let stat = PathBuf::from("/proc").join(pid.trim()).join("stat");
+        let killed = (0..100).any(|_| {
+            // The orphan may be left as a zombie until it is reaped
+            let killed = std::fs::read_to_string(&stat)
+                .map_or(true, |s| s.rsplit(") ").next().unwrap().starts_with('Z'));
+            thread::sleep(POLL_INTERVAL);
+            killed
+        });
+        assert!(killed);
+
+        let err = sh("kill -9 $$", None).run(b"{}").unwrap_err();
+        assert_eq!(err.to_string(), "/bin/sh was killed by signal 9");
+
+        let mut hook = sh("true", None);
+        hook.path = PathBuf::from("/nonexistent");
+        match hook.run(b"{}") {
+            Err(HookError::Spawn(..)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
@@ -5,6 +5,12 @@
 //! [OCI runtime spec]: https://github.com/opencontainers/runtime-spec/blob/v1.0.1/config.md
 
 mod capability;
+mod default;
+mod hook;
+mod mount;
+mod rlimit;
+mod rootless;
+mod validate;
 
 use std::{collections::HashMap, path::PathBuf};
 
@@ -12,7 +18,12 @@ use environ_str::EnvVar;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-pub use capability::{Capabilities, Capability};
+pub use capability::{Capabilities, Capability, CapabilitySet, ParseCapabilityError};
+pub use hook::{HookError, HookOutput, HookPhase};
+pub use mount::{MountOptions, MountType};
+pub use rlimit::{RlimitResource, RlimitType};
+pub use rootless::Rootless;
+pub use validate::{ValidationError, Violation, ViolationKind};
 
 /// Runtime configuration schema.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -86,13 +97,17 @@ pub struct Mount {
         feature = "serde",
         serde(rename = "type", skip_serializing_if = "Option::is_none")
     )]
-    pub type_: Option<String>, // TODO: Use proper type?
+    pub type_: Option<MountType>,
 
     /// Device name, directory name, or dummy.
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub source: Option<PathBuf>,
 
     /// Mount options of the filesystem to be mounted.
+    ///
+    /// See [`MountOptions`] for parsing the options.
+    ///
+    /// [`MountOptions`]: struct.MountOptions.html
     #[cfg_attr(
         feature = "serde",
         serde(skip_serializing_if = "Vec::is_empty", default)
@@ -213,7 +228,7 @@ pub enum User {
 pub struct Rlimit {
     /// Type of platform resource being limited.
     #[cfg_attr(feature = "serde", serde(rename = "type"))]
-    pub type_: String, // TODO: Use proper type?
+    pub type_: RlimitType,
 
     /// Value of the limit enforced for the corresponding resource.
     pub soft: u64,
@@ -223,16 +238,47 @@ pub struct Rlimit {
 }
 
 /// Set of hooks for configuring custom actions related to the lifecycle of a container.
-#[derive(Debug, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+///
+/// See [`HookPhase`] for when each list of hooks is run.
+///
+/// [`HookPhase`]: enum.HookPhase.html
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
 pub struct Hooks {
     /// Pre-start hooks.
+    ///
+    /// Deprecated in favor of `create_runtime`, `create_container`, and `start_container`.
     #[cfg_attr(
         feature = "serde",
         serde(skip_serializing_if = "Vec::is_empty", default)
     )]
     pub prestart: Vec<Hook>,
 
+    /// Hooks run in the runtime namespace after the container environment is created.
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip_serializing_if = "Vec::is_empty", default)
+    )]
+    pub create_runtime: Vec<Hook>,
+
+    /// Hooks run in the container namespace after the container environment is created.
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip_serializing_if = "Vec::is_empty", default)
+    )]
+    pub create_container: Vec<Hook>,
+
+    /// Hooks run in the container namespace before the user-specified process is executed.
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip_serializing_if = "Vec::is_empty", default)
+    )]
+    pub start_container: Vec<Hook>,
+
     /// Post-start hooks.
     #[cfg_attr(
         feature = "serde",
@@ -312,4 +358,23 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_deser_hooks() {
+        const JSON_HOOKS: &str = r#"{
+            "createRuntime": [{"path": "/usr/bin/fix-mounts", "args": ["fix-mounts", "arg1"]}],
+            "startContainer": [{"path": "/usr/bin/refresh-ldcache", "timeout": 5}]
+        }"#;
+
+        let hooks: Hooks = serde_json::from_str(JSON_HOOKS).unwrap();
+
+        assert!(hooks.prestart.is_empty());
+        assert_eq!(hooks.create_runtime[0].args, ["fix-mounts", "arg1"]);
+        assert!(hooks.create_container.is_empty());
+        assert_eq!(hooks.start_container[0].timeout, Some(5));
+        assert_eq!(
+            serde_json::to_value(&hooks).unwrap(),
+            serde_json::from_str::<serde_json::Value>(JSON_HOOKS).unwrap()
+        );
+    }
 }
@@ -0,0 +1,282 @@
+//! Mount types and parsing of mount options.
+//!
+//! Options are translated into the arguments of mount(2) as described in the [mount(8)] man page.
+//!
+//! [mount(8)]: http://man7.org/linux/man-pages/man8/mount.8.html
+
+use std::{convert::Infallible, fmt, os::raw::c_ulong, str::FromStr};
+
+use super::Mount;
+
+/// Types of filesystems to be mounted.
+///
+/// When the feature `serde` is enabled, `MountType` can be serialized to / deserialized from the
+/// name of the filesystem type (e.g. `tmpfs`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MountType {
+    /// Bind mount (type `bind`).
+    Bind,
+    /// proc filesystem (type `proc`).
+    Proc,
+    /// sysfs filesystem (type `sysfs`).
+    Sysfs,
+    /// tmpfs filesystem (type `tmpfs`).
+    Tmpfs,
+    /// devpts filesystem (type `devpts`).
+    Devpts,
+    /// POSIX message queue filesystem (type `mqueue`).
+    Mqueue,
+    /// cgroup v1 filesystem (type `cgroup`).
+    Cgroup,
+    /// cgroup v2 filesystem (type `cgroup2`).
+    Cgroup2,
+    /// overlay filesystem (type `overlay`).
+    Overlay,
+    /// Other filesystem type.
+    Other(String),
+}
+
+/// Mount options parsed into flags and data for mount(2).
+///
+/// Options are parsed in the same way as mount(8): flags such as `ro` and `nosuid` are set in
+/// `flags`, their negations such as `rw` and `suid` clear them, the last propagation type such as
+/// `rshared` is set in `propagation`, and the other options such as `size=65536k` are kept in
+/// `data`.
+/// `defaults` clears the flags set by `ro`, `nosuid`, `nodev`, `noexec` and `sync` before it.
+///
+/// Flags other than `MS_BIND` and `MS_REC` are ignored by the kernel when a bind mount is created,
+/// so a bind mount with e.g. `ro` needs a second mount(2) call with `MS_REMOUNT | MS_BIND` and the
+/// flags to take effect.
+///
+/// # Examples
+///
+/// ```
+/// use oci_runtime_config::config::MountOptions;
+///
+/// let options = MountOptions::parse(&["rbind", "ro", "rprivate", "mode=755"]);
+///
+/// assert_eq!(options.flags, libc::MS_BIND | libc::MS_REC | libc::MS_RDONLY);
+/// assert_eq!(options.propagation, libc::MS_PRIVATE | libc::MS_REC);
+/// assert_eq!(options.data(), "mode=755");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MountOptions {
+    /// `MS_*` flags to mount the filesystem with.
+    pub flags: c_ulong,
+
+    /// `MS_*` propagation flags, which MUST be applied with a separate mount(2) call.
+    pub propagation: c_ulong,
+
+    /// Filesystem-specific options, in order.
+    pub data: Vec<String>,
+}
+
+/// Options which clear (`true`) or set (`false`) a flag.
+const FLAGS: &[(&str, bool, c_ulong)] = &[
+    ("acl", false, libc::MS_POSIXACL),
+    ("async", true, libc::MS_SYNCHRONOUS),
+    ("atime", true, libc::MS_NOATIME),
+    ("bind", false, libc::MS_BIND),
+    (
+        "defaults",
+        true,
+        libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC | libc::MS_SYNCHRONOUS,
+    ),
+    ("dev", true, libc::MS_NODEV),
+    ("diratime", true, libc::MS_NODIRATIME),
+    ("dirsync", false, libc::MS_DIRSYNC),
+    ("exec", true, libc::MS_NOEXEC),
+    ("mand", false, libc::MS_MANDLOCK),
+    ("noacl", true, libc::MS_POSIXACL),
+    ("noatime", false, libc::MS_NOATIME),
+    ("nodev", false, libc::MS_NODEV),
+    ("nodiratime", false, libc::MS_NODIRATIME),
+    ("noexec", false, libc::MS_NOEXEC),
+    ("nomand", true, libc::MS_MANDLOCK),
+    ("norelatime", true, libc::MS_RELATIME),
+    ("nostrictatime", true, libc::MS_STRICTATIME),
+    ("nosuid", false, libc::MS_NOSUID),
+    ("rbind", false, libc::MS_BIND | libc::MS_REC),
+    ("relatime", false, libc::MS_RELATIME),
+    ("remount", false, libc::MS_REMOUNT),
+    ("ro", false, libc::MS_RDONLY),
+    ("rw", true, libc::MS_RDONLY),
+    ("strictatime", false, libc::MS_STRICTATIME),
+    ("suid", true, libc::MS_NOSUID),
+    ("sync", false, libc::MS_SYNCHRONOUS),
+];
+
+const PROPAGATIONS: &[(&str, c_ulong)] = &[
+    ("private", libc::MS_PRIVATE),
+    ("rprivate", libc::MS_PRIVATE | libc::MS_REC),
+    ("shared", libc::MS_SHARED),
+    ("rshared", libc::MS_SHARED | libc::MS_REC),
+    ("slave", libc::MS_SLAVE),
+    ("rslave", libc::MS_SLAVE | libc::MS_REC),
+    ("unbindable", libc::MS_UNBINDABLE),
+    ("runbindable", libc::MS_UNBINDABLE | libc::MS_REC),
+];
+
+impl MountOptions {
+    /// Parses mount options.
+    pub fn parse<S: AsRef<str>>(options: &[S]) -> Self {
+        let mut parsed = Self::default();
+
+        for option in options {
+            let option = option.as_ref();
+
+            if let Some(&(_, clear, flag)) = FLAGS.iter().find(|f| f.0 == option) {
+                if clear {
+                    parsed.flags &= !flag;
+                } else {
+                    parsed.flags |= flag;
+                }
+            } else if let Some(&(_, flag)) = PROPAGATIONS.iter().find(|p| p.0 == option) {
+                // Only one propagation type takes effect, so the last one wins
+                parsed.propagation = flag;
+            } else {
+                parsed.data.push(option.to_string());
+            }
+        }
+
+        parsed
+    }
+
+    /// Returns the data argument for mount(2), which is the filesystem-specific options joined
+    /// with commas.
+    pub fn data(&self) -> String {
+        self.data.join(",")
+    }
+
+    /// Returns whether the filesystem is bind-mounted.
+    pub fn is_bind(&self) -> bool {
+        self.flags & libc::MS_BIND != 0
+    }
+}
+
+impl Mount {
+    /// Parses the options of this mount.
+    ///
+    /// A mount of type `bind` is bind-mounted even without the `bind` or `rbind` option.
+    pub fn parse_options(&self) -> MountOptions {
+        let mut options = MountOptions::parse(&self.options);
+        if self.type_ == Some(MountType::Bind) {
+            options.flags |= libc::MS_BIND;
+        }
+        options
+    }
+}
+
+impl fmt::Display for MountType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bind => "bind",
+            Self::Proc => "proc",
+            Self::Sysfs => "sysfs",
+            Self::Tmpfs => "tmpfs",
+            Self::Devpts => "devpts",
+            Self::Mqueue => "mqueue",
+            Self::Cgroup => "cgroup",
+            Self::Cgroup2 => "cgroup2",
+            Self::Overlay => "overlay",
+            Self::Other(s) => s,
+        })
+    }
+}
+
+impl FromStr for MountType {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "bind" => Self::Bind,
+            "proc" => Self::Proc,
+            "sysfs" => Self::Sysfs,
+            "tmpfs" => Self::Tmpfs,
+            "devpts" => Self::Devpts,
+            "mqueue" => Self::Mqueue,
+            "cgroup" => Self::Cgroup,
+            "cgroup2" => Self::Cgroup2,
+            "overlay" => Self::Overlay,
+            _ => Self::Other(s.to_string()),
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for MountType {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MountType {
+    fn deserialize<D: serde::Deserializer<'de>>(deser: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deser)?;
+        Ok(s.parse().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_mount_type_from_str() {
+        assert_eq!("cgroup2".parse(), Ok(MountType::Cgroup2));
+        assert_eq!(
+            "fuse.sshfs".parse(),
+            Ok(MountType::Other("fuse.sshfs".to_string()))
+        );
+        assert_eq!(MountType::Other("nfs".to_string()).to_string(), "nfs");
+    }
+
+    #[test]
+    fn test_mount_options_parse() {
+        let options =
+            MountOptions::parse(&["nosuid", "noexec", "strictatime", "mode=755", "size=65536k"]);
+        assert_eq!(
+            options,
+            MountOptions {
+                flags: libc::MS_NOSUID | libc::MS_NOEXEC | libc::MS_STRICTATIME,
+                propagation: 0,
+                data: vec!["mode=755".to_string(), "size=65536k".to_string()],
+            }
+        );
+        assert_eq!(options.data(), "mode=755,size=65536k");
+
+        // Later options override earlier ones
+        let options = MountOptions::parse(&["ro", "nodev", "rw", "dev", "defaults"]);
+        assert_eq!(options.flags, 0);
+
+        // `defaults` means `rw,suid,dev,exec,async`
+        let options = MountOptions::parse(&["ro", "nosuid", "nodev", "noexec", "defaults", "bind"]);
+        assert_eq!(options.flags, libc::MS_BIND);
+        let options = MountOptions::parse(&["defaults", "ro"]);
+        assert_eq!(options.flags, libc::MS_RDONLY);
+
+        // The last propagation type wins
+        let options = MountOptions::parse(&["private", "rshared"]);
+        assert_eq!(options.propagation, libc::MS_SHARED | libc::MS_REC);
+        let options = MountOptions::parse(&["rslave", "private"]);
+        assert_eq!(options.propagation, libc::MS_PRIVATE);
+    }
+
+    #[test]
+    fn test_mount_parse_options() {
+        let mount = Mount {
+            destination: PathBuf::from("/data"),
+            type_: Some(MountType::Bind),
+            source: Some(PathBuf::from("/srv/data")),
+            options: vec!["ro".to_string(), "rslave".to_string()],
+        };
+
+        let options = mount.parse_options();
+        assert!(options.is_bind());
+        assert_eq!(options.flags, libc::MS_BIND | libc::MS_RDONLY);
+        assert_eq!(options.propagation, libc::MS_SLAVE | libc::MS_REC);
+        assert!(options.data.is_empty());
+    }
+}
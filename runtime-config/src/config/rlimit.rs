@@ -0,0 +1,143 @@
+//! Resource limits of processes.
+//!
+//! For more information about the resources, see the [getrlimit(2)] man page.
+//!
+//! [getrlimit(2)]: http://man7.org/linux/man-pages/man2/getrlimit.2.html
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Type of the resource argument of getrlimit(2) and setrlimit(2) on the target platform.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+pub type RlimitResource = libc::__rlimit_resource_t;
+
+/// Type of the resource argument of getrlimit(2) and setrlimit(2) on the target platform.
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+pub type RlimitResource = libc::c_int;
+
+/// Kinds of resources that can be limited for a process.
+///
+/// When the feature `serde` is enabled, `RlimitType` can be serialized to / deserialized from a
+/// resource name as defined in the [getrlimit(2)] man page (e.g. `RLIMIT_NOFILE`).
+///
+/// [getrlimit(2)]: http://man7.org/linux/man-pages/man2/getrlimit.2.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RlimitType {
+    /// `RLIMIT_AS`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_AS"))]
+    As,
+
+    /// `RLIMIT_CORE`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_CORE"))]
+    Core,
+
+    /// `RLIMIT_CPU`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_CPU"))]
+    Cpu,
+
+    /// `RLIMIT_DATA`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_DATA"))]
+    Data,
+
+    /// `RLIMIT_FSIZE`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_FSIZE"))]
+    Fsize,
+
+    /// `RLIMIT_LOCKS`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_LOCKS"))]
+    Locks,
+
+    /// `RLIMIT_MEMLOCK`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_MEMLOCK"))]
+    Memlock,
+
+    /// `RLIMIT_MSGQUEUE`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_MSGQUEUE"))]
+    Msgqueue,
+
+    /// `RLIMIT_NICE`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_NICE"))]
+    Nice,
+
+    /// `RLIMIT_NOFILE`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_NOFILE"))]
+    Nofile,
+
+    /// `RLIMIT_NPROC`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_NPROC"))]
+    Nproc,
+
+    /// `RLIMIT_RSS`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_RSS"))]
+    Rss,
+
+    /// `RLIMIT_RTPRIO`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_RTPRIO"))]
+    Rtprio,
+
+    /// `RLIMIT_RTTIME`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_RTTIME"))]
+    Rttime,
+
+    /// `RLIMIT_SIGPENDING`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_SIGPENDING"))]
+    Sigpending,
+
+    /// `RLIMIT_STACK`
+    #[cfg_attr(feature = "serde", serde(rename = "RLIMIT_STACK"))]
+    Stack,
+}
+
+impl RlimitType {
+    /// Returns the resource constant to be passed to getrlimit(2) or setrlimit(2).
+    pub fn resource(self) -> RlimitResource {
+        match self {
+            RlimitType::As => libc::RLIMIT_AS,
+            RlimitType::Core => libc::RLIMIT_CORE,
+            RlimitType::Cpu => libc::RLIMIT_CPU,
+            RlimitType::Data => libc::RLIMIT_DATA,
+            RlimitType::Fsize => libc::RLIMIT_FSIZE,
+            RlimitType::Locks => libc::RLIMIT_LOCKS,
+            RlimitType::Memlock => libc::RLIMIT_MEMLOCK,
+            RlimitType::Msgqueue => libc::RLIMIT_MSGQUEUE,
+            RlimitType::Nice => libc::RLIMIT_NICE,
+            RlimitType::Nofile => libc::RLIMIT_NOFILE,
+            RlimitType::Nproc => libc::RLIMIT_NPROC,
+            RlimitType::Rss => libc::RLIMIT_RSS,
+            RlimitType::Rtprio => libc::RLIMIT_RTPRIO,
+            RlimitType::Rttime => libc::RLIMIT_RTTIME,
+            RlimitType::Sigpending => libc::RLIMIT_SIGPENDING,
+            RlimitType::Stack => libc::RLIMIT_STACK,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rlimit_type_resource() {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        let ret = unsafe { libc::getrlimit(RlimitType::Nofile.resource(), &mut limit) };
+
+        assert_eq!(ret, 0);
+        assert!(limit.rlim_cur <= limit.rlim_max);
+        assert_eq!(RlimitType::Core.resource(), libc::RLIMIT_CORE);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_rlimit_type_serde() {
+        let json = r#"["RLIMIT_NOFILE","RLIMIT_RTTIME"]"#;
+        let types: Vec<RlimitType> = serde_json::from_str(json).unwrap();
+
+        assert_eq!(types, vec![RlimitType::Nofile, RlimitType::Rttime]);
+        assert_eq!(serde_json::to_string(&types).unwrap(), json);
+        assert!(serde_json::from_str::<RlimitType>(r#""RLIMIT_NOFILES""#).is_err());
+    }
+}
@@ -0,0 +1,251 @@
+//! Rewriting of configs for rootless containers.
+//!
+//! The rewriting follows [`runc spec --rootless`], and can be applied to any config.
+//!
+//! [`runc spec --rootless`]: https://github.com/opencontainers/runc/blob/v1.0.0/libcontainer/specconv/example.go
+
+use std::path::{Path, PathBuf};
+
+use crate::linux::{Namespace, NamespaceType, UserNamespaceMappings};
+
+use super::{Config, Mount, MountType};
+
+/// Unprivileged user on the host which runs a rootless container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rootless {
+    /// User ID on the host, to which root in the container is mapped.
+    pub uid: u32,
+
+    /// Group ID on the host, to which root in the container is mapped.
+    pub gid: u32,
+
+    /// Whether the network namespace is kept, e.g. when its network is set up by slirp4netns.
+    ///
+    /// An unprivileged user cannot set up a network in a new network namespace, so the namespace
+    /// is dropped by default and the container shares the network with the host.
+    pub keep_network: bool,
+}
+
+impl Rootless {
+    /// Returns the effective user and group of the current process.
+    pub fn current() -> Self {
+        Rootless {
+            uid: unsafe { libc::geteuid() },
+            gid: unsafe { libc::getegid() },
+            keep_network: false,
+        }
+    }
+}
+
+impl Config {
+    /// Rewrites this config to be run by the unprivileged user `rootless`.
+    ///
+    /// The config is rewritten as follows:
+    ///
+    /// * A new user namespace is created, where root is mapped to `rootless.uid` and
+    ///   `rootless.gid`. An existing user namespace to be created and the ID mappings are replaced.
+    /// * The network namespace to be created is dropped unless `rootless.keep_network` is set.
+    /// * Namespaces with a `path` are kept, as they are joined rather than created. If a user
+    ///   namespace is joined, no user namespace is created and the ID mappings are kept.
+    /// * A mount at `/sys` is replaced with a read-only recursive bind mount of `/sys` on the host,
+    ///   as sysfs cannot be mounted without a network namespace owned by the user.
+    /// * `gid=` and `uid=` options are removed from mounts, as the IDs may not be mapped.
+    /// * `linux.cgroupsPath` and `linux.resources` are removed, as cgroups cannot be created by
+    ///   the user.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oci_runtime_config::{config::Rootless, Config};
+    ///
+    /// let mut config = Config::default_linux();
+    /// config.make_rootless(&Rootless {
+    ///     uid: 1000,
+    ///     gid: 1000,
+    ///     keep_network: false,
+    /// });
+    /// let linux = config.linux.unwrap();
+    ///
+    /// assert_eq!(linux.uid_mappings[0].host_id, 1000);
+    /// assert_eq!(linux.resources, None);
+    /// ```
+    pub fn make_rootless(&mut self, rootless: &Rootless) {
+        for mount in &mut self.mounts {
+            if mount.destination == Path::new("/sys") {
+                *mount = Mount {
+                    destination: PathBuf::from("/sys"),
+                    type_: Some(MountType::Bind),
+                    source: Some(PathBuf::from("/sys")),
+                    options: ["rbind", "nosuid", "noexec", "nodev", "ro"]
+                        .iter()
+                        .map(|o| o.to_string())
+                        .collect(),
+                };
+            } else {
+                mount
+                    .options
+                    .retain(|o| !o.starts_with("gid=") && !o.starts_with("uid="));
+            }
+        }
+
+        let linux = self.linux.get_or_insert_with(Default::default);
+        linux.namespaces.retain(|ns| {
+            ns.path.is_some()
+                || match ns.type_ {
+                    NamespaceType::User => false,
+                    NamespaceType::Network => rootless.keep_network,
+                    _ => true,
+                }
+        });
+        if !linux
+            .namespaces
+            .iter()
+            .any(|ns| ns.type_ == NamespaceType::User)
+        {
+            linux.namespaces.push(Namespace {
+                type_: NamespaceType::User,
+                path: None,
+            });
+            linux.uid_mappings = vec![root_mapping(rootless.uid)];
+            linux.gid_mappings = vec![root_mapping(rootless.gid)];
+        }
+        linux.cgroups_path = None;
+        linux.resources = None;
+    }
+}
+
+/// Returns a mapping of root in the container to `host_id`.
+fn root_mapping(host_id: u32) -> UserNamespaceMappings {
+    UserNamespaceMappings {
+        host_id,
+        container_id: 0,
+        size: 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rootless(keep_network: bool) -> Rootless {
+        Rootless {
+            uid: 1000,
+            gid: 100,
+            keep_network,
+        }
+    }
+
+    #[test]
+    fn test_make_rootless() {
+        let mut config = Config::default_linux();
+        {
+            let linux = config.linux.as_mut().unwrap();
+            linux.namespaces.push(Namespace {
+                type_: NamespaceType::User,
+                path: None,
+            });
+            linux.uid_mappings = vec![root_mapping(0)];
+            linux.gid_mappings = vec![root_mapping(0)];
+            linux.cgroups_path = Some(PathBuf::from("/myRuntime/myContainer"));
+        }
+        config.mounts.push(Mount {
+            destination: PathBuf::from("/data"),
+            type_: Some(MountType::Tmpfs),
+            source: None,
+            options: vec![String::from("uid=1"), String::from("mode=700")],
+        });
+        config.make_rootless(&rootless(false));
+        config.validate().unwrap();
+
+        let sys = config
+            .mounts
+            .iter()
+            .find(|m| m.destination == Path::new("/sys"))
+            .unwrap();
+        assert_eq!(sys.type_, Some(MountType::Bind));
+        assert!(sys.parse_options().is_bind());
+        assert!(config
+            .mounts
+            .iter()
+            .flat_map(|m| &m.options)
+            .all(|o| !o.starts_with("gid=") && !o.starts_with("uid=")));
+
+        let linux = config.linux.unwrap();
+        let types: Vec<_> = linux.namespaces.iter().map(|ns| ns.type_).collect();
+        assert_eq!(
+            types,
+            vec![
+                NamespaceType::Pid,
+                NamespaceType::Ipc,
+                NamespaceType::Uts,
+                NamespaceType::Mount,
+                NamespaceType::User,
+            ]
+        );
+        assert_eq!(linux.namespaces[4].path, None);
+        assert_eq!(linux.uid_mappings, vec![root_mapping(1000)]);
+        assert_eq!(linux.gid_mappings, vec![root_mapping(100)]);
+        assert_eq!(linux.cgroups_path, None);
+        assert_eq!(linux.resources, None);
+    }
+
+    #[test]
+    fn test_make_rootless_joined_namespaces() {
+        let mut config = Config::default_linux();
+        {
+            let linux = config.linux.as_mut().unwrap();
+            for ns in &mut linux.namespaces {
+                if ns.type_ == NamespaceType::Network {
+                    ns.path = Some(PathBuf::from("/var/run/netns/slirp"));
+                }
+            }
+            linux.namespaces.push(Namespace {
+                type_: NamespaceType::User,
+                path: Some(PathBuf::from("/proc/1/ns/user")),
+            });
+            linux.uid_mappings = vec![root_mapping(0)];
+            linux.gid_mappings = vec![root_mapping(0)];
+        }
+        config.make_rootless(&rootless(false));
+        config.validate().unwrap();
+
+        // Namespaces to be joined are kept, and no user namespace is created
+        let linux = config.linux.unwrap();
+        let joined: Vec<_> = linux
+            .namespaces
+            .iter()
+            .filter(|ns| ns.path.is_some())
+            .map(|ns| ns.type_)
+            .collect();
+        assert_eq!(joined, vec![NamespaceType::Network, NamespaceType::User]);
+        assert_eq!(
+            linux
+                .namespaces
+                .iter()
+                .filter(|ns| ns.type_ == NamespaceType::User)
+                .count(),
+            1
+        );
+        assert_eq!(linux.uid_mappings, vec![root_mapping(0)]);
+        assert_eq!(linux.gid_mappings, vec![root_mapping(0)]);
+    }
+
+    #[test]
+    fn test_make_rootless_keep_network() {
+        let mut config = Config::default_linux();
+        config.make_rootless(&rootless(true));
+        let linux = config.linux.unwrap();
+        assert!(linux
+            .namespaces
+            .iter()
+            .any(|ns| ns.type_ == NamespaceType::Network));
+
+        // A config without the Linux section gets one
+        let mut config = Config::default_linux();
+        config.linux = None;
+        config.make_rootless(&rootless(false));
+        let linux = config.linux.unwrap();
+        assert_eq!(linux.namespaces.len(), 1);
+        assert_eq!(linux.uid_mappings, vec![root_mapping(1000)]);
+    }
+}
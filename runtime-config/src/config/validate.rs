@@ -0,0 +1,411 @@
+//! Validation of configs against the runtime spec.
+//!
+//! Only requirements which can be checked without the host are validated, e.g. whether paths are
+//! absolute or fields are duplicated. See [`Config::validate`] for the list of the requirements.
+//!
+//! [`Config::validate`]: ../struct.Config.html#method.validate
+
+use std::{
+    collections::HashSet,
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::{Config, HookPhase, RlimitType},
+    linux::NamespaceType,
+    OCI_VERSION,
+};
+
+/// Error type that can be returned when a config violates the runtime spec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// All violations found in the config, in order of fields.
+    pub violations: Vec<Violation>,
+}
+
+/// Violation of the runtime spec by a field of a config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// Path to the field as in the JSON representation, e.g. `mounts[0].destination`.
+    pub field: String,
+    /// What is wrong with the field.
+    pub kind: ViolationKind,
+}
+
+/// Kinds of violations of the runtime spec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// Version is not in the SemVer format.
+    InvalidVersion(String),
+    /// Version is not compatible with `OCI_VERSION`.
+    IncompatibleVersion(String),
+    /// Path is not absolute.
+    NotAbsolute(PathBuf),
+    /// Value is empty.
+    Empty,
+    /// Value is not greater than zero.
+    NotPositive,
+    /// Namespace type appears more than once.
+    DuplicateNamespace(NamespaceType),
+    /// Rlimit type appears more than once.
+    DuplicateRlimit(RlimitType),
+    /// Soft limit exceeds the hard limit.
+    SoftExceedsHard {
+        /// Soft limit.
+        soft: u64,
+        /// Hard limit.
+        hard: u64,
+    },
+    /// ID mappings are specified without a user namespace.
+    UnexpectedMappings,
+    /// New user namespace is specified without ID mappings.
+    MissingMappings,
+}
+
+impl Config {
+    /// Validates this config against the requirements of the runtime spec.
+    ///
+    /// The following requirements are checked:
+    ///
+    /// * `ociVersion` is a SemVer version compatible with [`OCI_VERSION`].
+    /// * `process.cwd` is absolute, and `process.args` is not empty.
+    /// * Each rlimit type appears at most once in `process.rlimits`, and its soft limit does not
+    ///   exceed its hard limit.
+    /// * `mounts[].destination`, `linux.devices[].path`, and `hooks.*[].path` are absolute.
+    /// * `hooks.*[].timeout` is greater than zero if set.
+    /// * Each namespace type appears at most once in `linux.namespaces`.
+    /// * `linux.uidMappings` and `linux.gidMappings` are specified if a new user namespace is
+    ///   created (a user namespace without `path`), and only if a user namespace is specified.
+    ///
+    /// # Errors
+    ///
+    /// If this config violates any of the requirements, `Err(ValidationError)` listing all
+    /// violations is returned.
+    ///
+    /// [`OCI_VERSION`]: ../constant.OCI_VERSION.html
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut violations = Vec::new();
+        let mut violation = |field: String, kind| violations.push(Violation { field, kind });
+
+        match semver::Version::parse(&self.oci_version) {
+            Ok(version) => {
+                if !is_compatible(&version) {
+                    violation(
+                        "ociVersion".to_string(),
+                        ViolationKind::IncompatibleVersion(self.oci_version.clone()),
+                    );
+                }
+            }
+            Err(_) => violation(
+                "ociVersion".to_string(),
+                ViolationKind::InvalidVersion(self.oci_version.clone()),
+            ),
+        }
+
+        for (i, mount) in self.mounts.iter().enumerate() {
+            check_absolute(
+                &mut violation,
+                format!("mounts[{}].destination", i),
+                &mount.destination,
+            );
+        }
+
+        if let Some(ref process) = self.process {
+            check_absolute(&mut violation, "process.cwd".to_string(), &process.cwd);
+            if process.args.is_empty() {
+                violation("process.args".to_string(), ViolationKind::Empty);
+            }
+
+            let mut types = HashSet::new();
+            for (i, rlimit) in process.rlimits.iter().enumerate() {
+                if !types.insert(rlimit.type_) {
+                    violation(
+                        format!("process.rlimits[{}].type", i),
+                        ViolationKind::DuplicateRlimit(rlimit.type_),
+                    );
+                }
+                if rlimit.soft > rlimit.hard {
+                    violation(
+                        format!("process.rlimits[{}].soft", i),
+                        ViolationKind::SoftExceedsHard {
+                            soft: rlimit.soft,
+                            hard: rlimit.hard,
+                        },
+                    );
+                }
+            }
+        }
+
+        if let Some(ref hooks) = self.hooks {
+            for &phase in &HookPhase::ALL {
+                for (i, hook) in hooks.get(phase).iter().enumerate() {
+                    let field = format!("hooks.{}[{}].path", phase, i);
+                    check_absolute(&mut violation, field, &hook.path);
+                    if hook.timeout == Some(0) {
+                        violation(
+                            format!("hooks.{}[{}].timeout", phase, i),
+                            ViolationKind::NotPositive,
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(ref linux) = self.linux {
+            let mut types = HashSet::new();
+            for (i, namespace) in linux.namespaces.iter().enumerate() {
+                if !types.insert(namespace.type_) {
+                    violation(
+                        format!("linux.namespaces[{}].type", i),
+                        ViolationKind::DuplicateNamespace(namespace.type_),
+                    );
+                }
+            }
+
+            let user_ns = types.contains(&NamespaceType::User);
+            // Joined user namespaces already have their mappings
+            let new_user_ns = linux
+                .namespaces
+                .iter()
+                .any(|ns| ns.type_ == NamespaceType::User && ns.path.is_none());
+            let mappings = [
+                ("linux.uidMappings", linux.uid_mappings.is_empty()),
+                ("linux.gidMappings", linux.gid_mappings.is_empty()),
+            ];
+            for &(field, is_empty) in &mappings {
+                if new_user_ns && is_empty {
+                    violation(field.to_string(), ViolationKind::MissingMappings);
+                } else if !user_ns && !is_empty {
+                    violation(field.to_string(), ViolationKind::UnexpectedMappings);
+                }
+            }
+
+            for (i, device) in linux.devices.iter().enumerate() {
+                let field = format!("linux.devices[{}].path", i);
+                check_absolute(&mut violation, field, &device.path);
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { violations })
+        }
+    }
+}
+
+/// Returns whether a config of `version` can be handled by an implementation of `OCI_VERSION`.
+fn is_compatible(version: &semver::Version) -> bool {
+    let supported = semver::Version::parse(OCI_VERSION).unwrap();
+
+    // Versions before 1.0.0 may break compatibility in any minor version
+    version.major == supported.major && (version.major != 0 || version.minor == supported.minor)
+}
+
+fn check_absolute(violation: &mut impl FnMut(String, ViolationKind), field: String, path: &Path) {
+    if !path.is_absolute() {
+        violation(field, ViolationKind::NotAbsolute(path.to_path_buf()));
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid config: ")?;
+        for (i, v) in self.violations.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{}", v)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ValidationError {}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.field)?;
+
+        match self.kind {
+            ViolationKind::InvalidVersion(ref v) => write!(f, "{} is not a SemVer version", v),
+            ViolationKind::IncompatibleVersion(ref v) => {
+                write!(f, "{} is not compatible with {}", v, OCI_VERSION)
+            }
+            ViolationKind::NotAbsolute(ref p) => write!(f, "{} is not absolute", p.display()),
+            ViolationKind::Empty => f.write_str("must not be empty"),
+            ViolationKind::NotPositive => f.write_str("must be greater than zero"),
+            ViolationKind::DuplicateNamespace(t) => write!(f, "duplicate namespace {:?}", t),
+            ViolationKind::DuplicateRlimit(t) => write!(f, "duplicate rlimit {:?}", t),
+            ViolationKind::SoftExceedsHard { soft, hard } => {
+                write!(f, "{} exceeds hard limit {}", soft, hard)
+            }
+            ViolationKind::UnexpectedMappings => f.write_str("specified without user namespace"),
+            ViolationKind::MissingMappings => f.write_str("required by user namespace"),
+        }
+    }
+}
+
+#[cfg(all(feature = "serde", test))]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{
+        "ociVersion": "1.0.1",
+        "root": { "path": "rootfs" },
+        "mounts": [{ "destination": "/proc", "type": "proc", "source": "proc" }],
+        "process": {
+            "user": { "uid": 0, "gid": 0 },
+            "cwd": "/",
+            "args": ["sh"],
+            "rlimits": [
+                { "type": "RLIMIT_NOFILE", "soft": 1024, "hard": 4096 },
+                { "type": "RLIMIT_CORE", "soft": 0, "hard": 0 }
+            ]
+        },
+        "hooks": { "prestart": [{ "path": "/usr/bin/setup" }] },
+        "linux": {
+            "namespaces": [{ "type": "pid" }, { "type": "user" }],
+            "uidMappings": [{ "hostID": 1000, "containerID": 0, "size": 1 }],
+            "gidMappings": [{ "hostID": 1000, "containerID": 0, "size": 1 }],
+            "devices": [{ "type": "c", "path": "/dev/fuse", "major": 10, "minor": 229 }],
+            "sysctl": {}
+        }
+    }"#;
+
+    fn config() -> Config {
+        serde_json::from_str(JSON).unwrap()
+    }
+
+    fn violation(field: &str, kind: ViolationKind) -> Violation {
+        Violation {
+            field: field.to_string(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(config().validate(), Ok(()));
+
+        let mut config = config();
+        config.oci_version = "1.2.0-rc.1".to_string();
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_joined_user_ns() {
+        let mut config = config();
+        let linux = config.linux.as_mut().unwrap();
+        linux.namespaces[1].path = Some(PathBuf::from("/proc/1/ns/user"));
+        linux.uid_mappings.clear();
+        linux.gid_mappings.clear();
+
+        // Mappings of a joined user namespace are not required
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn err_validate() {
+        let mut config = config();
+        config.oci_version = "2.0.0".to_string();
+        config.mounts[0].destination = PathBuf::from("proc");
+        let process = config.process.as_mut().unwrap();
+        process.cwd = PathBuf::from("home");
+        process.args.clear();
+        process.rlimits[1].type_ = RlimitType::Nofile;
+        process.rlimits[1].soft = 1;
+        let prestart = &mut config.hooks.as_mut().unwrap().prestart[0];
+        prestart.path = PathBuf::from("setup");
+        prestart.timeout = Some(0);
+        let linux = config.linux.as_mut().unwrap();
+        linux.namespaces.push(linux.namespaces[0].clone());
+        linux.namespaces.retain(|n| n.type_ != NamespaceType::User);
+        linux.devices[0].path = PathBuf::from("dev/fuse");
+
+        let err = config.validate().unwrap_err();
+        assert_eq!(
+            err.violations,
+            vec![
+                violation(
+                    "ociVersion",
+                    ViolationKind::IncompatibleVersion("2.0.0".to_string())
+                ),
+                violation(
+                    "mounts[0].destination",
+                    ViolationKind::NotAbsolute(PathBuf::from("proc"))
+                ),
+                violation(
+                    "process.cwd",
+                    ViolationKind::NotAbsolute(PathBuf::from("home"))
+                ),
+                violation("process.args", ViolationKind::Empty),
+                violation(
+                    "process.rlimits[1].type",
+                    ViolationKind::DuplicateRlimit(RlimitType::Nofile)
+                ),
+                violation(
+                    "process.rlimits[1].soft",
+                    ViolationKind::SoftExceedsHard { soft: 1, hard: 0 }
+                ),
+                violation(
+                    "hooks.prestart[0].path",
+                    ViolationKind::NotAbsolute(PathBuf::from("setup"))
+                ),
+                violation("hooks.prestart[0].timeout", ViolationKind::NotPositive),
+                violation(
+                    "linux.namespaces[1].type",
+                    ViolationKind::DuplicateNamespace(NamespaceType::Pid)
+                ),
+                violation("linux.uidMappings", ViolationKind::UnexpectedMappings),
+                violation("linux.gidMappings", ViolationKind::UnexpectedMappings),
+                violation(
+                    "linux.devices[0].path",
+                    ViolationKind::NotAbsolute(PathBuf::from("dev/fuse"))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn err_validate_version() {
+        let test_cases = &[
+            ("1.0", ViolationKind::InvalidVersion("1.0".to_string())),
+            (
+                "0.5.0-dev",
+                ViolationKind::IncompatibleVersion("0.5.0-dev".to_string()),
+            ),
+        ];
+
+        for (version, kind) in test_cases {
+            let mut config = config();
+            config.oci_version = version.to_string();
+            assert_eq!(
+                config.validate().unwrap_err().violations,
+                vec![violation("ociVersion", kind.clone())]
+            );
+        }
+    }
+
+    #[test]
+    fn err_validate_missing_mappings() {
+        let mut config = config();
+        let linux = config.linux.as_mut().unwrap();
+        linux.uid_mappings.clear();
+
+        let err = config.validate().unwrap_err();
+        assert_eq!(
+            err.violations,
+            vec![violation(
+                "linux.uidMappings",
+                ViolationKind::MissingMappings
+            )]
+        );
+        assert_eq!(
+            err.to_string(),
+            "Invalid config: linux.uidMappings: required by user namespace"
+        );
+    }
+}
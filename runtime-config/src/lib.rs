@@ -3,7 +3,8 @@
 //! Schema in this crate is based on the v1.0.1 release of the OCI runtime config spec. See the
 //! [OCI runtime spec] for more information about the spec.
 //!
-//! [`Config`] is the top-level struct for the OCI runtime config schema.
+//! [`Config`] is the top-level struct for the OCI runtime config schema. [`State`] is the state of
+//! a container, which is passed to hooks.
 //!
 //! For platform-specific config sections, this crate currently supports only Linux-specific config
 //! section, implemented as [`Linux`]. Deserialization of a runtime config with a Windows- or
@@ -12,6 +13,7 @@
 //! [OCI runtime spec]: https://github.com/opencontainers/runtime-spec/releases/tag/v1.0.1
 //! [`Config`]: config/struct.Config.html
 //! [`Linux`]: linux/struct.Linux.html
+//! [`State`]: state/struct.State.html
 
 #![warn(
     future_incompatible,
@@ -24,11 +26,15 @@
     unused
 )]
 
+#[cfg(feature = "bundle")]
+pub mod bundle;
 pub mod config;
 pub mod linux;
+pub mod state;
 
 pub use config::Config;
 pub use linux::Linux;
+pub use state::State;
 
 /// Version of the OCI runtime spec on which this crate is based.
 pub const OCI_VERSION: &str = "1.0.1";
@@ -52,13 +58,13 @@ mod tests {
             mounts: vec![
                 config::Mount {
                     destination: PathBuf::from("/proc"),
-                    type_: Some(String::from("proc")),
+                    type_: Some(config::MountType::Proc),
                     source: Some(PathBuf::from("proc")),
                     options: vec![],
                 },
                 config::Mount {
                     destination: PathBuf::from("/dev"),
-                    type_: Some(String::from("tmpfs")),
+                    type_: Some(config::MountType::Tmpfs),
                     source: Some(PathBuf::from("tmpfs")),
                     options: vec![
                         String::from("nosuid"),
@@ -69,7 +75,7 @@ mod tests {
                 },
                 config::Mount {
                     destination: PathBuf::from("/dev/pts"),
-                    type_: Some(String::from("devpts")),
+                    type_: Some(config::MountType::Devpts),
                     source: Some(PathBuf::from("devpts")),
                     options: vec![
                         String::from("nosuid"),
@@ -82,7 +88,7 @@ mod tests {
                 },
                 config::Mount {
                     destination: PathBuf::from("/dev/shm"),
-                    type_: Some(String::from("tmpfs")),
+                    type_: Some(config::MountType::Tmpfs),
                     source: Some(PathBuf::from("shm")),
                     options: vec![
                         String::from("nosuid"),
@@ -94,7 +100,7 @@ mod tests {
                 },
                 config::Mount {
                     destination: PathBuf::from("/dev/mqueue"),
-                    type_: Some(String::from("mqueue")),
+                    type_: Some(config::MountType::Mqueue),
                     source: Some(PathBuf::from("mqueue")),
                     options: vec![
                         String::from("nosuid"),
@@ -104,7 +110,7 @@ mod tests {
                 },
                 config::Mount {
                     destination: PathBuf::from("/sys"),
-                    type_: Some(String::from("sysfs")),
+                    type_: Some(config::MountType::Sysfs),
                     source: Some(PathBuf::from("sysfs")),
                     options: vec![
                         String::from("nosuid"),
@@ -114,7 +120,7 @@ mod tests {
                 },
                 config::Mount {
                     destination: PathBuf::from("/sys/fs/cgroup"),
-                    type_: Some(String::from("cgroup")),
+                    type_: Some(config::MountType::Cgroup),
                     source: Some(PathBuf::from("cgroup")),
                     options: vec![
                         String::from("nosuid"),
@@ -148,12 +154,12 @@ mod tests {
                 args: vec![String::from("sh")],
                 rlimits: vec![
                     config::Rlimit {
-                        type_: String::from("RLIMIT_CORE"),
+                        type_: config::RlimitType::Core,
                         hard: 1024,
                         soft: 1024,
                     },
                     config::Rlimit {
-                        type_: String::from("RLIMIT_NOFILE"),
+                        type_: config::RlimitType::Nofile,
                         hard: 1024,
                         soft: 1024,
                     },
@@ -219,6 +225,9 @@ mod tests {
                     env: vec![],
                     timeout: None,
                 }],
+                create_runtime: vec![],
+                create_container: vec![],
+                start_container: vec![],
             }),
             annotations: [
                 (String::from("com.example.key1"), String::from("value1")),
@@ -296,21 +305,21 @@ mod tests {
                             type_: None,
                             major: None,
                             minor: None,
-                            access: Some(String::from("rwm")),
+                            access: Some("rwm".parse().unwrap()),
                         },
                         linux::resources::Device {
                             allow: true,
                             type_: Some(linux::resources::DeviceType::Character),
                             major: Some(10),
                             minor: Some(229),
-                            access: Some(String::from("rw")),
+                            access: Some("rw".parse().unwrap()),
                         },
                         linux::resources::Device {
                             allow: true,
                             type_: Some(linux::resources::DeviceType::Block),
                             major: Some(8),
                             minor: Some(0),
-                            access: Some(String::from("r")),
+                            access: Some("r".parse().unwrap()),
                         },
                     ],
                     memory: Some(linux::resources::Memory {
@@ -321,6 +330,8 @@ mod tests {
                         kernel_tcp: Some(-1),
                         swappiness: Some(0),
                         disable_oom_killer: Some(false),
+                        use_hierarchy: None,
+                        check_before_update: None,
                     }),
                     cpu: Some(linux::resources::Cpu {
                         shares: Some(1024),
@@ -328,8 +339,9 @@ mod tests {
                         period: Some(500000),
                         realtime_runtime: Some(950000),
                         realtime_period: Some(1000000),
-                        cpus: Some(String::from("2-3")),
-                        mems: Some(String::from("0-7")),
+                        cpus: Some("2-3".parse().unwrap()),
+                        mems: Some("0-7".parse().unwrap()),
+                        idle: None,
                     }),
                     block_io: Some(linux::resources::BlockIo {
                         weight: Some(10),
@@ -379,6 +391,8 @@ mod tests {
                         ],
                     }),
                     pids: Some(linux::resources::Pids { limit: 32771 }),
+                    rdma: Default::default(),
+                    unified: Default::default(),
                 }),
                 intel_rdt: None,
                 sysctl: [
@@ -390,13 +404,18 @@ mod tests {
                 .collect(),
                 seccomp: Some(linux::Seccomp {
                     default_action: linux::seccomp::Action::Allow,
+                    default_errno_ret: None,
                     architectures: vec![
                         linux::seccomp::Architecture::X86,
                         linux::seccomp::Architecture::X32,
                     ],
+                    flags: vec![],
+                    listener_path: None,
+                    listener_metadata: None,
                     syscalls: vec![linux::seccomp::Syscall {
                         names: vec![String::from("getcwd"), String::from("chmod")],
                         action: linux::seccomp::Action::Errno,
+                        errno_ret: None,
                         args: vec![],
                     }],
                 }),
@@ -418,6 +437,8 @@ mod tests {
                 mount_label: Some(String::from(
                     "system_u:object_r:svirt_sandbox_file_t:s0:c715,c811",
                 )),
+                time_offsets: Default::default(),
+                personality: None,
             }),
         };
 
@@ -441,13 +462,13 @@ mod tests {
                 mounts: vec![
                     config::Mount {
                         destination: PathBuf::from("/proc"),
-                        type_: Some(String::from("proc")),
+                        type_: Some(config::MountType::Proc),
                         source: Some(PathBuf::from("proc")),
                         options: vec![],
                     },
                     config::Mount {
                         destination: PathBuf::from("/dev"),
-                        type_: Some(String::from("tmpfs")),
+                        type_: Some(config::MountType::Tmpfs),
                         source: Some(PathBuf::from("tmpfs")),
                         options: vec![
                             String::from("nosuid"),
@@ -458,7 +479,7 @@ mod tests {
                     },
                     config::Mount {
                         destination: PathBuf::from("/dev/pts"),
-                        type_: Some(String::from("devpts")),
+                        type_: Some(config::MountType::Devpts),
                         source: Some(PathBuf::from("devpts")),
                         options: vec![
                             String::from("nosuid"),
@@ -471,7 +492,7 @@ mod tests {
                     },
                     config::Mount {
                         destination: PathBuf::from("/dev/shm"),
-                        type_: Some(String::from("tmpfs")),
+                        type_: Some(config::MountType::Tmpfs),
                         source: Some(PathBuf::from("shm")),
                         options: vec![
                             String::from("nosuid"),
@@ -483,7 +504,7 @@ mod tests {
                     },
                     config::Mount {
                         destination: PathBuf::from("/dev/mqueue"),
-                        type_: Some(String::from("mqueue")),
+                        type_: Some(config::MountType::Mqueue),
                         source: Some(PathBuf::from("mqueue")),
                         options: vec![
                             String::from("nosuid"),
@@ -493,7 +514,7 @@ mod tests {
                     },
                     config::Mount {
                         destination: PathBuf::from("/sys"),
-                        type_: Some(String::from("sysfs")),
+                        type_: Some(config::MountType::Sysfs),
                         source: Some(PathBuf::from("sysfs")),
                         options: vec![
                             String::from("nosuid"),
@@ -503,7 +524,7 @@ mod tests {
                     },
                     config::Mount {
                         destination: PathBuf::from("/sys/fs/cgroup"),
-                        type_: Some(String::from("cgroup")),
+                        type_: Some(config::MountType::Cgroup),
                         source: Some(PathBuf::from("cgroup")),
                         options: vec![
                             String::from("nosuid"),
@@ -537,12 +558,12 @@ mod tests {
                     args: vec![String::from("sh")],
                     rlimits: vec![
                         config::Rlimit {
-                            type_: String::from("RLIMIT_CORE"),
+                            type_: config::RlimitType::Core,
                             hard: 1024,
                             soft: 1024,
                         },
                         config::Rlimit {
-                            type_: String::from("RLIMIT_NOFILE"),
+                            type_: config::RlimitType::Nofile,
                             hard: 1024,
                             soft: 1024,
                         },
@@ -608,6 +629,9 @@ mod tests {
                         env: vec![],
                         timeout: None,
                     }],
+                    create_runtime: vec![],
+                    create_container: vec![],
+                    start_container: vec![],
                 }),
                 annotations: [
                     (String::from("com.example.key1"), String::from("value1")),
@@ -685,21 +709,21 @@ mod tests {
                                 type_: None,
                                 major: None,
                                 minor: None,
-                                access: Some(String::from("rwm")),
+                                access: Some("rwm".parse().unwrap()),
                             },
                             linux::resources::Device {
                                 allow: true,
                                 type_: Some(linux::resources::DeviceType::Character),
                                 major: Some(10),
                                 minor: Some(229),
-                                access: Some(String::from("rw")),
+                                access: Some("rw".parse().unwrap()),
                             },
                             linux::resources::Device {
                                 allow: true,
                                 type_: Some(linux::resources::DeviceType::Block),
                                 major: Some(8),
                                 minor: Some(0),
-                                access: Some(String::from("r")),
+                                access: Some("r".parse().unwrap()),
                             },
                         ],
                         memory: Some(linux::resources::Memory {
@@ -710,6 +734,8 @@ mod tests {
                             kernel_tcp: Some(-1),
                             swappiness: Some(0),
                             disable_oom_killer: Some(false),
+                            use_hierarchy: None,
+                            check_before_update: None,
                         }),
                         cpu: Some(linux::resources::Cpu {
                             shares: Some(1024),
@@ -717,8 +743,9 @@ mod tests {
                             period: Some(500000),
                             realtime_runtime: Some(950000),
                             realtime_period: Some(1000000),
-                            cpus: Some(String::from("2-3")),
-                            mems: Some(String::from("0-7")),
+                            cpus: Some("2-3".parse().unwrap()),
+                            mems: Some("0-7".parse().unwrap()),
+                            idle: None,
                         }),
                         block_io: Some(linux::resources::BlockIo {
                             weight: Some(10),
@@ -768,6 +795,8 @@ mod tests {
                             ],
                         }),
                         pids: Some(linux::resources::Pids { limit: 32771 }),
+                        rdma: Default::default(),
+                        unified: Default::default(),
                     }),
                     intel_rdt: None,
                     sysctl: [
@@ -779,13 +808,18 @@ mod tests {
                     .collect(),
                     seccomp: Some(linux::Seccomp {
                         default_action: linux::seccomp::Action::Allow,
+                        default_errno_ret: None,
                         architectures: vec![
                             linux::seccomp::Architecture::X86,
                             linux::seccomp::Architecture::X32,
                         ],
+                        flags: vec![],
+                        listener_path: None,
+                        listener_metadata: None,
                         syscalls: vec![linux::seccomp::Syscall {
                             names: vec![String::from("getcwd"), String::from("chmod")],
                             action: linux::seccomp::Action::Errno,
+                            errno_ret: None,
                             args: vec![],
                         }],
                     }),
@@ -807,6 +841,8 @@ mod tests {
                     mount_label: Some(String::from(
                         "system_u:object_r:svirt_sandbox_file_t:s0:c715,c811",
                     )),
+                    time_offsets: Default::default(),
+                    personality: None,
                 }),
             }
         );
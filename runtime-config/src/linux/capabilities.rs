@@ -0,0 +1,303 @@
+//! Capability state of processes on the host.
+//!
+//! The state is read from the `Cap*` fields of `/proc/<pid>/status`. For more information about
+//! the fields, see the [proc(5)] man page.
+//!
+//! [proc(5)]: http://man7.org/linux/man-pages/man5/proc.5.html
+
+use std::{error::Error, fmt, fs, io, path::Path};
+
+use crate::config::{Capabilities, Capability, CapabilitySet};
+
+/// Path to the status of the current process.
+pub const SELF_STATUS: &str = "/proc/self/status";
+
+/// Capability set of a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SetKind {
+    /// Effective set (`CapEff`).
+    Effective,
+    /// Bounding set (`CapBnd`).
+    Bounding,
+    /// Inheritable set (`CapInh`).
+    Inheritable,
+    /// Permitted set (`CapPrm`).
+    Permitted,
+    /// Ambient set (`CapAmb`).
+    Ambient,
+}
+
+/// Difference of a capability set between expected and actual capabilities.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    /// Capability set which differs.
+    pub kind: SetKind,
+    /// Capabilities which are expected but not in the actual set.
+    pub missing: CapabilitySet,
+    /// Capabilities which are in the actual set but not expected.
+    pub unexpected: CapabilitySet,
+}
+
+/// Error type that can be returned when a status file has invalid capability fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseStatusError {
+    /// Field is not found.
+    Missing(&'static str),
+    /// Field is not a hexadecimal bitmask.
+    Invalid(&'static str, String),
+}
+
+/// Fields of the status file for each capability set.
+const FIELDS: &[(SetKind, &str)] = &[
+    (SetKind::Effective, "CapEff"),
+    (SetKind::Bounding, "CapBnd"),
+    (SetKind::Inheritable, "CapInh"),
+    (SetKind::Permitted, "CapPrm"),
+    (SetKind::Ambient, "CapAmb"),
+];
+
+/// Parses the capability sets in the contents of a status file.
+///
+/// Capabilities unknown to this crate are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use oci_runtime_config::{config::Capability, linux::capabilities};
+///
+/// let status = "CapInh:\t0000000000000000\nCapPrm:\t0000000000000021\n\
+///               CapEff:\t0000000000000020\nCapBnd:\t0000000000000021\n\
+///               CapAmb:\t0000000000000000\n";
+/// let caps = capabilities::parse_status(status).unwrap();
+///
+/// assert_eq!(caps.permitted, [Capability::Chown, Capability::Kill]);
+/// assert_eq!(caps.effective, [Capability::Kill]);
+/// ```
+pub fn parse_status(status: &str) -> Result<Capabilities, ParseStatusError> {
+    let mut caps = Capabilities::default();
+
+    for &(kind, field) in FIELDS {
+        let value = status
+            .lines()
+            .find_map(|line| {
+                let mut parts = line.splitn(2, ':');
+                if parts.next()? == field {
+                    parts.next().map(str::trim)
+                } else {
+                    None
+                }
+            })
+            .ok_or(ParseStatusError::Missing(field))?;
+        let bits = u64::from_str_radix(value, 16)
+            .map_err(|_| ParseStatusError::Invalid(field, value.to_string()))?;
+
+        *set_mut(&mut caps, kind) = CapabilitySet::from_bits(bits).iter().collect();
+    }
+
+    Ok(caps)
+}
+
+/// Reads the capability sets from a status file, such as [`SELF_STATUS`].
+///
+/// [`SELF_STATUS`]: constant.SELF_STATUS.html
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Capabilities> {
+    parse_status(&fs::read_to_string(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Reads the capability sets of the current process.
+pub fn current() -> io::Result<Capabilities> {
+    read(SELF_STATUS)
+}
+
+/// Reads the capability sets of the process `pid`.
+pub fn of_process(pid: u32) -> io::Result<Capabilities> {
+    read(format!("/proc/{}/status", pid))
+}
+
+/// Compares the capability sets of `actual` with `expected`, returning the sets which differ.
+///
+/// The order of capabilities in each set does not matter, and `Unknown` capabilities are
+/// ignored.
+///
+/// # Examples
+///
+/// ```
+/// use oci_runtime_config::{
+///     config::{Capabilities, Capability, CapabilitySet},
+///     linux::capabilities::{self, SetKind},
+/// };
+///
+/// let expected = Capabilities::from_set(CapabilitySet::docker_default());
+/// let mut actual = expected.clone();
+/// actual.effective.retain(|c| *c != Capability::Kill);
+/// let diff = capabilities::diff(&expected, &actual);
+///
+/// assert_eq!(diff.len(), 1);
+/// assert_eq!(diff[0].kind, SetKind::Effective);
+/// assert_eq!(diff[0].missing, Capability::Kill.into());
+/// ```
+pub fn diff(expected: &Capabilities, actual: &Capabilities) -> Vec<Difference> {
+    FIELDS
+        .iter()
+        .filter_map(|&(kind, _)| {
+            let expected: CapabilitySet = set(expected, kind).iter().cloned().collect();
+            let actual: CapabilitySet = set(actual, kind).iter().cloned().collect();
+            if expected == actual {
+                None
+            } else {
+                Some(Difference {
+                    kind,
+                    missing: expected - actual,
+                    unexpected: actual - expected,
+                })
+            }
+        })
+        .collect()
+}
+
+fn set(caps: &Capabilities, kind: SetKind) -> &[Capability] {
+    match kind {
+        SetKind::Effective => &caps.effective,
+        SetKind::Bounding => &caps.bounding,
+        SetKind::Inheritable => &caps.inheritable,
+        SetKind::Permitted => &caps.permitted,
+        SetKind::Ambient => &caps.ambient,
+    }
+}
+
+fn set_mut(caps: &mut Capabilities, kind: SetKind) -> &mut Vec<Capability> {
+    match kind {
+        SetKind::Effective => &mut caps.effective,
+        SetKind::Bounding => &mut caps.bounding,
+        SetKind::Inheritable => &mut caps.inheritable,
+        SetKind::Permitted => &mut caps.permitted,
+        SetKind::Ambient => &mut caps.ambient,
+    }
+}
+
+impl fmt::Display for SetKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SetKind::Effective => "effective",
+            SetKind::Bounding => "bounding",
+            SetKind::Inheritable => "inheritable",
+            SetKind::Permitted => "permitted",
+            SetKind::Ambient => "ambient",
+        })
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} set", self.kind)?;
+        if !self.missing.is_empty() {
+            write!(f, " is missing {}", self.missing)?;
+        }
+        if !self.unexpected.is_empty() {
+            if !self.missing.is_empty() {
+                f.write_str(" and")?;
+            }
+            write!(f, " has unexpected {}", self.unexpected)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ParseStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseStatusError::Missing(field) => write!(f, "{} is not found", field),
+            ParseStatusError::Invalid(field, value) => {
+                write!(f, "Invalid value of {}: {:?}", field, value)
+            }
+        }
+    }
+}
+
+impl Error for ParseStatusError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUS: &str = "Name:\tcat
+Umask:\t0022
+State:\tR (running)
+CapInh:\t0000000000000000
+CapPrm:\t00000000a80425fb
+CapEff:\t00000000a80425fb
+CapBnd:\t000001ffffffffff
+CapAmb:\t0000000000000000
+NoNewPrivs:\t0
+";
+
+    #[test]
+    fn test_parse_status() {
+        let caps = parse_status(STATUS).unwrap();
+
+        assert_eq!(
+            caps.effective.iter().cloned().collect::<CapabilitySet>(),
+            CapabilitySet::docker_default()
+        );
+        assert_eq!(caps.permitted, caps.effective);
+        assert_eq!(
+            caps.bounding,
+            CapabilitySet::all().iter().collect::<Vec<_>>()
+        );
+        assert!(caps.inheritable.is_empty());
+        assert!(caps.ambient.is_empty());
+    }
+
+    #[test]
+    fn test_read_current() {
+        let caps = current().unwrap();
+        for cap in caps.effective.iter().chain(&caps.ambient) {
+            assert!(caps.permitted.contains(cap));
+        }
+        assert_eq!(
+            of_process(std::process::id()).unwrap().bounding,
+            caps.bounding
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let expected = Capabilities::from_set(CapabilitySet::docker_default());
+        let actual = parse_status(STATUS).unwrap();
+        assert_eq!(
+            diff(&expected, &actual),
+            vec![Difference {
+                kind: SetKind::Bounding,
+                missing: CapabilitySet::EMPTY,
+                unexpected: CapabilitySet::all() - CapabilitySet::docker_default(),
+            }]
+        );
+
+        let mut actual = expected.clone();
+        actual.effective.reverse();
+        actual.ambient.push(Capability::Kill);
+        actual.permitted.retain(|c| *c != Capability::Chown);
+        let diff = diff(&expected, &actual);
+        let msgs: Vec<_> = diff.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                "permitted set is missing CAP_CHOWN",
+                "ambient set has unexpected CAP_KILL",
+            ]
+        );
+    }
+
+    #[test]
+    fn err_parse_status() {
+        let test_cases = vec![
+            ("CapInh:\t0\n", "CapEff is not found"),
+            ("CapEff:\txyz\n", "Invalid value of CapEff: \"xyz\""),
+        ];
+
+        for (status, msg) in test_cases {
+            assert_eq!(parse_status(status).unwrap_err().to_string(), msg);
+        }
+    }
+}
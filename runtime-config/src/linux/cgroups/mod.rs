@@ -0,0 +1,153 @@
+//! Translation of resource limits into cgroup interface files.
+//!
+//! For more information about cgroups, see the kernel docs for [cgroup v1] and [cgroup v2].
+//!
+//! [cgroup v1]: https://www.kernel.org/doc/Documentation/cgroup-v1/cgroups.txt
+//! [cgroup v2]: https://www.kernel.org/doc/Documentation/cgroup-v2.txt
+
+pub mod v1;
+pub mod v2;
+
+use std::{
+    collections::HashMap,
+    error, fmt,
+    fs::OpenOptions,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::linux::resources::Rdma;
+
+/// Value to be written to a cgroup interface file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    /// Name of the interface file, e.g. `memory.max`.
+    pub file: String,
+    /// Value to be written.
+    pub value: String,
+}
+
+/// Error type that can be returned when resource limits cannot be applied to a cgroup.
+#[derive(Debug)]
+pub enum Error {
+    /// Field has no equivalent in the cgroup version.
+    Unsupported(&'static str),
+    /// Limit on memory + swap usage is set without or below the limit on memory usage.
+    InvalidSwap {
+        /// Limit on memory usage.
+        limit: Option<i64>,
+        /// Limit on memory + swap usage.
+        swap: i64,
+    },
+    /// Interface file cannot be written.
+    Write(PathBuf, io::Error),
+    /// Interface file cannot be read.
+    Read(PathBuf, io::Error),
+    /// Interface file has unexpected contents.
+    InvalidStat(PathBuf),
+}
+
+impl Setting {
+    fn new<F: Into<String>, V: ToString>(file: F, value: V) -> Self {
+        Setting {
+            file: file.into(),
+            value: value.to_string(),
+        }
+    }
+}
+
+/// Returns the settings of `rdma.max` for `rdma`, sorted by device name.
+fn rdma_settings(rdma: &HashMap<String, Rdma>) -> Vec<Setting> {
+    let mut devices: Vec<_> = rdma.iter().collect();
+    devices.sort_by_key(|&(device, _)| device);
+
+    devices
+        .into_iter()
+        .filter_map(|(device, limits)| {
+            let mut value = device.clone();
+            if let Some(handles) = limits.hca_handles {
+                value.push_str(&format!(" hca_handle={}", handles));
+            }
+            if let Some(objects) = limits.hca_objects {
+                value.push_str(&format!(" hca_object={}", objects));
+            }
+            if value.len() == device.len() {
+                None
+            } else {
+                Some(Setting::new("rdma.max", value))
+            }
+        })
+        .collect()
+}
+
+/// Writes `settings` in order to the interface files in the cgroup directory `dir`.
+///
+/// The interface files must exist, as they do in cgroupfs for enabled controllers.
+pub fn write_settings<P: AsRef<Path>>(dir: P, settings: &[Setting]) -> Result<(), Error> {
+    for setting in settings {
+        let path = dir.as_ref().join(&setting.file);
+        OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(&path)
+            .and_then(|mut f| f.write_all(setting.value.as_bytes()))
+            .map_err(|e| Error::Write(path, e))?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unsupported(field) => write!(f, "{} is not supported", field),
+            Error::InvalidSwap { limit, swap } => match limit {
+                Some(limit) => write!(f, "Swap limit {} is below memory limit {}", swap, limit),
+                None => write!(f, "Swap limit {} is set without memory limit", swap),
+            },
+            Error::Write(path, e) => write!(f, "Cannot write {}: {}", path.display(), e),
+            Error::Read(path, e) => write!(f, "Cannot read {}: {}", path.display(), e),
+            Error::InvalidStat(path) => write!(f, "Invalid contents in {}", path.display()),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Write(_, e) | Error::Read(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_write_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("pids.max"), "max").unwrap();
+
+        write_settings(dir.path(), &[Setting::new("pids.max", 32)]).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.path().join("pids.max")).unwrap(),
+            "32"
+        );
+    }
+
+    #[test]
+    fn err_write_settings() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let err = write_settings(dir.path(), &[Setting::new("memory.max", 1024)]).unwrap_err();
+        match err {
+            Error::Write(path, e) => {
+                assert_eq!(path, dir.path().join("memory.max"));
+                assert_eq!(e.kind(), io::ErrorKind::NotFound);
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+}
@@ -0,0 +1,812 @@
+//! Translation of resource limits into cgroup v1 interface files, and reading of usage stats.
+//!
+//! In cgroup v1, each controller has its own hierarchy mounted at `<root>/<controller>`, e.g.
+//! `/sys/fs/cgroup/memory`, and a cgroup at `<path>` is the directory `<root>/<controller>/<path>`
+//! in each hierarchy.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::linux::resources::{
+    BlockIo, Cpu, Device, DeviceAccess, DeviceType, Memory, Network, Resources,
+};
+
+use super::{rdma_settings, write_settings, Error, Setting};
+
+/// cgroup v1 controllers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Controller {
+    /// `blkio` controller.
+    Blkio,
+    /// `cpu` controller.
+    Cpu,
+    /// `cpuacct` controller.
+    Cpuacct,
+    /// `cpuset` controller.
+    Cpuset,
+    /// `devices` controller.
+    Devices,
+    /// `hugetlb` controller.
+    Hugetlb,
+    /// `memory` controller.
+    Memory,
+    /// `net_cls` controller.
+    NetCls,
+    /// `net_prio` controller.
+    NetPrio,
+    /// `pids` controller.
+    Pids,
+    /// `rdma` controller.
+    Rdma,
+}
+
+/// Usage stats of a cgroup.
+///
+/// Stats of a controller are `None` if the cgroup does not exist in its hierarchy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Stats of the `memory` controller.
+    pub memory: Option<MemoryStats>,
+    /// Stats of the `cpu` and `cpuacct` controllers.
+    pub cpu: Option<CpuStats>,
+    /// Stats of the `blkio` controller.
+    pub blkio: Option<BlkioStats>,
+    /// Stats of the `pids` controller.
+    pub pids: Option<PidsStats>,
+}
+
+/// Usage stats of the `memory` controller.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// Memory usage in bytes (`memory.usage_in_bytes`).
+    pub usage: u64,
+    /// Maximum memory usage in bytes (`memory.max_usage_in_bytes`).
+    pub max_usage: u64,
+    /// Number of times the memory limit was hit (`memory.failcnt`).
+    pub failcnt: u64,
+    /// Memory + swap usage in bytes (`memory.memsw.usage_in_bytes`), if swap accounting is enabled.
+    pub swap_usage: Option<u64>,
+    /// Detailed stats (`memory.stat`), e.g. `cache` and `rss`.
+    pub stat: HashMap<String, u64>,
+}
+
+/// Usage stats of the `cpu` and `cpuacct` controllers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CpuStats {
+    /// Total CPU time in nanoseconds (`cpuacct.usage`).
+    pub usage: u64,
+    /// Number of elapsed enforcement periods (`nr_periods` in `cpu.stat`).
+    pub nr_periods: u64,
+    /// Number of throttled periods (`nr_throttled` in `cpu.stat`).
+    pub nr_throttled: u64,
+    /// Total throttled time in nanoseconds (`throttled_time` in `cpu.stat`).
+    pub throttled_time: u64,
+}
+
+/// Usage stats of the `blkio` controller.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlkioStats {
+    /// Bytes transferred (`blkio.throttle.io_service_bytes`).
+    pub io_service_bytes: Vec<BlkioEntry>,
+    /// Number of I/O operations (`blkio.throttle.io_serviced`).
+    pub io_serviced: Vec<BlkioEntry>,
+}
+
+/// Per-device and per-operation entry of the `blkio` stats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlkioEntry {
+    /// Major number for the device.
+    pub major: u64,
+    /// Minor number for the device.
+    pub minor: u64,
+    /// Operation, e.g. `Read` or `Write`.
+    pub op: String,
+    /// Value of the stat.
+    pub value: u64,
+}
+
+/// Usage stats of the `pids` controller.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PidsStats {
+    /// Number of tasks (`pids.current`).
+    pub current: u64,
+    /// Maximum number of tasks (`pids.max`), or `None` for unlimited.
+    pub limit: Option<u64>,
+}
+
+impl Controller {
+    /// Returns the name of the controller, which is also the name of its hierarchy.
+    pub fn name(self) -> &'static str {
+        match self {
+            Controller::Blkio => "blkio",
+            Controller::Cpu => "cpu",
+            Controller::Cpuacct => "cpuacct",
+            Controller::Cpuset => "cpuset",
+            Controller::Devices => "devices",
+            Controller::Hugetlb => "hugetlb",
+            Controller::Memory => "memory",
+            Controller::NetCls => "net_cls",
+            Controller::NetPrio => "net_prio",
+            Controller::Pids => "pids",
+            Controller::Rdma => "rdma",
+        }
+    }
+}
+
+/// Returns the settings of the interface files for `resources` per controller, in the order to
+/// be written.
+///
+/// # Errors
+///
+/// If `unified` is not empty, `Error::Unsupported` is returned, as it holds cgroup v2 interface
+/// files.
+///
+/// # Examples
+///
+/// ```
+/// use oci_runtime_config::linux::{
+///     cgroups::v1::{self, Controller},
+///     resources::Memory,
+///     Resources,
+/// };
+///
+/// let resources = Resources {
+///     memory: Some(Memory {
+///         limit: Some(1 << 30),
+///         ..Default::default()
+///     }),
+///     ..Default::default()
+/// };
+/// let settings = v1::settings(&resources).unwrap();
+///
+/// assert_eq!(settings[0].0, Controller::Memory);
+/// assert_eq!(settings[0].1.file, "memory.limit_in_bytes");
+/// assert_eq!(settings[0].1.value, "1073741824");
+/// ```
+pub fn settings(resources: &Resources) -> Result<Vec<(Controller, Setting)>, Error> {
+    if !resources.unified.is_empty() {
+        return Err(Error::Unsupported("unified"));
+    }
+
+    let mut settings = Vec::new();
+
+    for device in &resources.devices {
+        settings.push((Controller::Devices, device_setting(device)));
+    }
+    if let Some(ref memory) = resources.memory {
+        memory_settings(memory, &mut settings);
+    }
+    if let Some(ref cpu) = resources.cpu {
+        cpu_settings(cpu, &mut settings);
+    }
+    if let Some(ref block_io) = resources.block_io {
+        blkio_settings(block_io, &mut settings);
+    }
+    for hugepage in &resources.hugepage_limits {
+        let file = format!("hugetlb.{}.limit_in_bytes", hugepage.page_size);
+        settings.push((Controller::Hugetlb, Setting::new(file, hugepage.limit)));
+    }
+    if let Some(ref network) = resources.network {
+        network_settings(network, &mut settings);
+    }
+    if let Some(ref pids) = resources.pids {
+        let value = if pids.limit < 0 {
+            "max".to_string()
+        } else {
+            pids.limit.to_string()
+        };
+        settings.push((Controller::Pids, Setting::new("pids.max", value)));
+    }
+    for setting in rdma_settings(&resources.rdma) {
+        settings.push((Controller::Rdma, setting));
+    }
+
+    Ok(settings)
+}
+
+/// Applies `resources` to the cgroup at `path` in the hierarchies under `root`.
+///
+/// The cgroup directories must exist in the hierarchies of the controllers for `resources`.
+/// Nothing is written if `resources` cannot be translated (see [`settings`]).
+///
+/// If the memory limit is raised above the current memory + swap limit, the memory + swap limit is
+/// written first, as the kernel requires the memory limit not to exceed it at any time.
+///
+/// [`settings`]: fn.settings.html
+pub fn apply<P: AsRef<Path>, Q: AsRef<Path>>(
+    root: P,
+    path: Q,
+    resources: &Resources,
+) -> Result<(), Error> {
+    let dir = |controller: Controller| cgroup_dir(root.as_ref(), path.as_ref(), controller);
+    let mut settings = settings(resources)?;
+
+    if let Some(Memory {
+        limit: Some(limit),
+        swap: Some(_),
+        ..
+    }) = resources.memory
+    {
+        let memsw = dir(Controller::Memory).join("memory.memsw.limit_in_bytes");
+        let current = fs::read_to_string(memsw)
+            .ok()
+            .and_then(|s| s.trim().parse::<i64>().ok());
+        if current.map_or(false, |current| limit < 0 || limit > current) {
+            // memory.limit_in_bytes is immediately followed by memory.memsw.limit_in_bytes
+            let i = settings
+                .iter()
+                .position(|(_, s)| s.file == "memory.limit_in_bytes")
+                .unwrap();
+            settings.swap(i, i + 1);
+        }
+    }
+
+    for (controller, setting) in settings {
+        write_settings(dir(controller), &[setting])?;
+    }
+    Ok(())
+}
+
+/// Reads the usage stats of the cgroup at `path` in the hierarchies under `root`.
+pub fn stats<P: AsRef<Path>, Q: AsRef<Path>>(root: P, path: Q) -> Result<Stats, Error> {
+    let dir = |controller: Controller| {
+        let dir = cgroup_dir(root.as_ref(), path.as_ref(), controller);
+        if dir.is_dir() {
+            Some(dir)
+        } else {
+            None
+        }
+    };
+
+    let memory = match dir(Controller::Memory) {
+        Some(dir) => Some(MemoryStats {
+            usage: read_u64(&dir.join("memory.usage_in_bytes"))?,
+            max_usage: read_u64(&dir.join("memory.max_usage_in_bytes"))?,
+            failcnt: read_u64(&dir.join("memory.failcnt"))?,
+            swap_usage: read_optional(&dir.join("memory.memsw.usage_in_bytes"))?,
+            stat: read_flat_keyed(&dir.join("memory.stat"))?,
+        }),
+        None => None,
+    };
+
+    let cpu = match (dir(Controller::Cpu), dir(Controller::Cpuacct)) {
+        (Some(cpu), Some(cpuacct)) => {
+            let stat = read_flat_keyed(&cpu.join("cpu.stat"))?;
+            let get = |key| stat.get(key).copied().unwrap_or(0);
+            Some(CpuStats {
+                usage: read_u64(&cpuacct.join("cpuacct.usage"))?,
+                nr_periods: get("nr_periods"),
+                nr_throttled: get("nr_throttled"),
+                throttled_time: get("throttled_time"),
+            })
+        }
+        _ => None,
+    };
+
+    let blkio = match dir(Controller::Blkio) {
+        Some(dir) => Some(BlkioStats {
+            io_service_bytes: read_blkio(&dir.join("blkio.throttle.io_service_bytes"))?,
+            io_serviced: read_blkio(&dir.join("blkio.throttle.io_serviced"))?,
+        }),
+        None => None,
+    };
+
+    let pids = match dir(Controller::Pids) {
+        Some(dir) => {
+            let max = dir.join("pids.max");
+            let limit = match read(&max)?.trim() {
+                "max" => None,
+                s => Some(s.parse().map_err(|_| Error::InvalidStat(max))?),
+            };
+            Some(PidsStats {
+                current: read_u64(&dir.join("pids.current"))?,
+                limit,
+            })
+        }
+        None => None,
+    };
+
+    Ok(Stats {
+        memory,
+        cpu,
+        blkio,
+        pids,
+    })
+}
+
+fn cgroup_dir(root: &Path, path: &Path, controller: Controller) -> PathBuf {
+    let path = path.strip_prefix("/").unwrap_or(path);
+    root.join(controller.name()).join(path)
+}
+
+fn device_setting(device: &Device) -> Setting {
+    let file = if device.allow {
+        "devices.allow"
+    } else {
+        "devices.deny"
+    };
+    let type_ = match device.type_ {
+        None | Some(DeviceType::All) => 'a',
+        Some(DeviceType::Character) => 'c',
+        Some(DeviceType::Block) => 'b',
+    };
+    let number = |n: Option<i64>| n.map_or("*".to_string(), |n| n.to_string());
+    let access = device.access.unwrap_or(DeviceAccess::ALL);
+
+    let value = format!(
+        "{} {}:{} {}",
+        type_,
+        number(device.major),
+        number(device.minor),
+        access
+    );
+    Setting::new(file, value)
+}
+
+fn memory_settings(memory: &Memory, settings: &mut Vec<(Controller, Setting)>) {
+    let files = [
+        ("memory.limit_in_bytes", memory.limit),
+        ("memory.memsw.limit_in_bytes", memory.swap),
+        ("memory.soft_limit_in_bytes", memory.reservation),
+        ("memory.kmem.limit_in_bytes", memory.kernel),
+        ("memory.kmem.tcp.limit_in_bytes", memory.kernel_tcp),
+    ];
+    for &(file, value) in &files {
+        if let Some(value) = value {
+            settings.push((Controller::Memory, Setting::new(file, value)));
+        }
+    }
+
+    if let Some(swappiness) = memory.swappiness {
+        let setting = Setting::new("memory.swappiness", swappiness);
+        settings.push((Controller::Memory, setting));
+    }
+    if let Some(disable) = memory.disable_oom_killer {
+        let setting = Setting::new("memory.oom_control", disable as u8);
+        settings.push((Controller::Memory, setting));
+    }
+    if let Some(use_hierarchy) = memory.use_hierarchy {
+        let setting = Setting::new("memory.use_hierarchy", use_hierarchy as u8);
+        settings.push((Controller::Memory, setting));
+    }
+}
+
+fn cpu_settings(cpu: &Cpu, settings: &mut Vec<(Controller, Setting)>) {
+    if let Some(shares) = cpu.shares {
+        settings.push((Controller::Cpu, Setting::new("cpu.shares", shares)));
+    }
+    // The period is written first, as the quota is checked against it
+    if let Some(period) = cpu.period {
+        settings.push((Controller::Cpu, Setting::new("cpu.cfs_period_us", period)));
+    }
+    if let Some(quota) = cpu.quota {
+        settings.push((Controller::Cpu, Setting::new("cpu.cfs_quota_us", quota)));
+    }
+    if let Some(period) = cpu.realtime_period {
+        settings.push((Controller::Cpu, Setting::new("cpu.rt_period_us", period)));
+    }
+    if let Some(runtime) = cpu.realtime_runtime {
+        settings.push((Controller::Cpu, Setting::new("cpu.rt_runtime_us", runtime)));
+    }
+    if let Some(idle) = cpu.idle {
+        settings.push((Controller::Cpu, Setting::new("cpu.idle", idle)));
+    }
+    if let Some(ref cpus) = cpu.cpus {
+        settings.push((Controller::Cpuset, Setting::new("cpuset.cpus", cpus)));
+    }
+    if let Some(ref mems) = cpu.mems {
+        settings.push((Controller::Cpuset, Setting::new("cpuset.mems", mems)));
+    }
+}
+
+fn blkio_settings(block_io: &BlockIo, settings: &mut Vec<(Controller, Setting)>) {
+    if let Some(weight) = block_io.weight {
+        settings.push((Controller::Blkio, Setting::new("blkio.weight", weight)));
+    }
+    if let Some(weight) = block_io.leaf_weight {
+        settings.push((Controller::Blkio, Setting::new("blkio.leaf_weight", weight)));
+    }
+    for device in &block_io.weight_device {
+        let weights = [
+            ("blkio.weight_device", device.weight),
+            ("blkio.leaf_weight_device", device.leaf_weight),
+        ];
+        for &(file, weight) in &weights {
+            if let Some(weight) = weight {
+                let value = format!("{}:{} {}", device.major, device.minor, weight);
+                settings.push((Controller::Blkio, Setting::new(file, value)));
+            }
+        }
+    }
+
+    let throttles = [
+        (
+            "blkio.throttle.read_bps_device",
+            &block_io.throttle_read_bps_device,
+        ),
+        (
+            "blkio.throttle.write_bps_device",
+            &block_io.throttle_write_bps_device,
+        ),
+        (
+            "blkio.throttle.read_iops_device",
+            &block_io.throttle_read_iops_device,
+        ),
+        (
+            "blkio.throttle.write_iops_device",
+            &block_io.throttle_write_iops_device,
+        ),
+    ];
+    for &(file, devices) in &throttles {
+        for device in devices {
+            let value = format!("{}:{} {}", device.major, device.minor, device.rate);
+            settings.push((Controller::Blkio, Setting::new(file, value)));
+        }
+    }
+}
+
+fn network_settings(network: &Network, settings: &mut Vec<(Controller, Setting)>) {
+    if let Some(class_id) = network.class_id {
+        settings.push((
+            Controller::NetCls,
+            Setting::new("net_cls.classid", class_id),
+        ));
+    }
+    for priority in &network.priorities {
+        let value = format!("{} {}", priority.name, priority.priority);
+        settings.push((
+            Controller::NetPrio,
+            Setting::new("net_prio.ifpriomap", value),
+        ));
+    }
+}
+
+fn read(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path).map_err(|e| Error::Read(path.to_path_buf(), e))
+}
+
+fn read_u64(path: &Path) -> Result<u64, Error> {
+    read(path)?
+        .trim()
+        .parse()
+        .map_err(|_| Error::InvalidStat(path.to_path_buf()))
+}
+
+/// Reads a file which does not exist if its feature is disabled.
+fn read_optional(path: &Path) -> Result<Option<u64>, Error> {
+    match read_u64(path) {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::Read(_, ref e)) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reads a file of lines in the format `<key> <value>`.
+fn read_flat_keyed(path: &Path) -> Result<HashMap<String, u64>, Error> {
+    read(path)?
+        .lines()
+        .map(|line| {
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next().map(str::parse), fields.next()) {
+                (Some(key), Some(Ok(value)), None) => Ok((key.to_string(), value)),
+                _ => Err(Error::InvalidStat(path.to_path_buf())),
+            }
+        })
+        .collect()
+}
+
+/// Reads a file of lines in the format `<major>:<minor> <op> <value>`, except for the line of the
+/// total in the format `Total <value>`.
+fn read_blkio(path: &Path) -> Result<Vec<BlkioEntry>, Error> {
+    let invalid = || Error::InvalidStat(path.to_path_buf());
+
+    let mut entries = Vec::new();
+    for line in read(path)?.lines() {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        match fields[..] {
+            [device, op, value] => {
+                let mut numbers = device.splitn(2, ':').map(str::parse);
+                match (numbers.next(), numbers.next(), value.parse()) {
+                    (Some(Ok(major)), Some(Ok(minor)), Ok(value)) => entries.push(BlkioEntry {
+                        major,
+                        minor,
+                        op: op.to_string(),
+                        value,
+                    }),
+                    _ => return Err(invalid()),
+                }
+            }
+            ["Total", _] => {}
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linux::resources::{DeviceThrottle, NetworkPriority, Pids, Rdma};
+
+    fn create(root: &Path, controller: &str, files: &[(&str, &str)]) {
+        let dir = root.join(controller).join("test");
+        fs::create_dir_all(&dir).unwrap();
+        for (file, contents) in files {
+            fs::write(dir.join(file), contents).unwrap();
+        }
+    }
+
+    fn setting(controller: Controller, file: &str, value: &str) -> (Controller, Setting) {
+        (controller, Setting::new(file, value))
+    }
+
+    #[test]
+    fn test_settings() {
+        let resources = Resources {
+            devices: vec![
+                Device {
+                    allow: false,
+                    type_: None,
+                    major: None,
+                    minor: None,
+                    access: None,
+                },
+                Device {
+                    allow: true,
+                    type_: Some(DeviceType::Character),
+                    major: Some(1),
+                    minor: None,
+                    access: Some("rw".parse().unwrap()),
+                },
+            ],
+            memory: Some(Memory {
+                limit: Some(1 << 30),
+                swap: Some(-1),
+                disable_oom_killer: Some(true),
+                use_hierarchy: Some(true),
+                ..Default::default()
+            }),
+            cpu: Some(Cpu {
+                shares: Some(512),
+                quota: Some(50_000),
+                period: Some(100_000),
+                mems: Some("0".parse().unwrap()),
+                idle: Some(0),
+                ..Default::default()
+            }),
+            block_io: Some(BlockIo {
+                weight: Some(300),
+                throttle_write_bps_device: vec![DeviceThrottle {
+                    major: 8,
+                    minor: 0,
+                    rate: 1 << 20,
+                }],
+                ..Default::default()
+            }),
+            network: Some(Network {
+                class_id: Some(0x10_0001),
+                priorities: vec![NetworkPriority {
+                    name: "eth0".to_string(),
+                    priority: 5,
+                }],
+            }),
+            pids: Some(Pids { limit: 32 }),
+            rdma: vec![(
+                "mlx4_0".to_string(),
+                Rdma {
+                    hca_handles: None,
+                    hca_objects: Some(2000),
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            settings(&resources).unwrap(),
+            vec![
+                setting(Controller::Devices, "devices.deny", "a *:* rwm"),
+                setting(Controller::Devices, "devices.allow", "c 1:* rw"),
+                setting(Controller::Memory, "memory.limit_in_bytes", "1073741824"),
+                setting(Controller::Memory, "memory.memsw.limit_in_bytes", "-1"),
+                setting(Controller::Memory, "memory.oom_control", "1"),
+                setting(Controller::Memory, "memory.use_hierarchy", "1"),
+                setting(Controller::Cpu, "cpu.shares", "512"),
+                setting(Controller::Cpu, "cpu.cfs_period_us", "100000"),
+                setting(Controller::Cpu, "cpu.cfs_quota_us", "50000"),
+                setting(Controller::Cpu, "cpu.idle", "0"),
+                setting(Controller::Cpuset, "cpuset.mems", "0"),
+                setting(Controller::Blkio, "blkio.weight", "300"),
+                setting(
+                    Controller::Blkio,
+                    "blkio.throttle.write_bps_device",
+                    "8:0 1048576"
+                ),
+                setting(Controller::NetCls, "net_cls.classid", "1048577"),
+                setting(Controller::NetPrio, "net_prio.ifpriomap", "eth0 5"),
+                setting(Controller::Pids, "pids.max", "32"),
+                setting(Controller::Rdma, "rdma.max", "mlx4_0 hca_object=2000"),
+            ]
+        );
+    }
+
+    #[test]
+    fn err_settings_unified() {
+        let mut resources = Resources::default();
+        resources
+            .unified
+            .insert("memory.high".to_string(), "1G".to_string());
+        match settings(&resources).unwrap_err() {
+            Error::Unsupported(field) => assert_eq!(field, "unified"),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let root = tempfile::tempdir().unwrap();
+        let memory_files = [
+            ("memory.limit_in_bytes", "1073741824"),
+            ("memory.memsw.limit_in_bytes", "1073741824"),
+        ];
+        create(root.path(), "memory", &memory_files);
+        create(root.path(), "pids", &[("pids.max", "max")]);
+
+        let resources = Resources {
+            memory: Some(Memory {
+                limit: Some(2 << 30),
+                swap: Some(4 << 30),
+                ..Default::default()
+            }),
+            pids: Some(Pids { limit: 64 }),
+            ..Default::default()
+        };
+        apply(root.path(), "/test", &resources).unwrap();
+
+        let read = |file: &str| fs::read_to_string(root.path().join(file)).unwrap();
+        assert_eq!(read("memory/test/memory.limit_in_bytes"), "2147483648");
+        assert_eq!(
+            read("memory/test/memory.memsw.limit_in_bytes"),
+            "4294967296"
+        );
+        assert_eq!(read("pids/test/pids.max"), "64");
+
+        let resources = Resources {
+            cpu: Some(Cpu {
+                shares: Some(512),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        match apply(root.path(), "/test", &resources).unwrap_err() {
+            Error::Write(path, _) => assert_eq!(path, root.path().join("cpu/test/cpu.shares")),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_apply_swap_order() {
+        let memory = |limit, swap| Resources {
+            memory: Some(Memory {
+                limit: Some(limit),
+                swap: Some(swap),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("memory/test");
+        let limit = dir.join("memory.limit_in_bytes");
+        let memsw = dir.join("memory.memsw.limit_in_bytes");
+
+        // A directory in place of memory.limit_in_bytes fails the write to it, so the other file
+        // tells whether it was written before
+        create(
+            root.path(),
+            "memory",
+            &[("memory.memsw.limit_in_bytes", "2147483648")],
+        );
+        fs::create_dir(&limit).unwrap();
+        let err = |resources| match apply(root.path(), "/test", &resources).unwrap_err() {
+            Error::Write(path, _) => path,
+            e => panic!("unexpected error: {}", e),
+        };
+
+        // Lowered limit: memory first
+        assert_eq!(err(memory(1 << 30, 1 << 31)), limit);
+        assert_eq!(fs::read_to_string(&memsw).unwrap(), "2147483648");
+
+        // Raised limit: memory + swap first
+        assert_eq!(err(memory(3 << 30, 4 << 30)), limit);
+        assert_eq!(fs::read_to_string(&memsw).unwrap(), "4294967296");
+    }
+
+    #[test]
+    fn test_stats() {
+        let root = tempfile::tempdir().unwrap();
+        create(
+            root.path(),
+            "memory",
+            &[
+                ("memory.usage_in_bytes", "4096\n"),
+                ("memory.max_usage_in_bytes", "8192\n"),
+                ("memory.failcnt", "0\n"),
+                ("memory.stat", "cache 1024\nrss 2048\n"),
+            ],
+        );
+        create(
+            root.path(),
+            "cpu",
+            &[(
+                "cpu.stat",
+                "nr_periods 10\nnr_throttled 2\nthrottled_time 500\n",
+            )],
+        );
+        create(root.path(), "cpuacct", &[("cpuacct.usage", "123456\n")]);
+        create(
+            root.path(),
+            "blkio",
+            &[
+                (
+                    "blkio.throttle.io_service_bytes",
+                    "8:0 Read 4096\n8:0 Write 0\nTotal 4096\n",
+                ),
+                ("blkio.throttle.io_serviced", "Total 0\n"),
+            ],
+        );
+
+        let stats = stats(root.path(), "test").unwrap();
+
+        let memory = stats.memory.unwrap();
+        assert_eq!(memory.usage, 4096);
+        assert_eq!(memory.max_usage, 8192);
+        assert_eq!(memory.swap_usage, None);
+        assert_eq!(memory.stat["rss"], 2048);
+        assert_eq!(
+            stats.cpu,
+            Some(CpuStats {
+                usage: 123_456,
+                nr_periods: 10,
+                nr_throttled: 2,
+                throttled_time: 500,
+            })
+        );
+        assert_eq!(
+            stats.blkio.unwrap().io_service_bytes,
+            vec![
+                BlkioEntry {
+                    major: 8,
+                    minor: 0,
+                    op: "Read".to_string(),
+                    value: 4096,
+                },
+                BlkioEntry {
+                    major: 8,
+                    minor: 0,
+                    op: "Write".to_string(),
+                    value: 0,
+                },
+            ]
+        );
+        assert_eq!(stats.pids, None);
+    }
+
+    #[test]
+    fn err_stats() {
+        let root = tempfile::tempdir().unwrap();
+        create(
+            root.path(),
+            "pids",
+            &[("pids.current", "3\n"), ("pids.max", "many\n")],
+        );
+
+        match stats(root.path(), "test").unwrap_err() {
+            Error::InvalidStat(path) => assert_eq!(path, root.path().join("pids/test/pids.max")),
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+}
@@ -0,0 +1,492 @@
+//! Translation of resource limits into cgroup v2 interface files.
+//!
+//! The translation follows the [conversion by runc]. Devices and network are not translated, as
+//! cgroup v2 controls devices by eBPF programs and has no network controllers.
+//!
+//! [conversion by runc]: https://github.com/opencontainers/runc/blob/v1.0.0/libcontainer/cgroups/utils.go
+
+use std::path::Path;
+
+use crate::linux::resources::{BlockIo, Cpu, Memory, Resources};
+
+use super::{rdma_settings, write_settings, Error, Setting};
+
+/// Default value of `cpu.max` period in microseconds.
+const DEFAULT_PERIOD: u64 = 100_000;
+
+/// Returns the settings of the interface files for `resources`, in the order to be written.
+///
+/// Memory fields which have no equivalent in cgroup v2, such as `memory.swappiness`, are ignored
+/// in the same way as runc. They are listed by [`ignored_fields`].
+///
+/// # Errors
+///
+/// If `resources` has another field which cannot be translated into cgroup v2, such as
+/// `cpu.realtimeRuntime`, `Error::Unsupported` is returned.
+///
+/// The settings in `unified` come last, sorted by file name, so that they override the settings
+/// converted from the other fields.
+///
+/// # Examples
+///
+/// ```
+/// use oci_runtime_config::linux::{cgroups::v2, resources::Pids, Resources};
+///
+/// let resources = Resources {
+///     pids: Some(Pids { limit: 32 }),
+///     ..Default::default()
+/// };
+/// let settings = v2::settings(&resources).unwrap();
+///
+/// assert_eq!(settings[0].file, "pids.max");
+/// assert_eq!(settings[0].value, "32");
+/// ```
+///
+/// [`ignored_fields`]: fn.ignored_fields.html
+pub fn settings(resources: &Resources) -> Result<Vec<Setting>, Error> {
+    let mut settings = Vec::new();
+
+    if let Some(ref memory) = resources.memory {
+        memory_settings(memory, &mut settings)?;
+    }
+    if let Some(ref cpu) = resources.cpu {
+        cpu_settings(cpu, &mut settings)?;
+    }
+    if let Some(ref block_io) = resources.block_io {
+        io_settings(block_io, &mut settings)?;
+    }
+    if let Some(ref pids) = resources.pids {
+        settings.push(Setting::new("pids.max", max(pids.limit)));
+    }
+    for hugepage in &resources.hugepage_limits {
+        let file = format!("hugetlb.{}.max", hugepage.page_size);
+        settings.push(Setting::new(file, hugepage.limit));
+    }
+    settings.extend(rdma_settings(&resources.rdma));
+
+    // Unified settings are written last, so that they override the converted ones
+    let mut unified: Vec<_> = resources.unified.iter().collect();
+    unified.sort();
+    for (file, value) in unified {
+        settings.push(Setting::new(file.as_str(), value));
+    }
+
+    Ok(settings)
+}
+
+/// Returns the fields of `resources` which are ignored by [`settings`], as they have no equivalent
+/// in cgroup v2.
+///
+/// # Examples
+///
+/// ```
+/// use oci_runtime_config::linux::{cgroups::v2, resources::Memory, Resources};
+///
+/// let resources = Resources {
+///     memory: Some(Memory {
+///         limit: Some(1 << 30),
+///         swappiness: Some(60),
+///         ..Default::default()
+///     }),
+///     ..Default::default()
+/// };
+///
+/// assert_eq!(v2::ignored_fields(&resources), vec!["memory.swappiness"]);
+/// ```
+///
+/// [`settings`]: fn.settings.html
+pub fn ignored_fields(resources: &Resources) -> Vec<&'static str> {
+    let memory = match resources.memory {
+        Some(ref memory) => memory,
+        None => return vec![],
+    };
+
+    let fields = [
+        ("memory.kernel", memory.kernel.is_some()),
+        ("memory.kernelTCP", memory.kernel_tcp.is_some()),
+        ("memory.swappiness", memory.swappiness.is_some()),
+        (
+            "memory.disableOOMKiller",
+            memory.disable_oom_killer.is_some(),
+        ),
+    ];
+    fields
+        .iter()
+        .filter(|&&(_, set)| set)
+        .map(|&(field, _)| field)
+        .collect()
+}
+
+/// Applies `resources` to the cgroup directory `dir`.
+///
+/// The controllers for `resources` must be enabled in the parent's `cgroup.subtree_control`.
+pub fn apply<P: AsRef<Path>>(dir: P, resources: &Resources) -> Result<(), Error> {
+    write_settings(dir, &settings(resources)?)
+}
+
+fn memory_settings(memory: &Memory, settings: &mut Vec<Setting>) -> Result<(), Error> {
+    // Memory accounting is always hierarchical in cgroup v2
+    if memory.use_hierarchy == Some(false) {
+        return Err(Error::Unsupported("memory.useHierarchy"));
+    }
+
+    if let Some(reservation) = memory.reservation {
+        settings.push(Setting::new("memory.low", max(reservation)));
+    }
+    if let Some(limit) = memory.limit {
+        settings.push(Setting::new("memory.max", max(limit)));
+    }
+
+    // memory.swap.max limits swap only, while the runtime spec limits memory + swap
+    match memory.swap {
+        None | Some(0) => {}
+        Some(-1) => settings.push(Setting::new("memory.swap.max", "max")),
+        Some(swap) => match memory.limit {
+            Some(limit) if limit > 0 && swap >= limit => {
+                settings.push(Setting::new("memory.swap.max", swap - limit));
+            }
+            limit => return Err(Error::InvalidSwap { limit, swap }),
+        },
+    }
+
+    Ok(())
+}
+
+fn cpu_settings(cpu: &Cpu, settings: &mut Vec<Setting>) -> Result<(), Error> {
+    if cpu.realtime_runtime.is_some() {
+        return Err(Error::Unsupported("cpu.realtimeRuntime"));
+    }
+    if cpu.realtime_period.is_some() {
+        return Err(Error::Unsupported("cpu.realtimePeriod"));
+    }
+
+    if let Some(shares) = cpu.shares {
+        if shares != 0 {
+            settings.push(Setting::new("cpu.weight", shares_to_weight(shares)));
+        }
+    }
+    if cpu.quota.is_some() || cpu.period.is_some() {
+        let quota = max(cpu.quota.unwrap_or(-1));
+        let period = cpu.period.unwrap_or(DEFAULT_PERIOD);
+        settings.push(Setting::new("cpu.max", format!("{} {}", quota, period)));
+    }
+    if let Some(ref cpus) = cpu.cpus {
+        settings.push(Setting::new("cpuset.cpus", cpus));
+    }
+    if let Some(ref mems) = cpu.mems {
+        settings.push(Setting::new("cpuset.mems", mems));
+    }
+    if let Some(idle) = cpu.idle {
+        settings.push(Setting::new("cpu.idle", idle));
+    }
+
+    Ok(())
+}
+
+fn io_settings(block_io: &BlockIo, settings: &mut Vec<Setting>) -> Result<(), Error> {
+    if block_io.leaf_weight.is_some()
+        || block_io
+            .weight_device
+            .iter()
+            .any(|d| d.leaf_weight.is_some())
+    {
+        return Err(Error::Unsupported("blockIO.leafWeight"));
+    }
+
+    if let Some(weight) = block_io.weight {
+        if weight != 0 {
+            let value = format!("default {}", blkio_to_io_weight(weight));
+            settings.push(Setting::new("io.weight", value));
+        }
+    }
+    for device in &block_io.weight_device {
+        if let Some(weight) = device.weight {
+            let value = format!(
+                "{}:{} {}",
+                device.major,
+                device.minor,
+                blkio_to_io_weight(weight)
+            );
+            settings.push(Setting::new("io.weight", value));
+        }
+    }
+
+    let throttles = [
+        ("rbps", &block_io.throttle_read_bps_device),
+        ("wbps", &block_io.throttle_write_bps_device),
+        ("riops", &block_io.throttle_read_iops_device),
+        ("wiops", &block_io.throttle_write_iops_device),
+    ];
+    for &(key, devices) in &throttles {
+        for device in devices {
+            let value = format!("{}:{} {}={}", device.major, device.minor, key, device.rate);
+            settings.push(Setting::new("io.max", value));
+        }
+    }
+
+    Ok(())
+}
+
+/// Formats a limit, where negative values mean unlimited.
+fn max(limit: i64) -> String {
+    if limit < 0 {
+        "max".to_string()
+    } else {
+        limit.to_string()
+    }
+}
+
+/// Converts `cpu.shares` in [2, 262144] to `cpu.weight` in [1, 10000].
+fn shares_to_weight(shares: u64) -> u64 {
+    let shares = shares.max(2).min(262_144);
+    1 + (shares - 2) * 9999 / 262_142
+}
+
+/// Converts `blkio.weight` in [10, 1000] to `io.weight` in [1, 10000].
+fn blkio_to_io_weight(weight: u16) -> u64 {
+    let weight = u64::from(weight.max(10).min(1000));
+    1 + (weight - 10) * 9999 / 990
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linux::resources::{DeviceThrottle, DeviceWeight, HugepageLimit, Pids, Rdma};
+    use std::fs;
+
+    fn setting(file: &str, value: &str) -> Setting {
+        Setting::new(file, value)
+    }
+
+    fn resources() -> Resources {
+        Resources {
+            memory: Some(Memory {
+                limit: Some(1 << 30),
+                reservation: Some(1 << 29),
+                swap: Some(3 << 29),
+                ..Default::default()
+            }),
+            cpu: Some(Cpu {
+                shares: Some(1024),
+                quota: Some(50_000),
+                cpus: Some("0-1".parse().unwrap()),
+                ..Default::default()
+            }),
+            block_io: Some(BlockIo {
+                weight: Some(500),
+                weight_device: vec![DeviceWeight {
+                    major: 8,
+                    minor: 0,
+                    weight: Some(1000),
+                    leaf_weight: None,
+                }],
+                throttle_read_bps_device: vec![DeviceThrottle {
+                    major: 8,
+                    minor: 0,
+                    rate: 1 << 20,
+                }],
+                throttle_write_iops_device: vec![DeviceThrottle {
+                    major: 8,
+                    minor: 16,
+                    rate: 100,
+                }],
+                ..Default::default()
+            }),
+            pids: Some(Pids { limit: -1 }),
+            hugepage_limits: vec![HugepageLimit {
+                page_size: "2MB".to_string(),
+                limit: 1 << 21,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_settings() {
+        assert_eq!(
+            settings(&resources()).unwrap(),
+            vec![
+                setting("memory.low", "536870912"),
+                setting("memory.max", "1073741824"),
+                setting("memory.swap.max", "536870912"),
+                setting("cpu.weight", "39"),
+                setting("cpu.max", "50000 100000"),
+                setting("cpuset.cpus", "0-1"),
+                setting("io.weight", "default 4950"),
+                setting("io.weight", "8:0 10000"),
+                setting("io.max", "8:0 rbps=1048576"),
+                setting("io.max", "8:16 wiops=100"),
+                setting("pids.max", "max"),
+                setting("hugetlb.2MB.max", "2097152"),
+            ]
+        );
+        assert_eq!(settings(&Resources::default()).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_settings_unlimited() {
+        let resources = Resources {
+            memory: Some(Memory {
+                limit: Some(-1),
+                swap: Some(-1),
+                ..Default::default()
+            }),
+            cpu: Some(Cpu {
+                period: Some(250_000),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            settings(&resources).unwrap(),
+            vec![
+                setting("memory.max", "max"),
+                setting("memory.swap.max", "max"),
+                setting("cpu.max", "max 250000"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_settings_unified() {
+        let mut resources = Resources {
+            memory: Some(Memory {
+                limit: Some(1 << 30),
+                use_hierarchy: Some(true),
+                ..Default::default()
+            }),
+            cpu: Some(Cpu {
+                idle: Some(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        resources.rdma.insert(
+            "mlx5_1".to_string(),
+            Rdma {
+                hca_handles: Some(3),
+                hca_objects: None,
+            },
+        );
+        resources.rdma.insert(
+            "mlx4_0".to_string(),
+            Rdma {
+                hca_handles: Some(2),
+                hca_objects: Some(2000),
+            },
+        );
+        resources.rdma.insert("mlx4_1".to_string(), Rdma::default());
+        resources
+            .unified
+            .insert("memory.max".to_string(), "max".to_string());
+        resources
+            .unified
+            .insert("memory.high".to_string(), "1G".to_string());
+
+        assert_eq!(
+            settings(&resources).unwrap(),
+            vec![
+                setting("memory.max", "1073741824"),
+                setting("cpu.idle", "1"),
+                setting("rdma.max", "mlx4_0 hca_handle=2 hca_object=2000"),
+                setting("rdma.max", "mlx5_1 hca_handle=3"),
+                setting("memory.high", "1G"),
+                setting("memory.max", "max"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = [
+            "memory.low",
+            "memory.max",
+            "memory.swap.max",
+            "cpu.weight",
+            "cpu.max",
+        ];
+        for file in &files {
+            fs::write(dir.path().join(file), "").unwrap();
+        }
+
+        let mut resources = resources();
+        resources.block_io = None;
+        resources.pids = None;
+        resources.hugepage_limits.clear();
+        resources.cpu.as_mut().unwrap().cpus = None;
+        apply(dir.path(), &resources).unwrap();
+
+        let read = |file| fs::read_to_string(dir.path().join(file)).unwrap();
+        assert_eq!(read("memory.max"), "1073741824");
+        assert_eq!(read("cpu.max"), "50000 100000");
+    }
+
+    #[test]
+    fn err_settings() {
+        let test_cases = vec![
+            (
+                Memory {
+                    swap: Some(1024),
+                    ..Default::default()
+                },
+                "Swap limit 1024 is set without memory limit",
+            ),
+            (
+                Memory {
+                    limit: Some(2048),
+                    swap: Some(1024),
+                    ..Default::default()
+                },
+                "Swap limit 1024 is below memory limit 2048",
+            ),
+            (
+                Memory {
+                    use_hierarchy: Some(false),
+                    ..Default::default()
+                },
+                "memory.useHierarchy is not supported",
+            ),
+        ];
+
+        for (memory, msg) in test_cases {
+            let resources = Resources {
+                memory: Some(memory),
+                ..Default::default()
+            };
+            assert_eq!(settings(&resources).unwrap_err().to_string(), msg);
+        }
+    }
+
+    #[test]
+    fn test_settings_ignored() {
+        let resources = Resources {
+            memory: Some(Memory {
+                limit: Some(1 << 30),
+                kernel: Some(1 << 20),
+                kernel_tcp: Some(1 << 20),
+                swappiness: Some(60),
+                disable_oom_killer: Some(false),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            settings(&resources).unwrap(),
+            vec![setting("memory.max", "1073741824")]
+        );
+        assert_eq!(
+            ignored_fields(&resources),
+            vec![
+                "memory.kernel",
+                "memory.kernelTCP",
+                "memory.swappiness",
+                "memory.disableOOMKiller",
+            ]
+        );
+        assert!(ignored_fields(&self::resources()).is_empty());
+        assert!(ignored_fields(&Resources::default()).is_empty());
+    }
+}
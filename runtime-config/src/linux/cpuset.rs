@@ -0,0 +1,331 @@
+//! Sets of CPUs and memory nodes in the cpuset list format.
+//!
+//! For more information about the format, see the [kernel docs].
+//!
+//! [kernel docs]: https://www.kernel.org/doc/Documentation/cgroup-v1/cpusets.txt
+
+use std::{
+    collections::{btree_set, BTreeSet},
+    error::Error,
+    fmt, fs, io,
+    iter::FromIterator,
+    path::Path,
+    str::FromStr,
+};
+
+/// Path to the list of online CPUs of the host.
+pub const ONLINE_CPUS: &str = "/sys/devices/system/cpu/online";
+
+/// Path to the list of online memory nodes of the host.
+pub const ONLINE_MEMS: &str = "/sys/devices/system/node/online";
+
+/// Largest CPU or memory node number accepted, which is larger than any kernel supports.
+pub const MAX_ID: u32 = 0xffff;
+
+/// Set of CPUs or memory nodes, such as `0-3,7,9-11`.
+///
+/// When the feature `serde` is enabled, `CpuSet` can be serialized to / deserialized from the list
+/// format.
+///
+/// # Examples
+///
+/// ```
+/// use oci_runtime_config::linux::CpuSet;
+///
+/// let cpus: CpuSet = "0-3,7".parse().unwrap();
+/// let reserved: CpuSet = "0,1".parse().unwrap();
+///
+/// assert_eq!(cpus.difference(&reserved).to_string(), "2-3,7");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct CpuSet {
+    ids: BTreeSet<u32>,
+}
+
+/// Error type that can be returned when a list is not in the cpuset list format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseCpuSetError {
+    /// Item is not a number or a range of numbers.
+    InvalidItem(String),
+    /// Start of a range is greater than its end.
+    InvalidRange(u32, u32),
+    /// Number is greater than `MAX_ID`.
+    TooLarge(u32),
+}
+
+impl CpuSet {
+    /// Creates an empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a set in the list format from a file, such as [`ONLINE_CPUS`].
+    ///
+    /// [`ONLINE_CPUS`]: constant.ONLINE_CPUS.html
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Returns the online CPUs of the host.
+    pub fn online_cpus() -> io::Result<Self> {
+        Self::read(ONLINE_CPUS)
+    }
+
+    /// Returns the online memory nodes of the host.
+    pub fn online_mems() -> io::Result<Self> {
+        Self::read(ONLINE_MEMS)
+    }
+
+    /// Returns whether the set contains no ids.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Returns the number of ids in the set.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns whether the set contains `id`.
+    pub fn contains(&self, id: u32) -> bool {
+        self.ids.contains(&id)
+    }
+
+    /// Adds `id` to the set, returning whether it was not present.
+    pub fn insert(&mut self, id: u32) -> bool {
+        self.ids.insert(id)
+    }
+
+    /// Removes `id` from the set, returning whether it was present.
+    pub fn remove(&mut self, id: u32) -> bool {
+        self.ids.remove(&id)
+    }
+
+    /// Returns an iterator over the ids in ascending order.
+    pub fn iter(&self) -> btree_set::Iter<'_, u32> {
+        self.ids.iter()
+    }
+
+    /// Returns the ids in either `self` or `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        self.ids.union(&other.ids).copied().collect()
+    }
+
+    /// Returns the ids in both `self` and `other`.
+    pub fn intersection(&self, other: &Self) -> Self {
+        self.ids.intersection(&other.ids).copied().collect()
+    }
+
+    /// Returns the ids in `self` but not in `other`.
+    pub fn difference(&self, other: &Self) -> Self {
+        self.ids.difference(&other.ids).copied().collect()
+    }
+
+    /// Returns whether all ids in `self` are in `other`.
+    ///
+    /// This can be used to check a set against the host, e.g.
+    /// `cpus.is_subset(&CpuSet::online_cpus()?)`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.ids.is_subset(&other.ids)
+    }
+}
+
+impl FromStr for CpuSet {
+    type Err = ParseCpuSetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut set = Self::new();
+
+        let s = s.trim();
+        if s.is_empty() {
+            return Ok(set);
+        }
+
+        for item in s.split(',') {
+            let parse = |n: &str| {
+                let n = n
+                    .parse()
+                    .map_err(|_| ParseCpuSetError::InvalidItem(item.to_string()))?;
+                if n > MAX_ID {
+                    return Err(ParseCpuSetError::TooLarge(n));
+                }
+                Ok(n)
+            };
+
+            let (start, end) = match item.find('-') {
+                Some(i) => (parse(&item[..i])?, parse(&item[i + 1..])?),
+                None => {
+                    let n = parse(item)?;
+                    (n, n)
+                }
+            };
+            if start > end {
+                return Err(ParseCpuSetError::InvalidRange(start, end));
+            }
+
+            set.ids.extend(start..=end);
+        }
+
+        Ok(set)
+    }
+}
+
+impl fmt::Display for CpuSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ids = self.ids.iter().copied().peekable();
+        let mut first = true;
+
+        while let Some(start) = ids.next() {
+            let mut end = start;
+            while ids.peek() == Some(&(end + 1)) {
+                end = ids.next().unwrap();
+            }
+
+            if !first {
+                f.write_str(",")?;
+            }
+            first = false;
+
+            if start == end {
+                write!(f, "{}", start)?;
+            } else {
+                write!(f, "{}-{}", start, end)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromIterator<u32> for CpuSet {
+    fn from_iter<I: IntoIterator<Item = u32>>(iter: I) -> Self {
+        Self {
+            ids: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<u32> for CpuSet {
+    fn extend<I: IntoIterator<Item = u32>>(&mut self, iter: I) {
+        self.ids.extend(iter);
+    }
+}
+
+impl<'a> IntoIterator for &'a CpuSet {
+    type Item = &'a u32;
+    type IntoIter = btree_set::Iter<'a, u32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for CpuSet {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CpuSet {
+    fn deserialize<D: serde::Deserializer<'de>>(deser: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deser)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for ParseCpuSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseCpuSetError::InvalidItem(item) => write!(f, "Invalid cpuset item: {:?}", item),
+            ParseCpuSetError::InvalidRange(start, end) => {
+                write!(f, "Invalid cpuset range: {}-{}", start, end)
+            }
+            ParseCpuSetError::TooLarge(n) => write!(f, "Too large cpuset id: {}", n),
+        }
+    }
+}
+
+impl Error for ParseCpuSetError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpuset_parse() {
+        let test_cases = &[
+            ("", vec![]),
+            ("7", vec![7]),
+            ("0-3,7,9-11\n", vec![0, 1, 2, 3, 7, 9, 10, 11]),
+            ("5,1-2,2", vec![1, 2, 5]),
+        ];
+
+        for (s, ids) in test_cases {
+            let set: CpuSet = s.parse().unwrap();
+            assert_eq!(set.iter().copied().collect::<Vec<_>>(), *ids, "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_cpuset_display() {
+        let test_cases = &[
+            (vec![], ""),
+            (vec![3], "3"),
+            (vec![0, 1, 2, 3, 7, 9, 10, 11], "0-3,7,9-11"),
+            (vec![1, 2, 4], "1-2,4"),
+        ];
+
+        for (ids, s) in test_cases {
+            let set: CpuSet = ids.iter().copied().collect();
+            assert_eq!(set.to_string(), *s);
+        }
+    }
+
+    #[test]
+    fn test_cpuset_ops() {
+        let a: CpuSet = "0-3".parse().unwrap();
+        let b: CpuSet = "2-5".parse().unwrap();
+
+        assert_eq!(a.union(&b).to_string(), "0-5");
+        assert_eq!(a.intersection(&b).to_string(), "2-3");
+        assert_eq!(a.difference(&b).to_string(), "0-1");
+        assert!(a.intersection(&b).is_subset(&a));
+        assert!(!a.is_subset(&b));
+        assert_eq!(a.len(), 4);
+    }
+
+    #[test]
+    fn test_cpuset_online() {
+        let cpus = CpuSet::online_cpus().unwrap();
+        assert!(cpus.contains(0));
+        assert!("0".parse::<CpuSet>().unwrap().is_subset(&cpus));
+    }
+
+    #[test]
+    fn err_cpuset_parse() {
+        let test_cases = &[
+            ("1,", ParseCpuSetError::InvalidItem(String::new())),
+            ("a-3", ParseCpuSetError::InvalidItem("a-3".to_string())),
+            ("1-2-3", ParseCpuSetError::InvalidItem("1-2-3".to_string())),
+            ("3-1", ParseCpuSetError::InvalidRange(3, 1)),
+            ("0-65536", ParseCpuSetError::TooLarge(65536)),
+        ];
+
+        for (s, err) in test_cases {
+            assert_eq!(s.parse::<CpuSet>(), Err(err.clone()), "{:?}", s);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_cpuset_serde() {
+        let set: CpuSet = serde_json::from_str(r#""0-1,4""#).unwrap();
+        assert_eq!(set, vec![0, 1, 4].into_iter().collect());
+        assert_eq!(serde_json::to_string(&set).unwrap(), r#""0-1,4""#);
+        assert!(serde_json::from_str::<CpuSet>(r#""1-""#).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Evaluation of device whitelists in the same way as the devices cgroup.
+//!
+//! For more information about the devices cgroup, see the [kernel docs].
+//!
+//! [kernel docs]: https://www.kernel.org/doc/Documentation/cgroup-v1/devices.txt
+
+use crate::linux::resources::{Device, DeviceAccess, DeviceType};
+
+/// State of a devices cgroup after applying a device whitelist.
+///
+/// The state consists of a default behavior and a list of exceptions to it, which are updated by
+/// each entry as if it was written to `devices.allow` or `devices.deny`:
+///
+/// * An entry of type `a` sets the default behavior and removes all exceptions.
+/// * An entry which agrees with the default behavior removes its permissions from the exception for
+///   the same type, major, and minor numbers.
+/// * An entry which disagrees with the default behavior adds its permissions to the exception for
+///   the same type, major, and minor numbers.
+///
+/// # Examples
+///
+/// ```
+/// use oci_runtime_config::linux::{
+///     device_cgroup::DeviceCgroup,
+///     resources::{Device, DeviceAccess, DeviceType},
+/// };
+///
+/// let devices = vec![
+///     Device {
+///         allow: false,
+///         type_: None,
+///         major: None,
+///         minor: None,
+///         access: None,
+///     },
+///     Device {
+///         allow: true,
+///         type_: Some(DeviceType::Character),
+///         major: Some(1),
+///         minor: Some(3),
+///         access: Some("rw".parse().unwrap()),
+///     },
+/// ];
+/// let cgroup = DeviceCgroup::from_devices(&devices);
+///
+/// assert!(cgroup.is_allowed(DeviceType::Character, 1, 3, DeviceAccess::READ));
+/// assert!(!cgroup.is_allowed(DeviceType::Character, 1, 3, DeviceAccess::MKNOD));
+/// assert!(!cgroup.is_allowed(DeviceType::Block, 8, 0, DeviceAccess::READ));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceCgroup {
+    /// Whether devices not matching any exception are allowed.
+    pub default_allow: bool,
+
+    /// Exceptions to the default behavior.
+    pub exceptions: Vec<Exception>,
+}
+
+/// Exception to the default behavior of a devices cgroup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exception {
+    /// Type of device, which is either `Character` or `Block`.
+    pub type_: DeviceType,
+
+    /// Major number, or `None` for all.
+    pub major: Option<i64>,
+
+    /// Minor number, or `None` for all.
+    pub minor: Option<i64>,
+
+    /// Permissions of the exception.
+    pub access: DeviceAccess,
+}
+
+impl Default for DeviceCgroup {
+    /// Returns the state of the root cgroup, which allows all devices.
+    fn default() -> Self {
+        DeviceCgroup {
+            default_allow: true,
+            exceptions: Vec::new(),
+        }
+    }
+}
+
+impl DeviceCgroup {
+    /// Returns the state of the root cgroup after applying `devices` in order.
+    pub fn from_devices(devices: &[Device]) -> Self {
+        let mut cgroup = Self::default();
+        for device in devices {
+            cgroup.apply(device);
+        }
+        cgroup
+    }
+
+    /// Updates the state by a whitelist entry.
+    pub fn apply(&mut self, device: &Device) {
+        let type_ = match device.type_ {
+            None | Some(DeviceType::All) => {
+                self.default_allow = device.allow;
+                self.exceptions.clear();
+                return;
+            }
+            Some(type_) => type_,
+        };
+        let access = device.access.unwrap_or(DeviceAccess::ALL);
+
+        let pos = self
+            .exceptions
+            .iter()
+            .position(|e| e.type_ == type_ && e.major == device.major && e.minor == device.minor);
+
+        if device.allow == self.default_allow {
+            if let Some(pos) = pos {
+                let exception = &mut self.exceptions[pos];
+                exception.access = exception.access - access;
+                if exception.access.is_empty() {
+                    self.exceptions.remove(pos);
+                }
+            }
+        } else {
+            match pos {
+                Some(pos) => self.exceptions[pos].access |= access,
+                None => self.exceptions.push(Exception {
+                    type_,
+                    major: device.major,
+                    minor: device.minor,
+                    access,
+                }),
+            }
+        }
+    }
+
+    /// Returns whether `access` to the device is permitted.
+    ///
+    /// If the default behavior is to allow, the access is denied if any exception matching the
+    /// device has any of the permissions. Otherwise, the access is allowed only if an exception
+    /// matching the device has all of the permissions.
+    pub fn is_allowed(
+        &self,
+        type_: DeviceType,
+        major: i64,
+        minor: i64,
+        access: DeviceAccess,
+    ) -> bool {
+        let mut matches = self
+            .exceptions
+            .iter()
+            .filter(|e| e.matches(type_, major, minor));
+
+        if self.default_allow {
+            !matches.any(|e| e.access.intersects(access))
+        } else {
+            matches.any(|e| e.access.contains(access))
+        }
+    }
+}
+
+impl Exception {
+    fn matches(&self, type_: DeviceType, major: i64, minor: i64) -> bool {
+        (type_ == DeviceType::All || type_ == self.type_)
+            && self.major.map_or(true, |m| m == major)
+            && self.minor.map_or(true, |m| m == minor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(allow: bool, type_: char, major: i64, minor: i64, access: &str) -> Device {
+        let type_ = match type_ {
+            'a' => DeviceType::All,
+            'c' => DeviceType::Character,
+            _ => DeviceType::Block,
+        };
+        let id = |n| if n < 0 { None } else { Some(n) };
+        Device {
+            allow,
+            type_: Some(type_),
+            major: id(major),
+            minor: id(minor),
+            access: Some(access.parse().unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_device_cgroup_deny_default() {
+        let cgroup = DeviceCgroup::from_devices(&[
+            device(false, 'a', -1, -1, "rwm"),
+            device(true, 'c', -1, -1, "m"),
+            device(true, 'c', 1, 3, "rw"),
+            device(false, 'c', 1, 3, "w"),
+        ]);
+
+        assert!(!cgroup.default_allow);
+        assert!(cgroup.is_allowed(DeviceType::Character, 1, 3, DeviceAccess::READ));
+        assert!(!cgroup.is_allowed(DeviceType::Character, 1, 3, DeviceAccess::WRITE));
+        assert!(cgroup.is_allowed(DeviceType::Character, 4, 0, DeviceAccess::MKNOD));
+        assert!(!cgroup.is_allowed(DeviceType::Character, 4, 0, DeviceAccess::READ));
+        assert!(!cgroup.is_allowed(DeviceType::Block, 8, 0, DeviceAccess::MKNOD));
+
+        // Permissions of different exceptions are not combined
+        assert!(!cgroup.is_allowed(DeviceType::Character, 1, 3, "rm".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_device_cgroup_allow_default() {
+        let mut cgroup = DeviceCgroup::default();
+        cgroup.apply(&device(false, 'b', 8, -1, "w"));
+        cgroup.apply(&device(false, 'c', 10, 200, "rwm"));
+        cgroup.apply(&device(true, 'c', 10, 200, "rw"));
+
+        assert!(cgroup.is_allowed(DeviceType::Block, 8, 0, DeviceAccess::READ));
+        assert!(!cgroup.is_allowed(DeviceType::Block, 8, 1, DeviceAccess::ALL));
+        assert!(cgroup.is_allowed(DeviceType::Character, 10, 200, DeviceAccess::WRITE));
+        assert!(!cgroup.is_allowed(DeviceType::Character, 10, 200, DeviceAccess::MKNOD));
+
+        // Type `a` resets the exceptions regardless of the numbers
+        cgroup.apply(&device(true, 'a', 5, -1, "r"));
+        assert_eq!(cgroup, DeviceCgroup::default());
+    }
+
+    #[test]
+    fn test_device_access() {
+        let access: DeviceAccess = "mr".parse().unwrap();
+        assert_eq!(access, DeviceAccess::READ | DeviceAccess::MKNOD);
+        assert_eq!(access.to_string(), "rm");
+        assert_eq!("".parse(), Ok(DeviceAccess::NONE));
+        assert_eq!(
+            "rx".parse::<DeviceAccess>().unwrap_err().to_string(),
+            "Invalid device access: 'x'"
+        );
+    }
+}
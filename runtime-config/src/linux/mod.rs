@@ -4,9 +4,15 @@
 //!
 //! [OCI runtime spec for Linux-specific section]: https://github.com/opencontainers/runtime-spec/blob/v1.0.0/config-linux.md
 
+pub mod capabilities;
+pub mod cgroups;
+pub mod cpuset;
+pub mod device_cgroup;
+pub mod namespaces;
 pub mod resources;
 pub mod seccomp;
 
+pub use cpuset::CpuSet;
 pub use resources::Resources;
 pub use seccomp::Seccomp;
 
@@ -16,7 +22,7 @@ use std::{collections::HashMap, path::PathBuf};
 use serde::{Deserialize, Serialize};
 
 /// Schema of Linux-specific config section.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -68,7 +74,10 @@ pub struct Linux {
     pub intel_rdt: Option<IntelRdt>,
 
     /// Kernel parameters to be modified at runtime for the container.
-    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "HashMap::is_empty"))]
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip_serializing_if = "HashMap::is_empty", default)
+    )]
     pub sysctl: HashMap<String, String>,
 
     /// Seccomp config for the container.
@@ -102,6 +111,22 @@ pub struct Linux {
     /// SELinux context for the mounts in the container.
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub mount_label: Option<String>,
+
+    /// Offsets of the clocks in the time namespace, keyed by clock name (`monotonic` or
+    /// `boottime`).
+    ///
+    /// See the [time_namespaces(7)] man page for more information.
+    ///
+    /// [time_namespaces(7)]: http://man7.org/linux/man-pages/man7/time_namespaces.7.html
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip_serializing_if = "HashMap::is_empty", default)
+    )]
+    pub time_offsets: HashMap<String, TimeOffset>,
+
+    /// Execution domain of the container process, set by personality(2).
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub personality: Option<Personality>,
 }
 
 /// Namespace attached to this container.
@@ -139,6 +164,49 @@ pub enum NamespaceType {
     User,
     /// Isolated view of a cgroup hierarchy.
     Cgroup,
+    /// Time namespace.
+    Time,
+}
+
+/// Offset of a clock in a time namespace.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimeOffset {
+    /// Offset in seconds.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub secs: i64,
+
+    /// Offset in nanoseconds, added to `secs`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub nanosecs: u32,
+}
+
+/// Execution domain of a container process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Personality {
+    /// Execution domain.
+    pub domain: PersonalityDomain,
+
+    /// Additional flags to apply.
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip_serializing_if = "Vec::is_empty", default)
+    )]
+    pub flags: Vec<String>,
+}
+
+/// Execution domains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PersonalityDomain {
+    /// Standard Linux execution domain (`LINUX`).
+    #[cfg_attr(feature = "serde", serde(rename = "LINUX"))]
+    Linux,
+
+    /// 32-bit execution domain on 64-bit architectures (`LINUX32`).
+    #[cfg_attr(feature = "serde", serde(rename = "LINUX32"))]
+    Linux32,
 }
 
 /// User namespace ID mappings from a host to a container.
@@ -216,16 +284,27 @@ pub enum DeviceType {
 }
 
 /// Intel Resource Director Technology.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
     serde(rename_all = "camelCase")
 )]
 pub struct IntelRdt {
+    /// Identity of the class of service (CLOS), i.e. the name of the resctrl group.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "closID", skip_serializing_if = "Option::is_none")
+    )]
+    pub clos_id: Option<String>,
+
     /// Schema for L3 cache ID and capacity bitmask (CBM).
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub l3_cache_schema: Option<String>,
+
+    /// Schema of memory bandwidth per L3 cache ID.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub mem_bw_schema: Option<String>,
 }
 
 /// Rootfs mount propagation.
@@ -258,8 +337,62 @@ mod tests {
         assert_eq!(
             intel_rdt,
             IntelRdt {
-                l3_cache_schema: Some(String::from("L3:0=ffff0;1=3ff"))
+                l3_cache_schema: Some(String::from("L3:0=ffff0;1=3ff")),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_linux_serde() {
+        const JSON: &str = r#"{
+            "namespaces": [{"type": "time"}],
+            "timeOffsets": {
+                "monotonic": {"secs": 172800, "nanosecs": 0},
+                "boottime": {"secs": -3600, "nanosecs": 500}
+            },
+            "personality": {"domain": "LINUX32"},
+            "intelRdt": {
+                "closID": "guaranteed_group",
+                "l3CacheSchema": "L3:0=7f0;1=1f",
+                "memBwSchema": "MB:0=20;1=70"
+            },
+            "resources": {
+                "memory": {"limit": 536870912, "useHierarchy": true, "checkBeforeUpdate": true},
+                "cpu": {"idle": 1},
+                "rdma": {"mlx5_1": {"hcaHandles": 3, "hcaObjects": 10000}},
+                "unified": {"memory.high": "1073741824"}
             }
+        }"#;
+
+        let linux: Linux = serde_json::from_str(JSON).unwrap();
+
+        assert_eq!(linux.namespaces[0].type_, NamespaceType::Time);
+        assert_eq!(linux.time_offsets["boottime"].secs, -3600);
+        assert_eq!(
+            linux.personality.as_ref().unwrap().domain,
+            PersonalityDomain::Linux32
+        );
+        let intel_rdt = linux.intel_rdt.as_ref().unwrap();
+        assert_eq!(
+            intel_rdt.clos_id.as_ref().map(String::as_str),
+            Some("guaranteed_group")
+        );
+        assert_eq!(
+            intel_rdt.mem_bw_schema.as_ref().map(String::as_str),
+            Some("MB:0=20;1=70")
+        );
+        let resources = linux.resources.as_ref().unwrap();
+        let memory = resources.memory.as_ref().unwrap();
+        assert_eq!(memory.use_hierarchy, Some(true));
+        assert_eq!(memory.check_before_update, Some(true));
+        assert_eq!(resources.cpu.as_ref().unwrap().idle, Some(1));
+        assert_eq!(resources.rdma["mlx5_1"].hca_objects, Some(10000));
+        assert_eq!(resources.unified["memory.high"], "1073741824");
+
+        assert_eq!(
+            serde_json::to_value(&linux).unwrap(),
+            serde_json::from_str::<serde_json::Value>(JSON).unwrap()
         );
     }
 }
@@ -0,0 +1,374 @@
+//! Setup of the namespaces of a container.
+//!
+//! Namespaces without a `path` are created with the `CLONE_NEW*` flags returned by
+//! [`clone_flags`], and namespaces with a `path` are joined with setns(2). User namespace ID
+//! mappings are written to `/proc/<pid>/uid_map` and `/proc/<pid>/gid_map` of the container
+//! process, and time namespace clock offsets to `/proc/<pid>/timens_offsets`. For more
+//! information, see the [namespaces(7)], [user_namespaces(7)] and [time_namespaces(7)] man pages.
+//!
+//! [`clone_flags`]: fn.clone_flags.html
+//! [namespaces(7)]: http://man7.org/linux/man-pages/man7/namespaces.7.html
+//! [user_namespaces(7)]: http://man7.org/linux/man-pages/man7/user_namespaces.7.html
+//! [time_namespaces(7)]: http://man7.org/linux/man-pages/man7/time_namespaces.7.html
+
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io,
+    os::{raw::c_int, unix::io::AsRawFd},
+    path::PathBuf,
+};
+
+use super::{Namespace, NamespaceType, TimeOffset, UserNamespaceMappings};
+
+/// Namespace file opened to be joined.
+#[derive(Debug)]
+pub struct NamespaceFile {
+    /// Type of the namespace.
+    pub type_: NamespaceType,
+    /// Path to the namespace file.
+    pub path: PathBuf,
+    /// Opened namespace file.
+    pub file: File,
+}
+
+impl NamespaceType {
+    /// Returns the `CLONE_NEW*` flag of this namespace type.
+    ///
+    /// `CLONE_NEWTIME` of `Time` overlaps the byte of the exit signal (`CSIGNAL`) in the flags of
+    /// clone(2), so it can be used only with unshare(2), clone3(2) and setns(2).
+    pub fn clone_flag(self) -> c_int {
+        match self {
+            NamespaceType::Pid => libc::CLONE_NEWPID,
+            NamespaceType::Network => libc::CLONE_NEWNET,
+            NamespaceType::Mount => libc::CLONE_NEWNS,
+            NamespaceType::Ipc => libc::CLONE_NEWIPC,
+            NamespaceType::Uts => libc::CLONE_NEWUTS,
+            NamespaceType::User => libc::CLONE_NEWUSER,
+            NamespaceType::Cgroup => libc::CLONE_NEWCGROUP,
+            NamespaceType::Time => libc::CLONE_NEWTIME,
+        }
+    }
+
+    /// Returns the name of the namespace file in `/proc/<pid>/ns`.
+    pub fn proc_name(self) -> &'static str {
+        match self {
+            NamespaceType::Pid => "pid",
+            NamespaceType::Network => "net",
+            NamespaceType::Mount => "mnt",
+            NamespaceType::Ipc => "ipc",
+            NamespaceType::Uts => "uts",
+            NamespaceType::User => "user",
+            NamespaceType::Cgroup => "cgroup",
+            NamespaceType::Time => "time",
+        }
+    }
+}
+
+/// Returns the `CLONE_NEW*` flags to create the namespaces without a `path`.
+///
+/// The flags are meant for unshare(2). If they contain `CLONE_NEWTIME`, they cannot be passed to
+/// clone(2), which takes the exit signal in the same bits (see [`NamespaceType::clone_flag`]).
+///
+/// [`NamespaceType::clone_flag`]: ../enum.NamespaceType.html#method.clone_flag
+///
+/// # Examples
+///
+/// ```
+/// use oci_runtime_config::linux::{namespaces, Namespace, NamespaceType};
+///
+/// let flags = namespaces::clone_flags(&[
+///     Namespace {
+///         type_: NamespaceType::User,
+///         path: None,
+///     },
+///     Namespace {
+///         type_: NamespaceType::Network,
+///         path: Some("/var/run/netns/foo".into()),
+///     },
+/// ]);
+///
+/// assert_eq!(flags, libc::CLONE_NEWUSER);
+/// ```
+pub fn clone_flags(namespaces: &[Namespace]) -> c_int {
+    namespaces
+        .iter()
+        .filter(|ns| ns.path.is_none())
+        .fold(0, |flags, ns| flags | ns.type_.clone_flag())
+}
+
+/// Moves the calling process into new namespaces for the namespaces without a `path`.
+pub fn unshare(namespaces: &[Namespace]) -> io::Result<()> {
+    if unsafe { libc::unshare(clone_flags(namespaces)) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Opens the namespace files of the namespaces with a `path`.
+///
+/// The user namespace comes first, so that the other namespaces, which may be owned by it, can be
+/// joined with the capabilities gained in it. The other namespaces are in the order of
+/// `namespaces`.
+pub fn open(namespaces: &[Namespace]) -> io::Result<Vec<NamespaceFile>> {
+    let mut files = namespaces
+        .iter()
+        .filter_map(|ns| ns.path.as_ref().map(|path| (ns.type_, path)))
+        .map(|(type_, path)| {
+            let file = File::open(path)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+            Ok(NamespaceFile {
+                type_,
+                path: path.clone(),
+                file,
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    files.sort_by_key(|f| f.type_ != NamespaceType::User);
+    Ok(files)
+}
+
+/// Joins the namespaces of `files` in order.
+///
+/// This does not allocate, so it can be called between fork(2) and execve(2), e.g. in
+/// `CommandExt::pre_exec`.
+pub fn setns(files: &[NamespaceFile]) -> io::Result<()> {
+    for f in files {
+        if unsafe { libc::setns(f.file.as_raw_fd(), f.type_.clone_flag()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Joins the namespaces with a `path`.
+pub fn enter(namespaces: &[Namespace]) -> io::Result<()> {
+    setns(&open(namespaces)?)
+}
+
+/// Formats ID mappings in the format of `/proc/<pid>/uid_map`.
+pub fn format_mappings(mappings: &[UserNamespaceMappings]) -> String {
+    mappings
+        .iter()
+        .map(|m| format!("{} {} {}\n", m.container_id, m.host_id, m.size))
+        .collect()
+}
+
+/// Writes the user namespace ID mappings of the process `pid`.
+///
+/// Empty mappings are not written. If `allow_setgroups` is `false`, setgroups(2) is denied in the
+/// user namespace before the GID mappings are written, which is required for an unprivileged
+/// process to write the GID mappings.
+///
+/// The mappings can be written only once for each user namespace.
+pub fn write_mappings(
+    pid: u32,
+    uid_mappings: &[UserNamespaceMappings],
+    gid_mappings: &[UserNamespaceMappings],
+    allow_setgroups: bool,
+) -> io::Result<()> {
+    let proc = PathBuf::from(format!("/proc/{}", pid));
+
+    if !uid_mappings.is_empty() {
+        fs::write(proc.join("uid_map"), format_mappings(uid_mappings))?;
+    }
+    if !allow_setgroups {
+        fs::write(proc.join("setgroups"), "deny")?;
+    }
+    if !gid_mappings.is_empty() {
+        fs::write(proc.join("gid_map"), format_mappings(gid_mappings))?;
+    }
+
+    Ok(())
+}
+
+/// Formats clock offsets in the format of `/proc/<pid>/timens_offsets`, sorted by clock name.
+pub fn format_time_offsets(offsets: &HashMap<String, TimeOffset>) -> String {
+    let mut clocks: Vec<_> = offsets.iter().collect();
+    clocks.sort_by_key(|&(clock, _)| clock);
+    clocks
+        .into_iter()
+        .map(|(clock, offset)| format!("{} {} {}\n", clock, offset.secs, offset.nanosecs))
+        .collect()
+}
+
+/// Writes the clock offsets of the time namespace of the process `pid`.
+///
+/// After unshare(2) with `CLONE_NEWTIME`, the process itself stays in its time namespace and the
+/// new namespace is entered by its children, so the offsets must be written before the first
+/// child is created. Empty offsets are not written.
+pub fn write_time_offsets(pid: u32, offsets: &HashMap<String, TimeOffset>) -> io::Result<()> {
+    if offsets.is_empty() {
+        return Ok(());
+    }
+    fs::write(
+        format!("/proc/{}/timens_offsets", pid),
+        format_time_offsets(offsets),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read, Write},
+        os::unix::process::CommandExt,
+        process::{Command, Stdio},
+    };
+
+    fn namespace(type_: NamespaceType, path: Option<PathBuf>) -> Namespace {
+        Namespace { type_, path }
+    }
+
+    fn mapping(host_id: u32) -> UserNamespaceMappings {
+        UserNamespaceMappings {
+            host_id,
+            container_id: 0,
+            size: 1,
+        }
+    }
+
+    #[test]
+    fn test_clone_flags() {
+        let namespaces = vec![
+            namespace(NamespaceType::Pid, None),
+            namespace(NamespaceType::Mount, None),
+            namespace(
+                NamespaceType::Network,
+                Some(PathBuf::from("/proc/1/ns/net")),
+            ),
+            namespace(NamespaceType::Cgroup, None),
+        ];
+        assert_eq!(
+            clone_flags(&namespaces),
+            libc::CLONE_NEWPID | libc::CLONE_NEWNS | libc::CLONE_NEWCGROUP
+        );
+        assert_eq!(clone_flags(&[]), 0);
+    }
+
+    #[test]
+    fn test_open() {
+        let namespaces = vec![
+            namespace(NamespaceType::Uts, Some(PathBuf::from("/proc/self/ns/uts"))),
+            namespace(NamespaceType::Pid, None),
+            namespace(
+                NamespaceType::User,
+                Some(PathBuf::from("/proc/self/ns/user")),
+            ),
+        ];
+        let files = open(&namespaces).unwrap();
+        let types: Vec<_> = files.iter().map(|f| f.type_).collect();
+        assert_eq!(types, vec![NamespaceType::User, NamespaceType::Uts]);
+
+        let err = open(&[namespace(
+            NamespaceType::Ipc,
+            Some(PathBuf::from("/nonexistent")),
+        )])
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().starts_with("/nonexistent: "));
+    }
+
+    #[test]
+    fn test_format_mappings() {
+        let mappings = vec![
+            mapping(1000),
+            UserNamespaceMappings {
+                host_id: 100000,
+                container_id: 1,
+                size: 65536,
+            },
+        ];
+        assert_eq!(format_mappings(&mappings), "0 1000 1\n1 100000 65536\n");
+    }
+
+    #[test]
+    fn test_format_time_offsets() {
+        let mut offsets = HashMap::new();
+        offsets.insert(
+            String::from("monotonic"),
+            TimeOffset {
+                secs: 86400,
+                nanosecs: 0,
+            },
+        );
+        offsets.insert(
+            String::from("boottime"),
+            TimeOffset {
+                secs: -3600,
+                nanosecs: 500,
+            },
+        );
+        assert_eq!(
+            format_time_offsets(&offsets),
+            "boottime -3600 500\nmonotonic 86400 0\n"
+        );
+        assert_eq!(format_time_offsets(&HashMap::new()), "");
+    }
+
+    #[test]
+    fn test_user_namespace() {
+        let uid = unsafe { libc::geteuid() };
+        let gid = unsafe { libc::getegid() };
+        let user = [namespace(NamespaceType::User, None)];
+
+        // The child waits for its mappings to be written by the parent
+        let mut child = unsafe {
+            Command::new("/bin/sh")
+                .args(&[
+                    "-c",
+                    "read x; cat /proc/self/uid_map /proc/self/setgroups; sleep 10",
+                ])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .pre_exec(move || unshare(&user))
+                .spawn()
+                .unwrap()
+        };
+        let pid = child.id();
+        write_mappings(pid, &[mapping(uid)], &[mapping(gid)], false).unwrap();
+        child.stdin.take().unwrap().write_all(b"\n").unwrap();
+
+        let mut buf = [0; 256];
+        let mut stdout = child.stdout.take().unwrap();
+        let mut output = String::new();
+        while output.lines().count() < 2 {
+            let n = stdout.read(&mut buf).unwrap();
+            assert_ne!(n, 0);
+            output.push_str(std::str::from_utf8(&buf[..n]).unwrap());
+        }
+        let fields: Vec<_> = output.split_whitespace().collect();
+        assert_eq!(fields, vec!["0", &uid.to_string(), "1", "deny"]);
+
+        // Join the user namespace of the child
+        let files = open(&[namespace(
+            NamespaceType::User,
+            Some(PathBuf::from(format!(
+                "/proc/{}/ns/{}",
+                pid,
+                NamespaceType::User.proc_name()
+            ))),
+        )])
+        .unwrap();
+        let output = unsafe {
+            Command::new("/bin/cat")
+                .arg("/proc/self/gid_map")
+                .pre_exec(move || setns(&files))
+                .output()
+                .unwrap()
+        };
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        assert!(output.status.success());
+        let fields: Vec<_> = std::str::from_utf8(&output.stdout)
+            .unwrap()
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            fields,
+            vec!["0".to_string(), gid.to_string(), "1".to_string()]
+        );
+    }
+}
@@ -4,11 +4,15 @@
 //!
 //! [kernel docs]: https://www.kernel.org/doc/Documentation/cgroup-v1/cgroups.txt
 
+use std::{collections::HashMap, error::Error, fmt, ops, str::FromStr};
+
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+use crate::linux::{device_cgroup::DeviceCgroup, CpuSet};
+
 /// Resource limits for a container forced by cgroups.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -51,6 +55,31 @@ pub struct Resources {
     /// Represents a cgroup `pids` subsystems for the container.
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub pids: Option<Pids>,
+
+    /// Limits on the container's RDMA resources, keyed by device name (e.g. `mlx5_1`).
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip_serializing_if = "HashMap::is_empty", default)
+    )]
+    pub rdma: HashMap<String, Rdma>,
+
+    /// cgroup v2 interface files to be written, keyed by file name (e.g. `memory.high`).
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip_serializing_if = "HashMap::is_empty", default)
+    )]
+    pub unified: HashMap<String, String>,
+}
+
+impl Resources {
+    /// Returns the state of the devices cgroup after applying `devices`.
+    ///
+    /// See [`DeviceCgroup`] for how the whitelist is evaluated.
+    ///
+    /// [`DeviceCgroup`]: ../device_cgroup/struct.DeviceCgroup.html
+    pub fn device_cgroup(&self) -> DeviceCgroup {
+        DeviceCgroup::from_devices(&self.devices)
+    }
 }
 
 /// Device whitelist.
@@ -75,9 +104,9 @@ pub struct Device {
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
     pub minor: Option<i64>,
 
-    /// Permission for the device. Composition of `r` (read), `w` (write), and `m` (mknod).
+    /// Permission for the device. Unset values mean all permissions, mapping to `rwm`.
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
-    pub access: Option<String>, // TODO: User proper type?
+    pub access: Option<DeviceAccess>,
 }
 
 /// Types of devices.
@@ -100,10 +129,147 @@ pub enum DeviceType {
     Block,
 }
 
+/// Permission for devices, which is a composition of `r` (read), `w` (write), and `m` (mknod).
+///
+/// When the feature `serde` is enabled, `DeviceAccess` can be serialized to / deserialized from a
+/// string of the permission characters (e.g. `rw`).
+///
+/// # Examples
+///
+/// ```
+/// use oci_runtime_config::linux::resources::DeviceAccess;
+///
+/// let access: DeviceAccess = "rw".parse().unwrap();
+///
+/// assert!(access.contains(DeviceAccess::READ));
+/// assert!(!access.contains(DeviceAccess::MKNOD));
+/// assert_eq!(access | DeviceAccess::MKNOD, DeviceAccess::ALL);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct DeviceAccess(u8);
+
+/// Error type that can be returned when a string contains characters other than `r`, `w`, and `m`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDeviceAccessError(char);
+
+impl DeviceAccess {
+    /// No permissions.
+    pub const NONE: DeviceAccess = DeviceAccess(0);
+    /// Permission to read (`r`).
+    pub const READ: DeviceAccess = DeviceAccess(1);
+    /// Permission to write (`w`).
+    pub const WRITE: DeviceAccess = DeviceAccess(2);
+    /// Permission to create device files (`m`).
+    pub const MKNOD: DeviceAccess = DeviceAccess(4);
+    /// All permissions (`rwm`).
+    pub const ALL: DeviceAccess = DeviceAccess(7);
+
+    /// Returns whether no permissions are set.
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns whether all permissions of `other` are set.
+    pub fn contains(self, other: DeviceAccess) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns whether any permission of `other` is set.
+    pub fn intersects(self, other: DeviceAccess) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl ops::BitOr for DeviceAccess {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        DeviceAccess(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for DeviceAccess {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl ops::BitAnd for DeviceAccess {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        DeviceAccess(self.0 & rhs.0)
+    }
+}
+
+impl ops::Sub for DeviceAccess {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        DeviceAccess(self.0 & !rhs.0)
+    }
+}
+
+impl FromStr for DeviceAccess {
+    type Err = ParseDeviceAccessError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut access = DeviceAccess::NONE;
+        for c in s.chars() {
+            access |= match c {
+                'r' => DeviceAccess::READ,
+                'w' => DeviceAccess::WRITE,
+                'm' => DeviceAccess::MKNOD,
+                _ => return Err(ParseDeviceAccessError(c)),
+            };
+        }
+        Ok(access)
+    }
+}
+
+impl fmt::Display for DeviceAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let chars = [
+            (DeviceAccess::READ, "r"),
+            (DeviceAccess::WRITE, "w"),
+            (DeviceAccess::MKNOD, "m"),
+        ];
+        for &(access, c) in &chars {
+            if self.contains(access) {
+                f.write_str(c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for DeviceAccess {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        ser.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for DeviceAccess {
+    fn deserialize<D: serde::Deserializer<'de>>(deser: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deser)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for ParseDeviceAccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid device access: {:?}", self.0)
+    }
+}
+
+impl Error for ParseDeviceAccessError {}
+
 /// Limits on a container's memory usage.
 ///
 /// Values for memory specify the limit in bytes, or `-1` for unlimited memory usage.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Memory {
     /// Limit on memory usage.
@@ -139,10 +305,25 @@ pub struct Memory {
         serde(rename = "disableOOMKiller", skip_serializing_if = "Option::is_none")
     )]
     pub disable_oom_killer: Option<bool>,
+
+    /// Whether to enable hierarchical memory accounting.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "useHierarchy", skip_serializing_if = "Option::is_none")
+    )]
+    pub use_hierarchy: Option<bool>,
+
+    /// Whether to check that the new memory limit is not below the current usage before updating
+    /// it.
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "checkBeforeUpdate", skip_serializing_if = "Option::is_none")
+    )]
+    pub check_before_update: Option<bool>,
 }
 
 /// Limits on a container's CPU usage.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -173,15 +354,19 @@ pub struct Cpu {
 
     /// List of CPUs the container will run on.
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
-    pub cpus: Option<String>, // TODO: Use proper type?
+    pub cpus: Option<CpuSet>,
 
     /// List of memory nodes the container will use.
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
-    pub mems: Option<String>, // TODO: Use proper type?
+    pub mems: Option<CpuSet>,
+
+    /// Whether the cgroup is scheduled with `SCHED_IDLE` (`1`) or not (`0`).
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub idle: Option<i64>,
 }
 
 /// Represents a cgroup `blkio` subsystems for a container.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 #[cfg_attr(
     feature = "serde",
     derive(Serialize, Deserialize),
@@ -329,3 +514,20 @@ pub struct Pids {
     /// Maximum number of tasks in the cgroup.
     pub limit: i64,
 }
+
+/// Limits on RDMA resources of a device, represented by a cgroup `rdma` subsystem.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct Rdma {
+    /// Maximum number of HCA handles.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub hca_handles: Option<u32>,
+
+    /// Maximum number of HCA objects.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub hca_objects: Option<u32>,
+}
@@ -0,0 +1,826 @@
+//! Compilation of seccomp configs into classic BPF programs.
+//!
+//! The programs are loaded with `SECCOMP_SET_MODE_FILTER` as described in the [seccomp(2)] man
+//! page, and check the architecture and the system call number in `struct seccomp_data`.
+//!
+//! [seccomp(2)]: http://man7.org/linux/man-pages/man2/seccomp.2.html
+
+use std::{error::Error, fmt};
+
+use libc::sock_filter;
+
+use super::{syscalls, Action, Architecture, Seccomp, Syscall, SyscallArg, SyscallCmp};
+
+const BPF_LD: u16 = 0x00;
+const BPF_W: u16 = 0x00;
+const BPF_ABS: u16 = 0x20;
+const BPF_ALU: u16 = 0x04;
+const BPF_AND: u16 = 0x50;
+const BPF_JMP: u16 = 0x05;
+const BPF_JA: u16 = 0x00;
+const BPF_JEQ: u16 = 0x10;
+const BPF_JGT: u16 = 0x20;
+const BPF_JGE: u16 = 0x30;
+const BPF_K: u16 = 0x00;
+const BPF_RET: u16 = 0x06;
+
+/// Maximum number of instructions in a program accepted by the kernel.
+const BPF_MAXINSNS: usize = 4096;
+
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_KILL_THREAD: u32 = 0x0000_0000;
+const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+const SECCOMP_RET_TRACE: u32 = 0x7ff0_0000;
+const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+/// Mask of the data passed with `SECCOMP_RET_ERRNO` and `SECCOMP_RET_TRACE`.
+const SECCOMP_RET_DATA: u32 = 0x0000_ffff;
+
+/// Offsets of the fields in `struct seccomp_data`.
+const DATA_NR: u32 = 0;
+const DATA_ARCH: u32 = 4;
+const DATA_ARGS: u32 = 16;
+
+/// Number of arguments of a system call in `struct seccomp_data`.
+const MAX_ARGS: usize = 6;
+
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// Error type that can be returned when a seccomp config cannot be compiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompileError {
+    /// No supported architecture is specified, and the native architecture is not supported.
+    NoArchitecture,
+    /// Index of a system call argument is not less than 6.
+    InvalidArgIndex(usize),
+    /// Conditions of a rule are too many to be jumped over.
+    TooManyArgs(Vec<String>),
+    /// Program exceeds the maximum number of instructions.
+    TooLarge(usize),
+}
+
+impl Seccomp {
+    /// Compiles this config into a classic BPF program for `SECCOMP_SET_MODE_FILTER`.
+    ///
+    /// The program is built for the native architecture and `architectures`. Each system call is
+    /// checked against the rules in order, and the action of the first matching rule is taken. A
+    /// rule matches if the system call has one of `names` and all of `args` are satisfied, except
+    /// that any of `args` is enough if an argument index appears more than once, as in runc.
+    ///
+    /// Names unknown for an architecture are ignored for it, as the system call is not available
+    /// there. Architectures whose system call numbers are unknown, such as `SCMP_ARCH_S390`, are
+    /// skipped, so that profiles listing them, e.g. the default profile of Docker, can be compiled.
+    /// System calls of other architectures are killed.
+    ///
+    /// # Errors
+    ///
+    /// If no architecture is supported, or the program exceeds the size limit of the kernel,
+    /// `Err(CompileError)` is returned.
+    pub fn compile(&self) -> Result<Vec<sock_filter>, CompileError> {
+        let mut arches = Vec::new();
+        for arch in Architecture::native().iter().chain(&self.architectures) {
+            if arch.audit_arch().is_some() && !arches.contains(arch) {
+                arches.push(*arch);
+            }
+        }
+        if arches.is_empty() {
+            return Err(CompileError::NoArchitecture);
+        }
+
+        for syscall in &self.syscalls {
+            if let Some(arg) = syscall.args.iter().find(|a| a.index >= MAX_ARGS) {
+                return Err(CompileError::InvalidArgIndex(arg.index));
+            }
+        }
+
+        let mut prog = vec![stmt(BPF_LD | BPF_W | BPF_ABS, DATA_ARCH)];
+
+        // x86-64 and x32 share the audit architecture, and are dispatched by `X32_SYSCALL_BIT`
+        let mut blocks: Vec<(u32, Vec<Architecture>)> = Vec::new();
+        for arch in arches {
+            let audit = arch.audit_arch().unwrap();
+            match blocks.iter_mut().find(|(a, _)| *a == audit) {
+                Some((_, block)) => block.push(arch),
+                None => blocks.push((audit, vec![arch])),
+            }
+        }
+
+        for (audit, block_arches) in blocks {
+            let block = self.compile_block(&block_arches)?;
+            prog.push(jump(BPF_JMP | BPF_JEQ | BPF_K, audit, 1, 0));
+            prog.push(stmt(BPF_JMP | BPF_JA, block.len() as u32));
+            prog.extend(block);
+        }
+        prog.push(stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_THREAD));
+
+        if prog.len() > BPF_MAXINSNS {
+            return Err(CompileError::TooLarge(prog.len()));
+        }
+        Ok(prog)
+    }
+
+    /// Compiles the rules for architectures sharing an audit architecture.
+    fn compile_block(&self, arches: &[Architecture]) -> Result<Vec<sock_filter>, CompileError> {
+        let mut block = vec![stmt(BPF_LD | BPF_W | BPF_ABS, DATA_NR)];
+
+        let has = |arch| arches.contains(&arch);
+        if has(Architecture::X86_64) != has(Architecture::X32) {
+            let (jt, jf) = if has(Architecture::X32) {
+                (1, 0)
+            } else {
+                (0, 1)
+            };
+            block.push(jump(BPF_JMP | BPF_JGE | BPF_K, X32_SYSCALL_BIT, jt, jf));
+            block.push(stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_THREAD));
+        }
+
+        // Whether the accumulator holds the system call number
+        let mut loaded = true;
+        // Numbers of consecutive rules without arguments and with the same action
+        let mut run: Vec<u32> = Vec::new();
+        let mut run_action = None;
+
+        for syscall in &self.syscalls {
+            let mut numbers = Vec::new();
+            for name in &syscall.names {
+                for &arch in arches {
+                    if let Some(nr) = arch.syscall_number(name) {
+                        numbers.push(nr);
+                    }
+                }
+            }
+            if numbers.is_empty() {
+                continue;
+            }
+
+            let action = syscall.action.ret(syscall.errno_ret);
+            if run_action != Some(action) || !syscall.args.is_empty() {
+                flush_run(&mut block, &mut run, run_action, &mut loaded);
+            }
+
+            if syscall.args.is_empty() {
+                run_action = Some(action);
+                run.extend(numbers);
+            } else {
+                for args in split_args(syscall) {
+                    let body = compile_args(&args, arches[0].big_endian(), action)
+                        .ok_or_else(|| CompileError::TooManyArgs(syscall.names.clone()))?;
+                    for &nr in &numbers {
+                        if !loaded {
+                            block.push(stmt(BPF_LD | BPF_W | BPF_ABS, DATA_NR));
+                        }
+                        block.push(jump(BPF_JMP | BPF_JEQ | BPF_K, nr, 1, 0));
+                        block.push(stmt(BPF_JMP | BPF_JA, body.len() as u32));
+                        block.extend_from_slice(&body);
+                        loaded = false;
+                    }
+                }
+            }
+        }
+        flush_run(&mut block, &mut run, run_action, &mut loaded);
+
+        block.push(stmt(
+            BPF_RET | BPF_K,
+            self.default_action.ret(self.default_errno_ret),
+        ));
+        Ok(block)
+    }
+}
+
+/// Emits checks for a run of system call numbers returning the same action.
+fn flush_run(
+    block: &mut Vec<sock_filter>,
+    run: &mut Vec<u32>,
+    action: Option<u32>,
+    loaded: &mut bool,
+) {
+    // Conditional jumps are limited to 255 instructions
+    for chunk in run.chunks(255) {
+        if !*loaded {
+            block.push(stmt(BPF_LD | BPF_W | BPF_ABS, DATA_NR));
+            *loaded = true;
+        }
+        for (i, &nr) in chunk.iter().enumerate() {
+            let jt = (chunk.len() - i) as u8;
+            block.push(jump(BPF_JMP | BPF_JEQ | BPF_K, nr, jt, 0));
+        }
+        block.push(stmt(BPF_JMP | BPF_JA, 1));
+        block.push(stmt(BPF_RET | BPF_K, action.unwrap()));
+    }
+    run.clear();
+}
+
+/// Splits the arguments of a rule into sets of arguments to be satisfied all.
+fn split_args(syscall: &Syscall) -> Vec<Vec<&SyscallArg>> {
+    let args = &syscall.args;
+    let repeated = args
+        .iter()
+        .enumerate()
+        .any(|(i, a)| args[..i].iter().any(|b| b.index == a.index));
+
+    if repeated {
+        args.iter().map(|a| vec![a]).collect()
+    } else {
+        vec![args.iter().collect()]
+    }
+}
+
+/// Compiles checks of the arguments, which return `action` if all are satisfied, or fall through
+/// the end otherwise.
+///
+/// Returns `None` if the checks are too long to jump over.
+fn compile_args(args: &[&SyscallArg], big_endian: bool, action: u32) -> Option<Vec<sock_filter>> {
+    const FAIL: u8 = 0xff;
+
+    let mut body = Vec::new();
+    for arg in args {
+        let offset = DATA_ARGS + 8 * arg.index as u32;
+        let (hi_offset, lo_offset) = if big_endian {
+            (offset, offset + 4)
+        } else {
+            (offset + 4, offset)
+        };
+        let hi = |v: u64| (v >> 32) as u32;
+        let lo = |v: u64| v as u32;
+        let v = arg.value;
+
+        let ld_hi = stmt(BPF_LD | BPF_W | BPF_ABS, hi_offset);
+        let ld_lo = stmt(BPF_LD | BPF_W | BPF_ABS, lo_offset);
+        let jeq = |k, jt, jf| jump(BPF_JMP | BPF_JEQ | BPF_K, k, jt, jf);
+        let jgt = |k, jt, jf| jump(BPF_JMP | BPF_JGT | BPF_K, k, jt, jf);
+        let jge = |k, jt, jf| jump(BPF_JMP | BPF_JGE | BPF_K, k, jt, jf);
+
+        // Jump offsets of `FAIL` are resolved to the end of the body later
+        match arg.op {
+            SyscallCmp::Eq => {
+                body.extend(&[ld_hi, jeq(hi(v), 0, FAIL), ld_lo, jeq(lo(v), 0, FAIL)])
+            }
+            SyscallCmp::Ne => body.extend(&[ld_hi, jeq(hi(v), 0, 2), ld_lo, jeq(lo(v), FAIL, 0)]),
+            SyscallCmp::Gt => body.extend(&[
+                ld_hi,
+                jgt(hi(v), 3, 0),
+                jeq(hi(v), 0, FAIL),
+                ld_lo,
+                jgt(lo(v), 0, FAIL),
+            ]),
+            SyscallCmp::Ge => body.extend(&[
+                ld_hi,
+                jgt(hi(v), 3, 0),
+                jeq(hi(v), 0, FAIL),
+                ld_lo,
+                jge(lo(v), 0, FAIL),
+            ]),
+            SyscallCmp::Lt => body.extend(&[
+                ld_hi,
+                jgt(hi(v), FAIL, 0),
+                jeq(hi(v), 0, 2),
+                ld_lo,
+                jge(lo(v), FAIL, 0),
+            ]),
+            SyscallCmp::Le => body.extend(&[
+                ld_hi,
+                jgt(hi(v), FAIL, 0),
+                jeq(hi(v), 0, 2),
+                ld_lo,
+                jgt(lo(v), FAIL, 0),
+            ]),
+            SyscallCmp::MaskedEq => {
+                let datum = arg.value_two.unwrap_or(0);
+                body.extend(&[
+                    ld_hi,
+                    stmt(BPF_ALU | BPF_AND | BPF_K, hi(v)),
+                    jeq(hi(datum), 0, FAIL),
+                    ld_lo,
+                    stmt(BPF_ALU | BPF_AND | BPF_K, lo(v)),
+                    jeq(lo(datum), 0, FAIL),
+                ]);
+            }
+        }
+    }
+    body.push(stmt(BPF_RET | BPF_K, action));
+
+    let len = body.len();
+    if len > usize::from(FAIL) {
+        return None;
+    }
+    for (i, insn) in body.iter_mut().enumerate() {
+        let to_end = (len - i - 1) as u8;
+        if insn.jt == FAIL {
+            insn.jt = to_end;
+        }
+        if insn.jf == FAIL {
+            insn.jf = to_end;
+        }
+    }
+    Some(body)
+}
+
+fn stmt(code: u16, k: u32) -> sock_filter {
+    jump(code, k, 0, 0)
+}
+
+fn jump(code: u16, k: u32, jt: u8, jf: u8) -> sock_filter {
+    sock_filter { code, jt, jf, k }
+}
+
+impl Action {
+    /// Returns the return value of a seccomp filter for this action, where `errno` is the data
+    /// for `Errno` and `Trace`, defaulting to `EPERM`.
+    fn ret(self, errno: Option<u32>) -> u32 {
+        let data = errno.unwrap_or(libc::EPERM as u32) & SECCOMP_RET_DATA;
+        match self {
+            Action::Kill | Action::KillThread => SECCOMP_RET_KILL_THREAD,
+            Action::KillProcess => SECCOMP_RET_KILL_PROCESS,
+            Action::Trap => SECCOMP_RET_TRAP,
+            Action::Errno => SECCOMP_RET_ERRNO | data,
+            Action::Trace => SECCOMP_RET_TRACE | data,
+            Action::Allow => SECCOMP_RET_ALLOW,
+            Action::Log => SECCOMP_RET_LOG,
+            Action::Notify => SECCOMP_RET_USER_NOTIF,
+        }
+    }
+}
+
+impl Architecture {
+    /// Returns the architecture of the current target, if it is supported.
+    pub fn native() -> Option<Architecture> {
+        if cfg!(all(target_arch = "x86_64", target_pointer_width = "64")) {
+            Some(Architecture::X86_64)
+        } else if cfg!(target_arch = "x86_64") {
+            Some(Architecture::X32)
+        } else if cfg!(target_arch = "x86") {
+            Some(Architecture::X86)
+        } else if cfg!(target_arch = "arm") {
+            Some(Architecture::Arm)
+        } else if cfg!(target_arch = "aarch64") {
+            Some(Architecture::Aarch64)
+        } else if cfg!(all(target_arch = "mips", target_endian = "big")) {
+            Some(Architecture::Mips)
+        } else if cfg!(target_arch = "mips") {
+            Some(Architecture::Mipsel)
+        } else if cfg!(all(target_arch = "mips64", target_endian = "big")) {
+            Some(Architecture::Mips64)
+        } else if cfg!(target_arch = "mips64") {
+            Some(Architecture::Mipsel64)
+        } else if cfg!(all(target_arch = "powerpc64", target_endian = "big")) {
+            Some(Architecture::Ppc64)
+        } else if cfg!(target_arch = "powerpc64") {
+            Some(Architecture::Ppc64le)
+        } else if cfg!(target_arch = "s390x") {
+            Some(Architecture::S390X)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the `AUDIT_ARCH_*` value in `struct seccomp_data`, or `None` if the architecture is
+    /// not supported.
+    fn audit_arch(self) -> Option<u32> {
+        const EM_386: u32 = 3;
+        const EM_MIPS: u32 = 8;
+        const EM_PPC64: u32 = 21;
+        const EM_S390: u32 = 22;
+        const EM_ARM: u32 = 40;
+        const EM_X86_64: u32 = 62;
+        const EM_AARCH64: u32 = 183;
+        const ARCH_64BIT: u32 = 0x8000_0000;
+        const ARCH_LE: u32 = 0x4000_0000;
+
+        Some(match self {
+            Architecture::X86 => EM_386 | ARCH_LE,
+            Architecture::X86_64 | Architecture::X32 => EM_X86_64 | ARCH_64BIT | ARCH_LE,
+            Architecture::Arm => EM_ARM | ARCH_LE,
+            Architecture::Aarch64 => EM_AARCH64 | ARCH_64BIT | ARCH_LE,
+            Architecture::Mips => EM_MIPS,
+            Architecture::Mipsel => EM_MIPS | ARCH_LE,
+            Architecture::Mips64 => EM_MIPS | ARCH_64BIT,
+            Architecture::Mipsel64 => EM_MIPS | ARCH_64BIT | ARCH_LE,
+            Architecture::Ppc64 => EM_PPC64 | ARCH_64BIT,
+            Architecture::Ppc64le => EM_PPC64 | ARCH_64BIT | ARCH_LE,
+            Architecture::S390X => EM_S390 | ARCH_64BIT,
+            _ => return None,
+        })
+    }
+
+    fn big_endian(self) -> bool {
+        match self {
+            Architecture::Mips
+            | Architecture::Mips64
+            | Architecture::Mips64n32
+            | Architecture::Ppc
+            | Architecture::Ppc64
+            | Architecture::S390
+            | Architecture::S390X
+            | Architecture::Parisc
+            | Architecture::Parisc64 => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the number of the system call `name`, or `None` if it or the architecture is
+    /// unknown.
+    fn syscall_number(self, name: &str) -> Option<u32> {
+        let table = match self {
+            Architecture::X86 => syscalls::X86,
+            Architecture::X86_64 => syscalls::X86_64,
+            Architecture::X32 => syscalls::X32,
+            Architecture::Arm => syscalls::ARM,
+            Architecture::Aarch64 => syscalls::AARCH64,
+            Architecture::Mips | Architecture::Mipsel => syscalls::MIPS,
+            Architecture::Mips64 | Architecture::Mipsel64 => syscalls::MIPS64,
+            Architecture::Ppc64 | Architecture::Ppc64le => syscalls::PPC64,
+            Architecture::S390X => syscalls::S390X,
+            _ => return None,
+        };
+        table
+            .binary_search_by_key(&name, |&(n, _)| n)
+            .ok()
+            .map(|i| table[i].1)
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::NoArchitecture => f.write_str("No supported architecture"),
+            CompileError::InvalidArgIndex(index) => write!(f, "Invalid argument index: {}", index),
+            CompileError::TooManyArgs(names) => {
+                write!(f, "Too many argument conditions for {}", names.join(", "))
+            }
+            CompileError::TooLarge(len) => {
+                write!(
+                    f,
+                    "Program has {} instructions, exceeding {}",
+                    len, BPF_MAXINSNS
+                )
+            }
+        }
+    }
+}
+
+impl Error for CompileError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs a seccomp filter against a system call, and returns the return value of the filter.
+    fn run(prog: &[sock_filter], arch: Architecture, nr: u32, args: &[u64]) -> u32 {
+        let big_endian = arch.big_endian();
+        let mut data = [0u8; 64];
+        let mut put = |offset: usize, bytes: &[u8]| {
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+        let u32_bytes = |v: u32| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        put(0, &u32_bytes(nr));
+        put(4, &u32_bytes(arch.audit_arch().unwrap()));
+        for (i, &arg) in args.iter().enumerate() {
+            let bytes = if big_endian {
+                arg.to_be_bytes()
+            } else {
+                arg.to_le_bytes()
+            };
+            put(16 + 8 * i, &bytes);
+        }
+
+        let mut a = 0u32;
+        let mut pc = 0;
+        loop {
+            let insn = prog[pc];
+            pc += 1;
+            match insn.code {
+                c if c == BPF_LD | BPF_W | BPF_ABS => {
+                    let k = insn.k as usize;
+                    let mut bytes = [0u8; 4];
+                    bytes.copy_from_slice(&data[k..k + 4]);
+                    a = if big_endian {
+                        u32::from_be_bytes(bytes)
+                    } else {
+                        u32::from_le_bytes(bytes)
+                    };
+                }
+                c if c == BPF_ALU | BPF_AND | BPF_K => a &= insn.k,
+                c if c == BPF_JMP | BPF_JA => pc += insn.k as usize,
+                c if c & 0x07 == BPF_JMP => {
+                    let cond = match c & 0xf0 {
+                        BPF_JEQ => a == insn.k,
+                        BPF_JGT => a > insn.k,
+                        BPF_JGE => a >= insn.k,
+                        _ => panic!("unexpected jump: {:#x}", c),
+                    };
+                    pc += usize::from(if cond { insn.jt } else { insn.jf });
+                }
+                c if c == BPF_RET | BPF_K => return insn.k,
+                c => panic!("unexpected instruction: {:#x}", c),
+            }
+        }
+    }
+
+    fn nr(arch: Architecture, name: &str) -> u32 {
+        arch.syscall_number(name).unwrap()
+    }
+
+    fn syscall(names: &[&str], action: Action, args: Vec<SyscallArg>) -> Syscall {
+        Syscall {
+            names: names.iter().map(|n| n.to_string()).collect(),
+            action,
+            errno_ret: None,
+            args,
+        }
+    }
+
+    fn seccomp(
+        default_action: Action,
+        architectures: Vec<Architecture>,
+        syscalls: Vec<Syscall>,
+    ) -> Seccomp {
+        Seccomp {
+            default_action,
+            default_errno_ret: None,
+            architectures,
+            flags: vec![],
+            listener_path: None,
+            listener_metadata: None,
+            syscalls,
+        }
+    }
+
+    fn arg(index: usize, op: SyscallCmp, value: u64, value_two: Option<u64>) -> SyscallArg {
+        SyscallArg {
+            index,
+            value,
+            value_two,
+            op,
+        }
+    }
+
+    const ALLOW: u32 = SECCOMP_RET_ALLOW;
+    const EPERM: u32 = SECCOMP_RET_ERRNO | libc::EPERM as u32;
+
+    #[test]
+    fn test_compile() {
+        let seccomp = seccomp(
+            Action::Errno,
+            vec![
+                Architecture::X86_64,
+                Architecture::X86,
+                Architecture::Aarch64,
+                // Skipped, as its system call numbers are unknown
+                Architecture::Parisc,
+            ],
+            vec![
+                syscall(
+                    &["read", "write", "open", "no_such_call"],
+                    Action::Allow,
+                    vec![],
+                ),
+                syscall(&["kill"], Action::Trap, vec![]),
+            ],
+        );
+        let prog = seccomp.compile().unwrap();
+
+        for &arch in &[Architecture::X86_64, Architecture::X86] {
+            assert_eq!(run(&prog, arch, nr(arch, "read"), &[]), ALLOW);
+            assert_eq!(run(&prog, arch, nr(arch, "open"), &[]), ALLOW);
+            assert_eq!(run(&prog, arch, nr(arch, "kill"), &[]), SECCOMP_RET_TRAP);
+            assert_eq!(run(&prog, arch, nr(arch, "mount"), &[]), EPERM);
+        }
+
+        // open is not available on AArch64
+        let arch = Architecture::Aarch64;
+        assert_eq!(run(&prog, arch, nr(arch, "write"), &[]), ALLOW);
+        assert_eq!(run(&prog, arch, nr(arch, "openat"), &[]), EPERM);
+
+        // Other architectures and x32 system calls are killed
+        let arch = Architecture::Arm;
+        assert_eq!(
+            run(&prog, arch, nr(arch, "read"), &[]),
+            SECCOMP_RET_KILL_THREAD
+        );
+        let x32_read = nr(Architecture::X32, "read");
+        let ret = run(&prog, Architecture::X86_64, x32_read, &[]);
+        assert_eq!(ret, SECCOMP_RET_KILL_THREAD);
+    }
+
+    #[test]
+    fn test_compile_x32() {
+        let seccomp = seccomp(
+            Action::Kill,
+            vec![Architecture::X86_64, Architecture::X32],
+            vec![syscall(&["getpid"], Action::Allow, vec![])],
+        );
+        let prog = seccomp.compile().unwrap();
+
+        for &arch in &[Architecture::X86_64, Architecture::X32] {
+            assert_eq!(run(&prog, arch, nr(arch, "getpid"), &[]), ALLOW);
+            assert_eq!(run(&prog, arch, nr(arch, "getppid"), &[]), 0);
+        }
+    }
+
+    #[test]
+    fn test_compile_args() {
+        let big = 0x1_0000_0005;
+        let test_cases = vec![
+            (SyscallCmp::Eq, vec![(5, false), (big, true), (6, false)]),
+            (SyscallCmp::Ne, vec![(5, true), (big, false), (6, true)]),
+            (
+                SyscallCmp::Gt,
+                vec![
+                    (big - 1, false),
+                    (big, false),
+                    (big + 1, true),
+                    (std::u64::MAX, true),
+                ],
+            ),
+            (
+                SyscallCmp::Ge,
+                vec![(5, false), (big, true), (big + 1, true)],
+            ),
+            (
+                SyscallCmp::Lt,
+                vec![(5, true), (big - 1, true), (big, false), (big + 1, false)],
+            ),
+            (
+                SyscallCmp::Le,
+                vec![(5, true), (big, true), (big + 1, false)],
+            ),
+        ];
+
+        for &arch in &[Architecture::X86_64, Architecture::S390X] {
+            for (op, cases) in &test_cases {
+                let seccomp = seccomp(
+                    Action::Errno,
+                    vec![arch],
+                    vec![syscall(
+                        &["ioctl"],
+                        Action::Allow,
+                        vec![arg(2, *op, big, None)],
+                    )],
+                );
+                let prog = seccomp.compile().unwrap();
+
+                for &(value, allowed) in cases {
+                    let ret = run(&prog, arch, nr(arch, "ioctl"), &[0, 0, value]);
+                    let expected = if allowed { ALLOW } else { EPERM };
+                    assert_eq!(ret, expected, "{:?} {:?} {:#x}", arch, op, value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compile_masked_args() {
+        let arch = Architecture::X86_64;
+        let seccomp = seccomp(
+            Action::Errno,
+            vec![],
+            vec![
+                // Both conditions must be satisfied
+                syscall(
+                    &["clone"],
+                    Action::Allow,
+                    vec![
+                        arg(0, SyscallCmp::MaskedEq, 0x7e02_0000, Some(0)),
+                        arg(1, SyscallCmp::Eq, 0, None),
+                    ],
+                ),
+                // Either condition is enough, as the index is repeated
+                syscall(
+                    &["personality"],
+                    Action::Allow,
+                    vec![
+                        arg(0, SyscallCmp::Eq, 0, None),
+                        arg(0, SyscallCmp::Eq, 8, None),
+                    ],
+                ),
+            ],
+        );
+        let prog = seccomp.compile().unwrap();
+
+        let clone = nr(arch, "clone");
+        assert_eq!(run(&prog, arch, clone, &[0x11, 0]), ALLOW);
+        assert_eq!(run(&prog, arch, clone, &[0x1000_0011, 0]), EPERM);
+        assert_eq!(run(&prog, arch, clone, &[0x11, 1]), EPERM);
+
+        let personality = nr(arch, "personality");
+        assert_eq!(run(&prog, arch, personality, &[0]), ALLOW);
+        assert_eq!(run(&prog, arch, personality, &[8]), ALLOW);
+        assert_eq!(run(&prog, arch, personality, &[1]), EPERM);
+    }
+
+    #[test]
+    fn test_compile_long_run() {
+        let arch = Architecture::X86_64;
+        let names: Vec<&str> = syscalls::X86_64.iter().map(|&(n, _)| n).collect();
+        let seccomp = seccomp(
+            Action::Kill,
+            vec![],
+            vec![syscall(&names, Action::Allow, vec![])],
+        );
+        let prog = seccomp.compile().unwrap();
+
+        for &(_, nr) in syscalls::X86_64 {
+            assert_eq!(run(&prog, arch, nr, &[]), ALLOW);
+        }
+        assert_eq!(run(&prog, arch, 1000, &[]), SECCOMP_RET_KILL_THREAD);
+    }
+
+    #[test]
+    fn test_compile_actions() {
+        let arch = Architecture::X86_64;
+        let mut seccomp = seccomp(
+            Action::Errno,
+            vec![],
+            vec![
+                syscall(&["clone3"], Action::Errno, vec![]),
+                syscall(&["ptrace"], Action::Log, vec![]),
+                syscall(&["reboot"], Action::KillProcess, vec![]),
+                syscall(&["getpid"], Action::Notify, vec![]),
+            ],
+        );
+        seccomp.default_errno_ret = Some(libc::EACCES as u32);
+        seccomp.syscalls[0].errno_ret = Some(libc::ENOSYS as u32);
+        let prog = seccomp.compile().unwrap();
+
+        let ret = |name| run(&prog, arch, nr(arch, name), &[]);
+        assert_eq!(ret("clone3"), SECCOMP_RET_ERRNO | libc::ENOSYS as u32);
+        assert_eq!(ret("ptrace"), SECCOMP_RET_LOG);
+        assert_eq!(ret("reboot"), SECCOMP_RET_KILL_PROCESS);
+        assert_eq!(ret("getpid"), SECCOMP_RET_USER_NOTIF);
+        assert_eq!(ret("mount"), SECCOMP_RET_ERRNO | libc::EACCES as u32);
+    }
+
+    #[test]
+    fn test_compile_default_profile() {
+        let arch = Architecture::X86_64;
+        let seccomp = Seccomp::default_profile(&Default::default(), arch);
+        let prog = seccomp.compile().unwrap();
+
+        let clone = nr(arch, "clone");
+        let clone_newuser = libc::CLONE_NEWUSER as u64;
+        assert_eq!(run(&prog, arch, nr(arch, "read"), &[]), ALLOW);
+        assert_eq!(run(&prog, arch, nr(arch, "mount"), &[]), EPERM);
+        assert_eq!(run(&prog, arch, clone, &[0x11]), ALLOW);
+        assert_eq!(run(&prog, arch, clone, &[clone_newuser]), EPERM);
+        let socket = nr(arch, "socket");
+        assert_eq!(run(&prog, arch, socket, &[libc::AF_INET as u64]), ALLOW);
+        assert_eq!(run(&prog, arch, socket, &[libc::AF_VSOCK as u64]), EPERM);
+        let ret = run(&prog, arch, nr(arch, "clone3"), &[]);
+        assert_eq!(ret, SECCOMP_RET_ERRNO | libc::ENOSYS as u32);
+
+        let arch = Architecture::X86;
+        assert_eq!(run(&prog, arch, nr(arch, "socketcall"), &[]), ALLOW);
+    }
+
+    #[test]
+    fn test_compile_default_profile_all_architectures() {
+        use Architecture::*;
+
+        let arches = [
+            X86,
+            X86_64,
+            X32,
+            Arm,
+            Aarch64,
+            Mips,
+            Mips64,
+            Mips64n32,
+            Mipsel,
+            Mipsel64,
+            Mipsel64n32,
+            Ppc,
+            Ppc64,
+            Ppc64le,
+            S390,
+            S390X,
+            Parisc,
+            Parisc64,
+        ];
+        for &arch in &arches {
+            let seccomp = Seccomp::default_profile(&Default::default(), arch);
+            assert!(seccomp.compile().is_ok(), "{:?}", arch);
+        }
+    }
+
+    #[test]
+    fn err_compile() {
+        let mut seccomp = seccomp(Action::Allow, vec![], vec![]);
+        seccomp.syscalls = vec![syscall(
+            &["read"],
+            Action::Kill,
+            vec![arg(6, SyscallCmp::Eq, 0, None)],
+        )];
+        assert_eq!(
+            seccomp.compile().unwrap_err(),
+            CompileError::InvalidArgIndex(6)
+        );
+    }
+}
@@ -4,6 +4,14 @@
 //!
 //! [kernel docs]: https://www.kernel.org/doc/Documentation/prctl/seccomp_filter.txt
 
+mod bpf;
+mod profile;
+mod syscalls;
+
+pub use bpf::CompileError;
+
+use std::path::PathBuf;
+
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
@@ -18,6 +26,10 @@ pub struct Seccomp {
     /// Default action for seccomp.
     pub default_action: Action,
 
+    /// Errno returned by the default action `Errno` or `Trace`. Unset values mean `EPERM`.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub default_errno_ret: Option<u32>,
+
     /// Architectures used for system calls.
     #[cfg_attr(
         feature = "serde",
@@ -25,6 +37,22 @@ pub struct Seccomp {
     )]
     pub architectures: Vec<Architecture>,
 
+    /// Flags passed to seccomp(2).
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip_serializing_if = "Vec::is_empty", default)
+    )]
+    pub flags: Vec<Flag>,
+
+    /// Path of the UNIX socket to which the runtime sends the seccomp notify file descriptor,
+    /// required by the action `Notify`.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub listener_path: Option<PathBuf>,
+
+    /// Opaque data sent to the listener with the seccomp notify file descriptor.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub listener_metadata: Option<String>,
+
     /// List of system call filters.
     #[cfg_attr(
         feature = "serde",
@@ -48,6 +76,14 @@ pub enum Action {
     #[cfg_attr(feature = "serde", serde(rename = "SCMP_ACT_KILL"))]
     Kill,
 
+    /// `SCMP_ACT_KILL_PROCESS`
+    #[cfg_attr(feature = "serde", serde(rename = "SCMP_ACT_KILL_PROCESS"))]
+    KillProcess,
+
+    /// `SCMP_ACT_KILL_THREAD`
+    #[cfg_attr(feature = "serde", serde(rename = "SCMP_ACT_KILL_THREAD"))]
+    KillThread,
+
     /// `SCMP_ACT_TRAP`
     #[cfg_attr(feature = "serde", serde(rename = "SCMP_ACT_TRAP"))]
     Trap,
@@ -63,6 +99,45 @@ pub enum Action {
     /// `SCMP_ACT_ALLOW`
     #[cfg_attr(feature = "serde", serde(rename = "SCMP_ACT_ALLOW"))]
     Allow,
+
+    /// `SCMP_ACT_LOG`
+    #[cfg_attr(feature = "serde", serde(rename = "SCMP_ACT_LOG"))]
+    Log,
+
+    /// `SCMP_ACT_NOTIFY`
+    #[cfg_attr(feature = "serde", serde(rename = "SCMP_ACT_NOTIFY"))]
+    Notify,
+}
+
+/// Flags for seccomp(2).
+///
+/// When the feature `serde` is enabled, `Flag` can be serialized to / deserialized from a flag
+/// name as defined in seccomp(2) (e.g. `SECCOMP_FILTER_FLAG_LOG`).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Flag {
+    /// `SECCOMP_FILTER_FLAG_TSYNC`
+    #[cfg_attr(feature = "serde", serde(rename = "SECCOMP_FILTER_FLAG_TSYNC"))]
+    Tsync,
+
+    /// `SECCOMP_FILTER_FLAG_LOG`
+    #[cfg_attr(feature = "serde", serde(rename = "SECCOMP_FILTER_FLAG_LOG"))]
+    Log,
+
+    /// `SECCOMP_FILTER_FLAG_SPEC_ALLOW`
+    #[cfg_attr(feature = "serde", serde(rename = "SECCOMP_FILTER_FLAG_SPEC_ALLOW"))]
+    SpecAllow,
+}
+
+impl Flag {
+    /// Returns the value of the flag for seccomp(2).
+    pub fn bits(self) -> u32 {
+        match self {
+            Flag::Tsync => 1,
+            Flag::Log => 2,
+            Flag::SpecAllow => 4,
+        }
+    }
 }
 
 /// List of architectures used for system calls.
@@ -151,7 +226,11 @@ pub enum Architecture {
 
 /// List of system call filters in seccomp.
 #[derive(Debug, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
 pub struct Syscall {
     /// Names of the syscalls.
     pub names: Vec<String>,
@@ -159,6 +238,10 @@ pub struct Syscall {
     /// Action for the seccomp rules.
     pub action: Action,
 
+    /// Errno returned by the action `Errno` or `Trace`. Unset values mean `EPERM`.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub errno_ret: Option<u32>,
+
     /// System call filter.
     #[cfg_attr(
         feature = "serde",
@@ -228,3 +311,72 @@ pub enum SyscallCmp {
     #[cfg_attr(feature = "serde", serde(rename = "SCMP_CMP_MASKED_EQ"))]
     MaskedEq,
 }
+
+#[cfg(all(feature = "serde", test))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seccomp_deser() {
+        // Excerpt of the default profile of Docker, which has extra fields
+        const JSON: &str = r#"{
+            "defaultAction": "SCMP_ACT_ERRNO",
+            "defaultErrnoRet": 1,
+            "archMap": [
+                {
+                    "architecture": "SCMP_ARCH_X86_64",
+                    "subArchitectures": ["SCMP_ARCH_X86", "SCMP_ARCH_X32"]
+                }
+            ],
+            "flags": ["SECCOMP_FILTER_FLAG_LOG"],
+            "listenerPath": "/run/seccomp-agent.socket",
+            "listenerMetadata": "profile=default",
+            "syscalls": [
+                {
+                    "names": ["clone3"],
+                    "action": "SCMP_ACT_ERRNO",
+                    "errnoRet": 38,
+                    "excludes": {"caps": ["CAP_SYS_ADMIN"]}
+                },
+                {
+                    "names": ["ptrace"],
+                    "action": "SCMP_ACT_LOG",
+                    "comment": "",
+                    "includes": {"minKernel": "4.8"}
+                },
+                {
+                    "names": ["reboot"],
+                    "action": "SCMP_ACT_KILL_PROCESS"
+                }
+            ]
+        }"#;
+
+        let seccomp: Seccomp = serde_json::from_str(JSON).unwrap();
+
+        assert_eq!(seccomp.default_action, Action::Errno);
+        assert_eq!(seccomp.default_errno_ret, Some(1));
+        assert!(seccomp.architectures.is_empty());
+        assert_eq!(seccomp.flags, vec![Flag::Log]);
+        assert_eq!(
+            seccomp.listener_path,
+            Some(PathBuf::from("/run/seccomp-agent.socket"))
+        );
+        assert_eq!(
+            seccomp.listener_metadata,
+            Some(String::from("profile=default"))
+        );
+        let actions: Vec<_> = seccomp
+            .syscalls
+            .iter()
+            .map(|s| (s.action, s.errno_ret))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                (Action::Errno, Some(38)),
+                (Action::Log, None),
+                (Action::KillProcess, None),
+            ]
+        );
+    }
+}
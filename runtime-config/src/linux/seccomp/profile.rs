@@ -0,0 +1,665 @@
+//! Default seccomp profile of Docker.
+//!
+//! The profile follows [the default profile of Moby], which allows system calls needed by common
+//! workloads and those guarded by the capabilities granted to the container.
+//!
+//! [the default profile of Moby]: https://github.com/moby/moby/blob/master/profiles/seccomp/default.json
+
+use crate::config::{Capabilities, Capability};
+
+use super::{Action, Architecture, Seccomp, Syscall, SyscallArg, SyscallCmp};
+
+/// `EPERM`, returned by the default action.
+const EPERM: u32 = 1;
+/// `ENOSYS`, returned for `clone3` so that callers fall back to `clone`.
+const ENOSYS: u32 = 38;
+/// `AF_VSOCK`, which is not namespaced.
+const AF_VSOCK: u64 = 40;
+/// `CLONE_NEWNS | CLONE_NEWCGROUP | CLONE_NEWUTS | CLONE_NEWIPC | CLONE_NEWUSER | CLONE_NEWPID |
+/// CLONE_NEWNET`.
+const CLONE_NAMESPACES: u64 = 0x7e02_0000;
+/// Personalities which are allowed: `PER_LINUX`, `PER_LINUX32`, `UNAME26`,
+/// `PER_LINUX32 | UNAME26`, and `0xffffffff` to query the current one.
+const PERSONALITIES: &[u64] = &[0x0, 0x8, 0x2_0000, 0x2_0008, 0xffff_ffff];
+
+/// System calls allowed regardless of capabilities and architectures.
+const ALLOWED: &[&str] = &[
+    "accept",
+    "accept4",
+    "access",
+    "adjtimex",
+    "alarm",
+    "bind",
+    "brk",
+    "cachestat",
+    "capget",
+    "capset",
+    "chdir",
+    "chmod",
+    "chown",
+    "chown32",
+    "clock_adjtime",
+    "clock_adjtime64",
+    "clock_getres",
+    "clock_getres_time64",
+    "clock_gettime",
+    "clock_gettime64",
+    "clock_nanosleep",
+    "clock_nanosleep_time64",
+    "close",
+    "close_range",
+    "connect",
+    "copy_file_range",
+    "creat",
+    "dup",
+    "dup2",
+    "dup3",
+    "epoll_create",
+    "epoll_create1",
+    "epoll_ctl",
+    "epoll_ctl_old",
+    "epoll_pwait",
+    "epoll_pwait2",
+    "epoll_wait",
+    "epoll_wait_old",
+    "eventfd",
+    "eventfd2",
+    "execve",
+    "execveat",
+    "exit",
+    "exit_group",
+    "faccessat",
+    "faccessat2",
+    "fadvise64",
+    "fadvise64_64",
+    "fallocate",
+    "fanotify_mark",
+    "fchdir",
+    "fchmod",
+    "fchmodat",
+    "fchmodat2",
+    "fchown",
+    "fchown32",
+    "fchownat",
+    "fcntl",
+    "fcntl64",
+    "fdatasync",
+    "fgetxattr",
+    "flistxattr",
+    "flock",
+    "fork",
+    "fremovexattr",
+    "fsetxattr",
+    "fstat",
+    "fstat64",
+    "fstatat64",
+    "fstatfs",
+    "fstatfs64",
+    "fsync",
+    "ftruncate",
+    "ftruncate64",
+    "futex",
+    "futex_requeue",
+    "futex_time64",
+    "futex_wait",
+    "futex_waitv",
+    "futex_wake",
+    "futimesat",
+    "getcpu",
+    "getcwd",
+    "getdents",
+    "getdents64",
+    "getegid",
+    "getegid32",
+    "geteuid",
+    "geteuid32",
+    "getgid",
+    "getgid32",
+    "getgroups",
+    "getgroups32",
+    "getitimer",
+    "getpeername",
+    "getpgid",
+    "getpgrp",
+    "getpid",
+    "getppid",
+    "getpriority",
+    "getrandom",
+    "getresgid",
+    "getresgid32",
+    "getresuid",
+    "getresuid32",
+    "getrlimit",
+    "get_robust_list",
+    "getrusage",
+    "getsid",
+    "getsockname",
+    "getsockopt",
+    "get_thread_area",
+    "gettid",
+    "gettimeofday",
+    "getuid",
+    "getuid32",
+    "getxattr",
+    "inotify_add_watch",
+    "inotify_init",
+    "inotify_init1",
+    "inotify_rm_watch",
+    "io_cancel",
+    "ioctl",
+    "io_destroy",
+    "io_getevents",
+    "io_pgetevents",
+    "io_pgetevents_time64",
+    "ioprio_get",
+    "ioprio_set",
+    "io_setup",
+    "io_submit",
+    "ipc",
+    "kill",
+    "landlock_add_rule",
+    "landlock_create_ruleset",
+    "landlock_restrict_self",
+    "lchown",
+    "lchown32",
+    "lgetxattr",
+    "link",
+    "linkat",
+    "listen",
+    "listxattr",
+    "llistxattr",
+    "_llseek",
+    "lremovexattr",
+    "lseek",
+    "lsetxattr",
+    "lstat",
+    "lstat64",
+    "madvise",
+    "map_shadow_stack",
+    "membarrier",
+    "memfd_create",
+    "memfd_secret",
+    "mincore",
+    "mkdir",
+    "mkdirat",
+    "mknod",
+    "mknodat",
+    "mlock",
+    "mlock2",
+    "mlockall",
+    "mmap",
+    "mmap2",
+    "mprotect",
+    "mq_getsetattr",
+    "mq_notify",
+    "mq_open",
+    "mq_timedreceive",
+    "mq_timedreceive_time64",
+    "mq_timedsend",
+    "mq_timedsend_time64",
+    "mq_unlink",
+    "mremap",
+    "msgctl",
+    "msgget",
+    "msgrcv",
+    "msgsnd",
+    "msync",
+    "munlock",
+    "munlockall",
+    "munmap",
+    "name_to_handle_at",
+    "nanosleep",
+    "newfstatat",
+    "_newselect",
+    "open",
+    "openat",
+    "openat2",
+    "pause",
+    "pidfd_open",
+    "pidfd_send_signal",
+    "pipe",
+    "pipe2",
+    "pkey_alloc",
+    "pkey_free",
+    "pkey_mprotect",
+    "poll",
+    "ppoll",
+    "ppoll_time64",
+    "prctl",
+    "pread64",
+    "preadv",
+    "preadv2",
+    "prlimit64",
+    "process_mrelease",
+    "pselect6",
+    "pselect6_time64",
+    "pwrite64",
+    "pwritev",
+    "pwritev2",
+    "read",
+    "readahead",
+    "readlink",
+    "readlinkat",
+    "readv",
+    "recv",
+    "recvfrom",
+    "recvmmsg",
+    "recvmmsg_time64",
+    "recvmsg",
+    "remap_file_pages",
+    "removexattr",
+    "rename",
+    "renameat",
+    "renameat2",
+    "restart_syscall",
+    "rmdir",
+    "rseq",
+    "rt_sigaction",
+    "rt_sigpending",
+    "rt_sigprocmask",
+    "rt_sigqueueinfo",
+    "rt_sigreturn",
+    "rt_sigsuspend",
+    "rt_sigtimedwait",
+    "rt_sigtimedwait_time64",
+    "rt_tgsigqueueinfo",
+    "sched_getaffinity",
+    "sched_getattr",
+    "sched_getparam",
+    "sched_get_priority_max",
+    "sched_get_priority_min",
+    "sched_getscheduler",
+    "sched_rr_get_interval",
+    "sched_rr_get_interval_time64",
+    "sched_setaffinity",
+    "sched_setattr",
+    "sched_setparam",
+    "sched_setscheduler",
+    "sched_yield",
+    "seccomp",
+    "select",
+    "semctl",
+    "semget",
+    "semop",
+    "semtimedop",
+    "semtimedop_time64",
+    "send",
+    "sendfile",
+    "sendfile64",
+    "sendmmsg",
+    "sendmsg",
+    "sendto",
+    "setfsgid",
+    "setfsgid32",
+    "setfsuid",
+    "setfsuid32",
+    "setgid",
+    "setgid32",
+    "setgroups",
+    "setgroups32",
+    "setitimer",
+    "setpgid",
+    "setpriority",
+    "setregid",
+    "setregid32",
+    "setresgid",
+    "setresgid32",
+    "setresuid",
+    "setresuid32",
+    "setreuid",
+    "setreuid32",
+    "setrlimit",
+    "set_robust_list",
+    "setsid",
+    "setsockopt",
+    "set_thread_area",
+    "set_tid_address",
+    "setuid",
+    "setuid32",
+    "setxattr",
+    "shmat",
+    "shmctl",
+    "shmdt",
+    "shmget",
+    "shutdown",
+    "sigaltstack",
+    "signalfd",
+    "signalfd4",
+    "sigprocmask",
+    "sigreturn",
+    "socketcall",
+    "socketpair",
+    "splice",
+    "stat",
+    "stat64",
+    "statfs",
+    "statfs64",
+    "statx",
+    "symlink",
+    "symlinkat",
+    "sync",
+    "sync_file_range",
+    "syncfs",
+    "sysinfo",
+    "tee",
+    "tgkill",
+    "time",
+    "timer_create",
+    "timer_delete",
+    "timer_getoverrun",
+    "timer_gettime",
+    "timer_gettime64",
+    "timer_settime",
+    "timer_settime64",
+    "timerfd_create",
+    "timerfd_gettime",
+    "timerfd_gettime64",
+    "timerfd_settime",
+    "timerfd_settime64",
+    "times",
+    "tkill",
+    "truncate",
+    "truncate64",
+    "ugetrlimit",
+    "umask",
+    "uname",
+    "unlink",
+    "unlinkat",
+    "utime",
+    "utimensat",
+    "utimensat_time64",
+    "utimes",
+    "vfork",
+    "vmsplice",
+    "wait4",
+    "waitid",
+    "waitpid",
+    "write",
+    "writev",
+    // Allowed since Linux 4.8, where they no longer bypass seccomp
+    "process_vm_readv",
+    "process_vm_writev",
+    "ptrace",
+];
+
+/// System calls allowed if the capability is in the bounding set.
+const CAPABILITY_ALLOWED: &[(Capability, &[&str])] = &[
+    (Capability::DacReadSearch, &["open_by_handle_at"]),
+    (
+        Capability::SysAdmin,
+        &[
+            "bpf",
+            "clone",
+            "clone3",
+            "fanotify_init",
+            "fsconfig",
+            "fsmount",
+            "fsopen",
+            "fspick",
+            "lookup_dcookie",
+            "mount",
+            "mount_setattr",
+            "move_mount",
+            "open_tree",
+            "perf_event_open",
+            "quotactl",
+            "quotactl_fd",
+            "setdomainname",
+            "sethostname",
+            "setns",
+            "syslog",
+            "umount",
+            "umount2",
+            "unshare",
+        ],
+    ),
+    (Capability::SysBoot, &["reboot"]),
+    (Capability::SysChroot, &["chroot"]),
+    (
+        Capability::SysModule,
+        &["delete_module", "init_module", "finit_module"],
+    ),
+    (Capability::SysPacct, &["acct"]),
+    (
+        Capability::SysPtrace,
+        &[
+            "kcmp",
+            "pidfd_getfd",
+            "process_madvise",
+            "process_vm_readv",
+            "process_vm_writev",
+            "ptrace",
+        ],
+    ),
+    (Capability::SysRawio, &["iopl", "ioperm"]),
+    (
+        Capability::SysTime,
+        &["settimeofday", "stime", "clock_settime", "clock_settime64"],
+    ),
+    (Capability::SysTtyConfig, &["vhangup"]),
+    (
+        Capability::SysNice,
+        &[
+            "get_mempolicy",
+            "mbind",
+            "set_mempolicy",
+            "set_mempolicy_home_node",
+        ],
+    ),
+    (Capability::Syslog, &["syslog"]),
+    (Capability::Bpf, &["bpf"]),
+    (Capability::Perfmon, &["perf_event_open"]),
+];
+
+impl Seccomp {
+    /// Returns the default seccomp profile of Docker for a container with `capabilities` on
+    /// `arch`.
+    ///
+    /// System calls are denied with `EPERM` unless allowed by the profile. Those guarded by a
+    /// capability are allowed only if the capability is in the bounding set, and `clone` is
+    /// restricted from creating namespaces without `CAP_SYS_ADMIN`. `architectures` consists of
+    /// `arch` and the architectures whose binaries can run on it.
+    ///
+    /// Unlike Docker, conditions on the kernel version are not evaluated, and system calls which
+    /// require Linux 4.8 are allowed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oci_runtime_config::{
+    ///     config::{Capabilities, Capability},
+    ///     linux::seccomp::{Action, Architecture, Seccomp},
+    /// };
+    ///
+    /// let capabilities = Capabilities {
+    ///     bounding: vec![Capability::SysChroot],
+    ///     ..Default::default()
+    /// };
+    /// let seccomp = Seccomp::default_profile(&capabilities, Architecture::X86_64);
+    ///
+    /// assert_eq!(seccomp.default_action, Action::Errno);
+    /// assert!(seccomp.syscalls.iter().any(|s| s.names == ["chroot"]));
+    /// assert!(!seccomp.syscalls.iter().any(|s| s.names.contains(&"mount".to_string())));
+    /// ```
+    pub fn default_profile(capabilities: &Capabilities, arch: Architecture) -> Seccomp {
+        let has = |cap: &Capability| capabilities.bounding.contains(cap);
+
+        let mut syscalls = vec![
+            allow(ALLOWED, vec![]),
+            allow(&["socket"], vec![arg(0, SyscallCmp::Ne, AF_VSOCK)]),
+            allow(
+                &["personality"],
+                PERSONALITIES
+                    .iter()
+                    .map(|&p| arg(0, SyscallCmp::Eq, p))
+                    .collect(),
+            ),
+        ];
+
+        let arch_allowed: &[&str] = match arch {
+            Architecture::Ppc64le => &["sync_file_range2", "swapcontext"],
+            Architecture::Arm | Architecture::Aarch64 => &[
+                "arm_fadvise64_64",
+                "arm_sync_file_range",
+                "sync_file_range2",
+                "breakpoint",
+                "cacheflush",
+                "set_tls",
+            ],
+            Architecture::X86_64 => &["arch_prctl", "modify_ldt"],
+            Architecture::X32 | Architecture::X86 => &["modify_ldt"],
+            Architecture::S390 | Architecture::S390X => &[
+                "s390_pci_mmio_read",
+                "s390_pci_mmio_write",
+                "s390_runtime_instr",
+            ],
+            _ => &[],
+        };
+        if !arch_allowed.is_empty() {
+            syscalls.push(allow(arch_allowed, vec![]));
+        }
+
+        for (cap, names) in CAPABILITY_ALLOWED {
+            if has(cap) {
+                syscalls.push(allow(names, vec![]));
+            }
+        }
+
+        if !has(&Capability::SysAdmin) {
+            // The flags are the second argument of clone on s390
+            let index = match arch {
+                Architecture::S390 | Architecture::S390X => 1,
+                _ => 0,
+            };
+            let flags = SyscallArg {
+                index,
+                value: CLONE_NAMESPACES,
+                value_two: Some(0),
+                op: SyscallCmp::MaskedEq,
+            };
+            syscalls.push(allow(&["clone"], vec![flags]));
+
+            // The flags of clone3 are in memory, and cannot be inspected
+            syscalls.push(Syscall {
+                names: vec![String::from("clone3")],
+                action: Action::Errno,
+                errno_ret: Some(ENOSYS),
+                args: vec![],
+            });
+        }
+
+        Seccomp {
+            default_action: Action::Errno,
+            default_errno_ret: Some(EPERM),
+            architectures: compatible_architectures(arch),
+            flags: vec![],
+            listener_path: None,
+            listener_metadata: None,
+            syscalls,
+        }
+    }
+}
+
+/// Returns `arch` and the architectures whose binaries can run on it.
+fn compatible_architectures(arch: Architecture) -> Vec<Architecture> {
+    use Architecture::*;
+
+    match arch {
+        X86_64 => vec![X86_64, X86, X32],
+        Aarch64 => vec![Aarch64, Arm],
+        Mips64 => vec![Mips64, Mips64n32, Mips],
+        Mips64n32 => vec![Mips64n32, Mips64, Mips],
+        Mipsel64 => vec![Mipsel64, Mipsel64n32, Mipsel],
+        Mipsel64n32 => vec![Mipsel64n32, Mipsel64, Mipsel],
+        S390X => vec![S390X, S390],
+        arch => vec![arch],
+    }
+}
+
+fn allow(names: &[&str], args: Vec<SyscallArg>) -> Syscall {
+    Syscall {
+        names: names.iter().map(|n| n.to_string()).collect(),
+        action: Action::Allow,
+        errno_ret: None,
+        args,
+    }
+}
+
+fn arg(index: usize, op: SyscallCmp, value: u64) -> SyscallArg {
+    SyscallArg {
+        index,
+        value,
+        value_two: None,
+        op,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(seccomp: &'a Seccomp, name: &str) -> Vec<&'a Syscall> {
+        seccomp
+            .syscalls
+            .iter()
+            .filter(|s| s.names.iter().any(|n| n == name))
+            .collect()
+    }
+
+    #[test]
+    fn test_default_profile() {
+        let seccomp = Seccomp::default_profile(&Capabilities::default(), Architecture::X86_64);
+
+        assert_eq!(seccomp.default_action, Action::Errno);
+        assert_eq!(seccomp.default_errno_ret, Some(EPERM));
+        assert_eq!(
+            seccomp.architectures,
+            vec![Architecture::X86_64, Architecture::X86, Architecture::X32]
+        );
+        assert_eq!(find(&seccomp, "read").len(), 1);
+        assert_eq!(find(&seccomp, "arch_prctl").len(), 1);
+        assert!(find(&seccomp, "mount").is_empty());
+        assert!(find(&seccomp, "reboot").is_empty());
+        assert!(find(&seccomp, "cacheflush").is_empty());
+
+        let clone = find(&seccomp, "clone");
+        assert_eq!(clone.len(), 1);
+        assert_eq!(clone[0].args[0].index, 0);
+        assert_eq!(clone[0].args[0].op, SyscallCmp::MaskedEq);
+
+        let clone3 = find(&seccomp, "clone3");
+        assert_eq!(clone3[0].action, Action::Errno);
+        assert_eq!(clone3[0].errno_ret, Some(ENOSYS));
+    }
+
+    #[test]
+    fn test_default_profile_capabilities() {
+        let capabilities = Capabilities {
+            bounding: vec![Capability::SysAdmin, Capability::SysBoot],
+            ..Default::default()
+        };
+        let seccomp = Seccomp::default_profile(&capabilities, Architecture::S390X);
+
+        assert_eq!(
+            seccomp.architectures,
+            vec![Architecture::S390X, Architecture::S390]
+        );
+        assert_eq!(find(&seccomp, "reboot").len(), 1);
+        assert_eq!(find(&seccomp, "s390_runtime_instr").len(), 1);
+        for name in &["mount", "clone", "clone3"] {
+            let syscalls = find(&seccomp, name);
+            assert_eq!(syscalls.len(), 1, "{}", name);
+            assert_eq!(syscalls[0].action, Action::Allow);
+            assert!(syscalls[0].args.is_empty());
+        }
+
+        // Capabilities other than the bounding set are not considered
+        let capabilities = Capabilities {
+            effective: vec![Capability::SysAdmin],
+            ..Default::default()
+        };
+        let seccomp = Seccomp::default_profile(&capabilities, Architecture::S390X);
+        assert!(find(&seccomp, "mount").is_empty());
+        assert_eq!(find(&seccomp, "clone")[0].args[0].index, 1);
+    }
+}
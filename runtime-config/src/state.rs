@@ -0,0 +1,276 @@
+//! Runtime state of containers.
+//!
+//! The state is returned by the `state` operation of a runtime, and is passed to hooks on their
+//! stdin. See [the runtime spec] for more information about the state and the lifecycle of
+//! containers.
+//!
+//! [the runtime spec]: https://github.com/opencontainers/runtime-spec/blob/v1.0.2/runtime.md
+
+use std::{collections::HashMap, error::Error, fmt, path::PathBuf};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// State of a container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct State {
+    /// Version of the OCI runtime spec.
+    pub oci_version: String,
+
+    /// Container's ID, which is unique across all containers on the host.
+    pub id: String,
+
+    /// Runtime state of the container.
+    pub status: Status,
+
+    /// ID of the container process, as seen by the host.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub pid: Option<u32>,
+
+    /// Absolute path to the container's bundle directory.
+    pub bundle: PathBuf,
+
+    /// Annotations of the container, taken from the config.
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip_serializing_if = "HashMap::is_empty", default)
+    )]
+    pub annotations: HashMap<String, String>,
+}
+
+/// Runtime state of a container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Status {
+    /// The container is being created.
+    Creating,
+    /// The container has been created, but the user-specified program has not been executed.
+    Created,
+    /// The container process has executed the user-specified program.
+    Running,
+    /// The container process has exited.
+    Stopped,
+}
+
+/// Operations and events which change the state of a container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// The `create` operation has finished creating the container.
+    Create,
+    /// The `start` operation executes the user-specified program.
+    Start,
+    /// The `kill` operation sends a signal to the container process.
+    Kill,
+    /// The container process has exited.
+    Exit,
+    /// The `delete` operation deletes the container.
+    Delete,
+}
+
+/// Error type that can be returned when an operation is not allowed in the current status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionError {
+    /// Status of the container.
+    pub status: Status,
+    /// Operation which is not allowed.
+    pub operation: Operation,
+}
+
+impl State {
+    /// Creates the state of a new container `id` with the bundle at `bundle`.
+    ///
+    /// The container is in the `creating` status, and has no process yet.
+    pub fn new<S: Into<String>, P: Into<PathBuf>>(id: S, bundle: P) -> Self {
+        State {
+            oci_version: crate::OCI_VERSION.to_string(),
+            id: id.into(),
+            status: Status::Creating,
+            pid: None,
+            bundle: bundle.into(),
+            annotations: Default::default(),
+        }
+    }
+
+    /// Applies `operation` to the status of this container.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use oci_runtime_config::state::{Operation, State, Status};
+    ///
+    /// let mut state = State::new("foo", "/containers/foo");
+    /// state.apply(Operation::Create).unwrap();
+    /// state.apply(Operation::Start).unwrap();
+    /// state.apply(Operation::Exit).unwrap();
+    ///
+    /// assert_eq!(state.status, Status::Stopped);
+    /// assert!(state.apply(Operation::Start).is_err());
+    /// ```
+    pub fn apply(&mut self, operation: Operation) -> Result<(), TransitionError> {
+        self.status = self.status.next(operation)?;
+        Ok(())
+    }
+}
+
+impl Status {
+    /// Returns the status after `operation`, or an error if `operation` is not allowed in this
+    /// status.
+    ///
+    /// The allowed transitions are:
+    ///
+    /// | Operation | From                  | To        |
+    /// |-----------|-----------------------|-----------|
+    /// | `Create`  | `Creating`            | `Created` |
+    /// | `Start`   | `Created`             | `Running` |
+    /// | `Kill`    | `Created`, `Running`  | unchanged |
+    /// | `Exit`    | `Created`, `Running`  | `Stopped` |
+    /// | `Delete`  | `Stopped`             | unchanged |
+    pub fn next(self, operation: Operation) -> Result<Status, TransitionError> {
+        use self::{Operation::*, Status::*};
+
+        match (operation, self) {
+            (Create, Creating) => Ok(Created),
+            (Start, Created) => Ok(Running),
+            (Kill, Created) | (Kill, Running) => Ok(self),
+            (Exit, Created) | (Exit, Running) => Ok(Stopped),
+            (Delete, Stopped) => Ok(Stopped),
+            _ => Err(TransitionError {
+                status: self,
+                operation,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Status::Creating => "creating",
+            Status::Created => "created",
+            Status::Running => "running",
+            Status::Stopped => "stopped",
+        })
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Create => "create",
+            Operation::Start => "start",
+            Operation::Kill => "kill",
+            Operation::Exit => "exit",
+            Operation::Delete => "delete",
+        })
+    }
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Cannot {} a container which is {}",
+            self.operation, self.status
+        )
+    }
+}
+
+impl Error for TransitionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let mut state = State::new("foo", "/containers/foo");
+        assert_eq!(state.status, Status::Creating);
+        assert_eq!(state.oci_version, crate::OCI_VERSION);
+
+        let test_cases = vec![
+            (Operation::Create, Status::Created),
+            (Operation::Kill, Status::Created),
+            (Operation::Start, Status::Running),
+            (Operation::Kill, Status::Running),
+            (Operation::Exit, Status::Stopped),
+            (Operation::Delete, Status::Stopped),
+        ];
+
+        for (operation, status) in test_cases {
+            state.apply(operation).unwrap();
+            assert_eq!(state.status, status);
+        }
+
+        // Killed before started
+        assert_eq!(Status::Created.next(Operation::Exit), Ok(Status::Stopped));
+    }
+
+    #[test]
+    fn err_apply() {
+        let test_cases = vec![
+            (Status::Creating, Operation::Start),
+            (Status::Creating, Operation::Delete),
+            (Status::Created, Operation::Create),
+            (Status::Created, Operation::Delete),
+            (Status::Running, Operation::Start),
+            (Status::Running, Operation::Delete),
+            (Status::Stopped, Operation::Start),
+            (Status::Stopped, Operation::Kill),
+        ];
+
+        for (status, operation) in test_cases {
+            let mut state = State::new("foo", "/containers/foo");
+            state.status = status;
+            let err = state.apply(operation).unwrap_err();
+            assert_eq!(err, TransitionError { status, operation });
+            assert_eq!(state.status, status);
+        }
+
+        assert_eq!(
+            Status::Stopped
+                .next(Operation::Start)
+                .unwrap_err()
+                .to_string(),
+            "Cannot start a container which is stopped"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_state_serde() {
+        const JSON_STATE: &str = r#"{
+            "ociVersion": "0.2.0",
+            "id": "oci-container1",
+            "status": "running",
+            "pid": 4422,
+            "bundle": "/containers/redis",
+            "annotations": {
+                "myKey": "myValue"
+            }
+        }"#;
+
+        let state: State = serde_json::from_str(JSON_STATE).unwrap();
+        assert_eq!(state.status, Status::Running);
+        assert_eq!(state.pid, Some(4422));
+        assert_eq!(state.annotations["myKey"], "myValue");
+        assert_eq!(
+            serde_json::to_value(&state).unwrap(),
+            serde_json::from_str::<serde_json::Value>(JSON_STATE).unwrap()
+        );
+
+        let state = State::new("foo", "/containers/foo");
+        assert_eq!(
+            serde_json::to_string(&state).unwrap(),
+            r#"{"ociVersion":"1.0.1","id":"foo","status":"creating","bundle":"/containers/foo"}"#
+        );
+    }
+}